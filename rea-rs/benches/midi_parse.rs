@@ -0,0 +1,129 @@
+//! Owned [MidiEventBuilder] against borrowed [MidiEventRefBuilder] on a
+//! dense CC take.
+//!
+//! Run with `cargo bench -p rea-rs --bench midi_parse`.
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, Criterion,
+};
+use rea_rs::midi::*;
+
+const EVENTS: u32 = 200_000;
+
+fn dense_take() -> Vec<u8> {
+    let mut writer = MidiBufWriter::with_capacity(EVENTS as usize * 12);
+    for idx in 0..EVENTS {
+        let (channel, shape) = match idx % 4 {
+            0 => (0xb0, CcShapeKind::Linear),
+            1 => (0xb1, CcShapeKind::SlowStartEnd),
+            _ => (0xb0 | (idx % 16) as u8, CcShapeKind::Square),
+        };
+        let msg = [channel, (idx % 32) as u8, (idx % 128) as u8];
+        writer.push_raw(idx * 10, false, false, shape, &msg);
+        if idx % 10 == 0 {
+            let note = (idx / 10 % 100) as u8;
+            writer.push_raw(idx * 10, false, false, shape, &[0x90, note, 90]);
+            writer.push_raw(idx * 10, false, false, shape, &[0x80, note, 0]);
+        }
+    }
+    writer.finish()
+}
+
+fn parse(c: &mut Criterion) {
+    let buf = dense_take();
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(20);
+    group.bench_function("owned", |b| {
+        b.iter_batched(
+            || buf.clone(),
+            |buf| MidiEventBuilder::new(buf.into_iter()).count(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| MidiEventRefBuilder::new(black_box(&buf)).count())
+    });
+    group.finish();
+}
+
+fn filter(c: &mut Criterion) {
+    let buf = dense_take();
+    let mut group = c.benchmark_group("filter");
+    group.sample_size(20);
+    group.bench_function("cc_owned", |b| {
+        b.iter_batched(
+            || buf.clone(),
+            |buf| {
+                MidiEventBuilder::new(buf.into_iter())
+                    .filter_cc()
+                    .map(|e| e.message().cc_val() as u64)
+                    .sum::<u64>()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("cc_borrowed", |b| {
+        b.iter(|| {
+            MidiEventRefBuilder::new(black_box(&buf))
+                .filter_cc()
+                .map(|e| e.data2().unwrap_or_default() as u64)
+                .sum::<u64>()
+        })
+    });
+    group.bench_function("notes_owned", |b| {
+        b.iter_batched(
+            || buf.clone(),
+            |buf| {
+                MidiEventBuilder::new(buf.into_iter())
+                    .filter_notes()
+                    .count()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("notes_borrowed", |b| {
+        b.iter(|| {
+            MidiEventRefBuilder::new(black_box(&buf))
+                .filter_notes()
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let buf = dense_take();
+    let events: Vec<_> =
+        MidiEventBuilder::new(buf.clone().into_iter()).collect();
+    let mut group = c.benchmark_group("write");
+    group.sample_size(20);
+    group.bench_function("consumer", |b| {
+        b.iter_batched(
+            || events.clone(),
+            |events| {
+                MidiEventConsumer::new(events.into_iter()).collect::<Vec<u8>>()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("writer", |b| {
+        b.iter(|| {
+            let mut writer = MidiBufWriter::with_capacity(buf.len());
+            for event in events.iter() {
+                writer.push(event);
+            }
+            writer.finish()
+        })
+    });
+    group.bench_function("writer_from_borrowed", |b| {
+        b.iter(|| {
+            let mut writer = MidiBufWriter::with_capacity(buf.len());
+            writer.extend(MidiEventRefBuilder::new(black_box(&buf)));
+            writer.finish()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, filter, write);
+criterion_main!(benches);
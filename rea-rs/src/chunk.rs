@@ -0,0 +1,703 @@
+//! Tree representation of RPP state chunks.
+//!
+//! State chunks ([crate::Track::chunk], [crate::Item::chunk],
+//! [crate::Envelope::state_chunk], `.rpp` files) are parsed into [Chunk]:
+//! a list of [ChunkNode], where `<NAME ...>` blocks hold their children,
+//! attribute lines are split to tokens and base64 (or `|`-prefixed text)
+//! lines are kept as [ChunkBlob].
+//!
+//! Every node remembers its source text, so serializing with
+//! [std::fmt::Display] reproduces unchanged parts byte-for-byte. Only
+//! modified nodes are written anew, with tokens quoted by the RPP rules.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::Chunk;
+//!
+//! let text = "<TRACK\n  NAME \"my track\"\n  VOLPAN 1 0 -1 -1 1\n>\n";
+//! let mut chunk: Chunk = text.parse().unwrap();
+//! assert_eq!(chunk.to_string(), text);
+//!
+//! let track = chunk.root_mut().unwrap();
+//! assert_eq!(track.attribute("NAME"), Some(&["my track".to_string()][..]));
+//! track.set_attribute("NAME", ["It's \"quoted\""]);
+//! track.set_attribute("SEL", ["1"]);
+//! assert_eq!(
+//!     chunk.to_string(),
+//!     "<TRACK\n  NAME `It's \"quoted\"`\n  VOLPAN 1 0 -1 -1 1\n  SEL 1\n>\n"
+//! );
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    Envelope, KnowsProject, Mutable, ProbablyMutable, ReaRsError,
+    ReaperResult, Track,
+};
+
+/// Blocks, which single-token lines are always base64 data.
+const BLOB_BLOCKS: [&str; 8] = [
+    "VST",
+    "AU",
+    "DX",
+    "LV2",
+    "CLAP",
+    "RENDER_CFG",
+    "RECORD_CFG",
+    "APPLY_CFG",
+];
+const INDENT: &str = "  ";
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// Split RPP line to tokens, removing quotes.
+///
+/// Token may be quoted by `"`, `'` or `` ` ``. There is no escaping:
+/// quoted token lasts until the same quote character.
+pub fn split_chunk_tokens(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let first = match chars.next() {
+            None => break,
+            Some(c) => c,
+        };
+        let mut token = String::new();
+        match QUOTES.contains(&first) {
+            true => {
+                for c in chars.by_ref() {
+                    if c == first {
+                        break;
+                    }
+                    token.push(c);
+                }
+            }
+            false => {
+                token.push(first);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    token.push(c);
+                }
+            }
+        }
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Quote token, if needed, by the RPP rules.
+///
+/// If token contains all three quote characters, backticks are replaced
+/// by `'`.
+pub fn quote_chunk_token(token: &str) -> String {
+    let needs_quotes = token.is_empty()
+        || token.contains(char::is_whitespace)
+        || token.starts_with(QUOTES);
+    if !needs_quotes {
+        return token.to_string();
+    }
+    match QUOTES.iter().find(|q| !token.contains(**q)) {
+        Some(q) => format!("{q}{token}{q}"),
+        None => format!("`{}`", token.replace('`', "'")),
+    }
+}
+
+/// Source text of a node line: without line ending.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Source {
+    text: Option<String>,
+    ending: String,
+}
+impl Source {
+    fn new(text: &str, ending: &str) -> Self {
+        Self {
+            text: Some(text.to_string()),
+            ending: ending.to_string(),
+        }
+    }
+    fn generated() -> Self {
+        Self {
+            text: None,
+            ending: "\n".to_string(),
+        }
+    }
+    fn write(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        depth: usize,
+        generate: impl FnOnce() -> String,
+    ) -> std::fmt::Result {
+        match &self.text {
+            Some(text) => write!(f, "{}{}", text, self.ending),
+            None => {
+                write!(
+                    f,
+                    "{}{}{}",
+                    INDENT.repeat(depth),
+                    generate(),
+                    self.ending
+                )
+            }
+        }
+    }
+}
+
+/// Attribute line: name and parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkLine {
+    tokens: Vec<String>,
+    source: Source,
+}
+impl ChunkLine {
+    pub fn new(
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let mut tokens = vec![name.into()];
+        tokens.extend(params.into_iter().map(|p| p.into()));
+        Self {
+            tokens,
+            source: Source::generated(),
+        }
+    }
+    pub fn name(&self) -> &str {
+        self.tokens.first().map(|t| t.as_str()).unwrap_or_default()
+    }
+    pub fn params(&self) -> &[String] {
+        self.tokens.get(1..).unwrap_or_default()
+    }
+    pub fn param(&self, index: usize) -> Option<&str> {
+        self.params().get(index).map(|p| p.as_str())
+    }
+    /// Set parameter. If there are less parameters, empty ones are added.
+    pub fn set_param(&mut self, index: usize, value: impl Into<String>) {
+        if self.tokens.len() < index + 2 {
+            self.tokens.resize(index + 2, String::new());
+        }
+        self.tokens[index + 1] = value.into();
+        self.source.text = None;
+    }
+    pub fn set_params(
+        &mut self,
+        params: impl IntoIterator<Item = impl Into<String>>,
+    ) {
+        self.tokens.truncate(1);
+        self.tokens.extend(params.into_iter().map(|p| p.into()));
+        self.source.text = None;
+    }
+    /// Whether line was changed since parsing (or is new).
+    pub fn is_modified(&self) -> bool {
+        self.source.text.is_none()
+    }
+    fn quoted(&self) -> String {
+        self.tokens
+            .iter()
+            .map(|t| quote_chunk_token(t))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Line of raw data: base64 or `|`-prefixed text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkBlob {
+    text: String,
+    source: Source,
+}
+impl ChunkBlob {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            source: Source::generated(),
+        }
+    }
+    /// Text without indentation.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.source.text = None;
+    }
+}
+
+/// `<NAME params ...>` block with children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkBlock {
+    header: ChunkLine,
+    children: Vec<ChunkNode>,
+    end: Source,
+}
+impl ChunkBlock {
+    pub fn new(
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            header: ChunkLine::new(name, params),
+            children: Vec::new(),
+            end: Source::generated(),
+        }
+    }
+    pub fn name(&self) -> &str {
+        self.header.name()
+    }
+    /// Header line, without `<`.
+    pub fn header(&self) -> &ChunkLine {
+        &self.header
+    }
+    pub fn header_mut(&mut self) -> &mut ChunkLine {
+        &mut self.header
+    }
+    pub fn params(&self) -> &[String] {
+        self.header.params()
+    }
+    pub fn param(&self, index: usize) -> Option<&str> {
+        self.header.param(index)
+    }
+
+    pub fn children(&self) -> &Vec<ChunkNode> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<ChunkNode> {
+        &mut self.children
+    }
+    pub fn push(&mut self, node: impl Into<ChunkNode>) {
+        self.children.push(node.into())
+    }
+    pub fn insert(&mut self, index: usize, node: impl Into<ChunkNode>) {
+        self.children.insert(index, node.into())
+    }
+
+    /// Child blocks.
+    pub fn blocks(&self) -> impl Iterator<Item = &ChunkBlock> {
+        self.children.iter().filter_map(|n| n.as_block())
+    }
+    pub fn blocks_mut(&mut self) -> impl Iterator<Item = &mut ChunkBlock> {
+        self.children.iter_mut().filter_map(|n| n.as_block_mut())
+    }
+    pub fn blocks_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ChunkBlock> {
+        self.blocks().filter(move |b| b.name() == name)
+    }
+    /// The first child block with the name.
+    pub fn block(&self, name: &str) -> Option<&ChunkBlock> {
+        self.blocks().find(|b| b.name() == name)
+    }
+    pub fn block_mut(&mut self, name: &str) -> Option<&mut ChunkBlock> {
+        self.blocks_mut().find(|b| b.name() == name)
+    }
+    /// Descend by names of nested blocks.
+    ///
+    /// Empty path gives the block itself.
+    pub fn find(&self, path: &[&str]) -> Option<&ChunkBlock> {
+        match path.split_first() {
+            None => Some(self),
+            Some((name, rest)) => self.block(name)?.find(rest),
+        }
+    }
+    pub fn find_mut(&mut self, path: &[&str]) -> Option<&mut ChunkBlock> {
+        match path.split_first() {
+            None => Some(self),
+            Some((name, rest)) => self.block_mut(name)?.find_mut(rest),
+        }
+    }
+
+    /// Child attribute lines.
+    pub fn lines(&self) -> impl Iterator<Item = &ChunkLine> {
+        self.children.iter().filter_map(|n| n.as_line())
+    }
+    pub fn lines_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ChunkLine> {
+        self.lines().filter(move |l| l.name() == name)
+    }
+    /// The first attribute line with the name.
+    pub fn line(&self, name: &str) -> Option<&ChunkLine> {
+        self.lines().find(|l| l.name() == name)
+    }
+    pub fn line_mut(&mut self, name: &str) -> Option<&mut ChunkLine> {
+        self.children
+            .iter_mut()
+            .filter_map(|n| n.as_line_mut())
+            .find(|l| l.name() == name)
+    }
+    /// Parameters of the first attribute line with the name.
+    pub fn attribute(&self, name: &str) -> Option<&[String]> {
+        self.line(name).map(|l| l.params())
+    }
+    /// Set parameters of the first attribute line with the name, or add
+    /// new line to the end of block.
+    pub fn set_attribute(
+        &mut self,
+        name: &str,
+        params: impl IntoIterator<Item = impl Into<String>>,
+    ) {
+        match self.line_mut(name) {
+            Some(line) => line.set_params(params),
+            None => self.push(ChunkLine::new(name, params)),
+        }
+    }
+    /// Remove all child lines and blocks with the name.
+    ///
+    /// Returns number of removed nodes.
+    pub fn remove_all(&mut self, name: &str) -> usize {
+        let len = self.children.len();
+        self.children.retain(|n| n.name() != Some(name));
+        len - self.children.len()
+    }
+
+    /// Child data lines, joined without separators.
+    pub fn blob_data(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|n| n.as_blob())
+            .map(|b| b.text())
+            .collect()
+    }
+    /// Replace data lines by `data`, split by lines of `line_length`.
+    ///
+    /// New lines are placed where the first old data line was, or to the
+    /// end of block.
+    pub fn set_blob_data(&mut self, data: &str, line_length: usize) {
+        let position = self
+            .children
+            .iter()
+            .position(|n| n.as_blob().is_some())
+            .unwrap_or(self.children.len());
+        self.children.retain(|n| n.as_blob().is_none());
+        let chars: Vec<char> = data.chars().collect();
+        let blobs = chars
+            .chunks(line_length.max(1))
+            .map(|c| ChunkNode::Blob(ChunkBlob::new(String::from_iter(c))));
+        self.children.splice(position..position, blobs);
+    }
+
+    fn write(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        depth: usize,
+    ) -> std::fmt::Result {
+        self.header
+            .source
+            .write(f, depth, || format!("<{}", self.header.quoted()))?;
+        for child in self.children.iter() {
+            child.write(f, depth + 1)?;
+        }
+        self.end.write(f, depth, || ">".to_string())
+    }
+}
+impl Display for ChunkBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// One element of [Chunk] tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkNode {
+    Block(ChunkBlock),
+    Line(ChunkLine),
+    Blob(ChunkBlob),
+}
+impl ChunkNode {
+    /// Name of block or line.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Block(b) => Some(b.name()),
+            Self::Line(l) => Some(l.name()),
+            Self::Blob(_) => None,
+        }
+    }
+    pub fn as_block(&self) -> Option<&ChunkBlock> {
+        match self {
+            Self::Block(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub fn as_block_mut(&mut self) -> Option<&mut ChunkBlock> {
+        match self {
+            Self::Block(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub fn as_line(&self) -> Option<&ChunkLine> {
+        match self {
+            Self::Line(l) => Some(l),
+            _ => None,
+        }
+    }
+    pub fn as_line_mut(&mut self) -> Option<&mut ChunkLine> {
+        match self {
+            Self::Line(l) => Some(l),
+            _ => None,
+        }
+    }
+    pub fn as_blob(&self) -> Option<&ChunkBlob> {
+        match self {
+            Self::Blob(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub(crate) fn write(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        depth: usize,
+    ) -> std::fmt::Result {
+        match self {
+            Self::Block(b) => b.write(f, depth),
+            Self::Line(l) => l.source.write(f, depth, || l.quoted()),
+            Self::Blob(b) => b.source.write(f, depth, || b.text.clone()),
+        }
+    }
+}
+impl From<ChunkBlock> for ChunkNode {
+    fn from(value: ChunkBlock) -> Self {
+        Self::Block(value)
+    }
+}
+impl From<ChunkLine> for ChunkNode {
+    fn from(value: ChunkLine) -> Self {
+        Self::Line(value)
+    }
+}
+impl From<ChunkBlob> for ChunkNode {
+    fn from(value: ChunkBlob) -> Self {
+        Self::Blob(value)
+    }
+}
+
+/// Parsed state chunk, usually with a single root block.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Chunk {
+    pub nodes: Vec<ChunkNode>,
+}
+impl Chunk {
+    pub fn new(root: ChunkBlock) -> Self {
+        Self {
+            nodes: vec![root.into()],
+        }
+    }
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        let mut stack: Vec<ChunkBlock> = Vec::new();
+        let mut nodes = Vec::new();
+        for (idx, (line, ending)) in split_lines(text).enumerate() {
+            let trimmed = line.trim();
+            let source = Source::new(line, ending);
+            if trimmed == ">" {
+                let mut block = stack.pop().ok_or_else(|| {
+                    ReaRsError::Parse(idx + 1, "unexpected '>'".to_string())
+                })?;
+                block.end = source;
+                match stack.last_mut() {
+                    Some(parent) => parent.push(block),
+                    None => nodes.push(block.into()),
+                }
+                continue;
+            }
+            if let Some(header) = trimmed.strip_prefix('<') {
+                let tokens = split_chunk_tokens(header);
+                stack.push(ChunkBlock {
+                    header: ChunkLine { tokens, source },
+                    children: Vec::new(),
+                    end: Source::default(),
+                });
+                continue;
+            }
+            let parent = stack.last().map(|b| b.name());
+            let node = match parse_line(trimmed, parent) {
+                Ok(tokens) => ChunkNode::Line(ChunkLine { tokens, source }),
+                Err(text) => ChunkNode::Blob(ChunkBlob { text, source }),
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.push(node),
+                None => nodes.push(node),
+            }
+        }
+        match stack.last() {
+            None => Ok(Self { nodes }),
+            Some(block) => Err(ReaRsError::Parse(
+                text.lines().count(),
+                format!("block <{} is not closed", block.name()),
+            )),
+        }
+    }
+    /// The first block of chunk.
+    pub fn root(&self) -> Option<&ChunkBlock> {
+        self.nodes.iter().find_map(|n| n.as_block())
+    }
+    pub fn root_mut(&mut self) -> Option<&mut ChunkBlock> {
+        self.nodes.iter_mut().find_map(|n| n.as_block_mut())
+    }
+}
+impl FromStr for Chunk {
+    type Err = ReaRsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+impl Display for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in self.nodes.iter() {
+            node.write(f, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lines with their endings (`\n`, `\r\n` or empty for the last line).
+fn split_lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split_inclusive('\n')
+        .map(|line| match line.strip_suffix("\r\n") {
+            Some(stripped) => (stripped, "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(stripped) => (stripped, "\n"),
+                None => (line, ""),
+            },
+        })
+}
+
+/// Tokens of attribute line, or text of data line.
+fn parse_line(
+    trimmed: &str,
+    parent: Option<&str>,
+) -> Result<Vec<String>, String> {
+    if trimmed.is_empty() || trimmed.starts_with('|') {
+        return Err(trimmed.to_string());
+    }
+    let tokens = split_chunk_tokens(trimmed);
+    let single_unquoted = tokens.len() == 1 && !trimmed.starts_with(QUOTES);
+    let is_blob = single_unquoted
+        && (parent.is_some_and(|p| BLOB_BLOCKS.contains(&p))
+            || !trimmed.chars().all(|c| {
+                c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'
+            }));
+    match is_blob {
+        true => Err(trimmed.to_string()),
+        false => Ok(tokens),
+    }
+}
+
+impl<'a, T: ProbablyMutable> Track<'a, T> {
+    /// [Track::chunk] as a tree.
+    pub fn chunk_tree(&self) -> ReaperResult<Chunk> {
+        self.chunk()?.parse()
+    }
+}
+impl<'a> Track<'a, Mutable> {
+    pub fn set_chunk_tree(
+        &mut self,
+        chunk: &Chunk,
+        need_undo: bool,
+    ) -> ReaperResult<()> {
+        self.set_chunk(chunk.to_string(), need_undo)
+    }
+}
+
+impl<'a, P: KnowsProject, T: ProbablyMutable> Envelope<'a, P, T> {
+    /// [Envelope::state_chunk] as a tree.
+    pub fn state_chunk_tree(&self) -> ReaperResult<Chunk> {
+        self.state_chunk().parse()
+    }
+}
+impl<'a, P: KnowsProject> Envelope<'a, P, Mutable> {
+    pub fn set_state_chunk_tree(
+        &mut self,
+        chunk: &Chunk,
+        with_undo: bool,
+    ) -> ReaperResult<()> {
+        self.set_state_chunk(chunk.to_string(), with_undo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACK: &str = include_str!("../fixtures/track.rpp");
+    const ENVELOPE: &str = include_str!("../fixtures/envelope.rpp");
+
+    #[test]
+    fn test_fixtures_round_trip() {
+        for text in [TRACK, ENVELOPE] {
+            let chunk = Chunk::parse(text).unwrap();
+            assert_eq!(chunk.to_string(), text);
+            let crlf = text.replace('\n', "\r\n");
+            assert_eq!(Chunk::parse(&crlf).unwrap().to_string(), crlf);
+        }
+        let chunk = Chunk::parse(ENVELOPE).unwrap();
+        let env = chunk.root().unwrap();
+        assert_eq!(env.name(), "PARMENV");
+        assert_eq!(env.params(), ["1:1", "0", "1", "0.5"]);
+        assert_eq!(env.lines_named("PT").count(), 3);
+        assert!(env.block("POOLEDENVINST").is_some());
+    }
+
+    #[test]
+    fn test_query_track() {
+        let chunk = Chunk::parse(TRACK).unwrap();
+        let track = chunk.root().unwrap();
+        assert_eq!(track.attribute("NAME").unwrap(), ["Lead 'synth'"]);
+        let item = track.block("ITEM").unwrap();
+        assert_eq!(item.attribute("NAME").unwrap(), ["It's a \"riff\""]);
+        let notes = item.block("NOTES").unwrap();
+        assert_eq!(notes.lines().count(), 0);
+        assert_eq!(
+            notes.blob_data(),
+            "|first line of notes|  second \"quoted\" line"
+        );
+        let source = track.find(&["ITEM", "SOURCE"]).unwrap();
+        assert_eq!(source.params(), ["MIDI"]);
+        assert_eq!(source.lines_named("E").count(), 3);
+        let vst = track.find(&["FXCHAIN", "VST"]).unwrap();
+        assert_eq!(vst.param(0), Some("VSTi: ReaSynth (Cockos)"));
+        assert_eq!(vst.param(2), Some("0"));
+        assert_eq!(vst.param(3), Some(""));
+        assert_eq!(vst.children().len(), 4);
+        assert!(vst.blob_data().ends_with("AAAQAAAA"));
+    }
+
+    #[test]
+    fn test_edit_keeps_untouched_lines() {
+        let mut chunk = Chunk::parse(TRACK).unwrap();
+        let track = chunk.root_mut().unwrap();
+        track.set_attribute("NAME", ["new name"]);
+        track.line_mut("VOLPAN").unwrap().set_param(0, "0.5");
+        assert_eq!(track.remove_all("ITEM"), 1);
+        let vst = track.find_mut(&["FXCHAIN", "VST"]).unwrap();
+        vst.set_blob_data("QUJDRA==", 4);
+        let text = chunk.to_string();
+        assert!(text.contains("\n  NAME \"new name\"\n"));
+        assert!(text.contains("\n  VOLPAN 0.5 0 -1 -1 1\n"));
+        assert!(text.contains("\n      QUJD\n      RA==\n    >\n"));
+        assert!(!text.contains("<ITEM"));
+        assert!(text.contains("\n    PT 2 0.5 5 1 0 0 -0.4\n"));
+        let parsed = Chunk::parse(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn test_quoting_and_errors() {
+        assert_eq!(quote_chunk_token("plain"), "plain");
+        assert_eq!(quote_chunk_token(""), "\"\"");
+        assert_eq!(quote_chunk_token("a b"), "\"a b\"");
+        assert_eq!(quote_chunk_token("\"a\""), "'\"a\"'");
+        assert_eq!(quote_chunk_token("a \"b' c"), "`a \"b' c`");
+        assert_eq!(quote_chunk_token("`a` \"b' c"), "`'a' \"b' c`");
+        assert_eq!(
+            split_chunk_tokens(r#"X "a b" 'c "d"' `e` f"#),
+            ["X", "a b", "c \"d\"", "e", "f"]
+        );
+        assert!(matches!(
+            Chunk::parse("<TRACK\n  NAME a\n"),
+            Err(ReaRsError::Parse(2, _))
+        ));
+        assert!(matches!(
+            Chunk::parse("<TRACK\n>\n>\n"),
+            Err(ReaRsError::Parse(3, _))
+        ));
+    }
+}
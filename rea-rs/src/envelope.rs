@@ -181,12 +181,43 @@ impl<'a, P: KnowsProject, T: ProbablyMutable> Envelope<'a, P, T> {
         self.get_point_ex(None, false, point_index)
     }
 
+    /// Point with its position.
+    pub fn get_point_with_position(
+        &self,
+        point_index: usize,
+    ) -> ReaperResult<(Position, EnvelopePoint)> {
+        self.get_point_with_position_ex(None, false, point_index)
+    }
+
+    /// All points with their positions.
+    pub fn points_with_positions(
+        &self,
+    ) -> ReaperResult<Vec<(Position, EnvelopePoint)>> {
+        (0..self.n_points())
+            .map(|idx| self.get_point_with_position(idx))
+            .collect()
+    }
+
     fn get_point_ex(
         &self,
         automation_item_index: Option<usize>,
         only_visible: bool,
         point_index: usize,
     ) -> ReaperResult<EnvelopePoint> {
+        self.get_point_with_position_ex(
+            automation_item_index,
+            only_visible,
+            point_index,
+        )
+        .map(|(_, point)| point)
+    }
+
+    fn get_point_with_position_ex(
+        &self,
+        automation_item_index: Option<usize>,
+        only_visible: bool,
+        point_index: usize,
+    ) -> ReaperResult<(Position, EnvelopePoint)> {
         let a_itm = automation_item_idx(only_visible, automation_item_index);
         let mut time = MaybeUninit::zeroed();
         let mut value = MaybeUninit::zeroed();
@@ -207,7 +238,7 @@ impl<'a, P: KnowsProject, T: ProbablyMutable> Envelope<'a, P, T> {
         };
         match result {
             true => unsafe {
-                Ok(EnvelopePoint {
+                let point = EnvelopePoint {
                     value: self.scale_from(value.assume_init()),
                     shape: EnvelopePointShape::from_int(shape.assume_init())
                         .expect(
@@ -215,7 +246,8 @@ impl<'a, P: KnowsProject, T: ProbablyMutable> Envelope<'a, P, T> {
                         ),
                     tension: tension.assume_init(),
                     selected: selected.assume_init(),
-                })
+                };
+                Ok((Position::from(time.assume_init()), point))
             },
             false => Err(ReaRsError::UnsuccessfulOperation(
                 "Can not set envelope point!",
@@ -515,6 +547,18 @@ impl<'a, P: KnowsProject> Envelope<'a, P, Mutable> {
         }
     }
 
+    /// Insert all points and sort envelope once.
+    pub fn insert_points(
+        &mut self,
+        points: impl IntoIterator<Item = (Position, EnvelopePoint)>,
+    ) -> ReaperResult<()> {
+        for (position, point) in points {
+            self.insert_point(position, point, false)?;
+        }
+        self.sort_points();
+        Ok(())
+    }
+
     pub fn delete_point(&mut self, index: usize) -> ReaperResult<()> {
         self.delete_point_ex(None, false, index)
     }
@@ -88,6 +88,10 @@ impl<'a, T: ProbablyMutable> TrackFX<'a, T> {
             }),
         }
     }
+    /// Index in the chain. Input FX have `0x1000000` flag set.
+    pub fn index(&self) -> usize {
+        self.index
+    }
     /// Iterate through (Immutable) FX params
     pub fn iter_params(&'a self) -> FXParamIterator<T, Track<'a, T>, Self> {
         FXParamIterator::new(self)
@@ -748,6 +752,10 @@ impl<'a, T: ProbablyMutable> TakeFX<'a, T> {
             }),
         }
     }
+    /// Index in the take FX chain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
     /// Iterate through (Immutable) FX params
     pub fn iter_params(&'a self) -> FXParamIterator<T, Take<'a, T>, Self> {
         FXParamIterator::new(self)
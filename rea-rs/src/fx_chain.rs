@@ -0,0 +1,382 @@
+//! FX chains (`.RfxChain` files).
+//!
+//! Chain file is a sequence of FX state nodes, the same as inside
+//! `FXCHAIN` block of track chunk, but without chain header. It can be
+//! read from, or loaded to track FX, input FX, take FX or monitoring FX.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{ChunkBlock, RfxChain, RppFx, RppFxChain, GUID};
+//!
+//! let mut chain = RfxChain::default();
+//! chain.fx.push(RppFx::new(ChunkBlock::new("JS", ["utility/volume", ""])));
+//! chain.fx.push(RppFx::new(ChunkBlock::new("JS", ["utility/phase", ""])));
+//! let text = chain.to_string();
+//! assert!(text.starts_with("BYPASS 0 0 0\n<JS utility/volume \"\"\n"));
+//!
+//! let phase = RfxChain::parse(&text).unwrap().select([1]);
+//! let mut track_chain = RppFxChain::new("FXCHAIN");
+//! let guid = GUID::parse_braced("{00000000-0000-0000-0000-000000000001}");
+//! let guid = guid.unwrap();
+//! phase.insert_into(&mut track_chain, None, || guid);
+//! assert_eq!(track_chain.fx[0].name(), Some("utility/phase"));
+//! ```
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use crate::{
+    Chunk, ChunkBlock, Item, KnowsProject, Mutable, ProbablyMutable, Project,
+    ReaRsError, Reaper, ReaperResult, RppFx, RppFxChain, RppItem, RppTrack,
+    Take, Track, WithReaperPtr, GUID,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// Flag of FX index, that addresses input (or monitoring) FX chain.
+const REC_FX_FLAG: usize = 0x1000000;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RfxChain {
+    pub fx: Vec<RppFx>,
+}
+impl RfxChain {
+    pub fn new(fx: impl IntoIterator<Item = RppFx>) -> Self {
+        Self {
+            fx: fx.into_iter().collect(),
+        }
+    }
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        let chunk = Chunk::parse(text)?;
+        let mut block = ChunkBlock::new("FXCHAIN", [""; 0]);
+        block.children_mut().extend(chunk.nodes);
+        Ok(Self::from_chain(&RppFxChain::from_block(&block)))
+    }
+    pub fn from_file(path: impl AsRef<Path>) -> ReaperResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ReaRsError::Io(e.to_string()))?;
+        Self::parse(&text)
+    }
+    pub fn write_file(&self, path: impl AsRef<Path>) -> ReaperResult<()> {
+        std::fs::write(path, self.to_string())
+            .map_err(|e| ReaRsError::Io(e.to_string()))
+    }
+
+    /// All FX of track, take or input chain.
+    pub fn from_chain(chain: &RppFxChain) -> Self {
+        Self::new(chain.fx.iter().cloned())
+    }
+    /// Only FX with given indexes, in the given order.
+    ///
+    /// Input FX flag (`0x1000000`) is ignored, so indexes of input
+    /// [crate::TrackFX::index] can be used as is. Missing indexes are
+    /// skipped.
+    pub fn select(&self, indexes: impl IntoIterator<Item = usize>) -> Self {
+        Self::new(
+            indexes
+                .into_iter()
+                .filter_map(|idx| self.fx.get(idx & !REC_FX_FLAG).cloned()),
+        )
+    }
+    /// Insert all FX to the chain at index, or at the end if None.
+    ///
+    /// FX and parameter envelope GUIDs are replaced by ones from
+    /// `new_guid`, so the same chain can be loaded many times.
+    pub fn insert_into(
+        &self,
+        chain: &mut RppFxChain,
+        index: Option<usize>,
+        mut new_guid: impl FnMut() -> GUID,
+    ) {
+        let index = index.unwrap_or(chain.fx.len()).min(chain.fx.len());
+        let fx: Vec<RppFx> = self
+            .fx
+            .iter()
+            .cloned()
+            .map(|mut fx| {
+                fx.guid = fx.guid.map(|_| new_guid());
+                for envelope in fx.envelopes.iter_mut() {
+                    envelope.guid = envelope.guid.map(|_| new_guid());
+                }
+                fx
+            })
+            .collect();
+        chain.fx.splice(index..index, fx).for_each(drop);
+    }
+}
+impl FromStr for RfxChain {
+    type Err = ReaRsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+impl Display for RfxChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in self.fx.iter().flat_map(|fx| fx.to_nodes()) {
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// FX chains, that are stored in track chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TrackFXChainKind {
+    /// `FXCHAIN`
+    Main,
+    /// `FXCHAIN_REC`: input FX.
+    Input,
+}
+impl TrackFXChainKind {
+    fn block_name(&self) -> &'static str {
+        match self {
+            Self::Main => "FXCHAIN",
+            Self::Input => "FXCHAIN_REC",
+        }
+    }
+    fn chain<'a>(&self, track: &'a RppTrack) -> Option<&'a RppFxChain> {
+        match self {
+            Self::Main => track.fx_chain.as_ref(),
+            Self::Input => track.input_fx.as_ref(),
+        }
+    }
+    fn chain_mut<'a>(&self, track: &'a mut RppTrack) -> &'a mut RppFxChain {
+        let chain = match self {
+            Self::Main => &mut track.fx_chain,
+            Self::Input => &mut track.input_fx,
+        };
+        chain.get_or_insert_with(|| RppFxChain::new(self.block_name()))
+    }
+}
+
+fn rpp_track(track: &Track<impl ProbablyMutable>) -> ReaperResult<RppTrack> {
+    let chunk = track.chunk_tree()?;
+    let root = chunk
+        .root()
+        .filter(|r| r.name() == "TRACK")
+        .ok_or(ReaRsError::InvalidObject("not a track chunk"))?;
+    Ok(RppTrack::from_block(root))
+}
+
+impl<'a, T: ProbablyMutable> Track<'a, T> {
+    /// FX chain, as it would be saved to `.RfxChain` file.
+    ///
+    /// Use [RfxChain::select] to save only some FX.
+    pub fn fx_chain(&self, kind: TrackFXChainKind) -> ReaperResult<RfxChain> {
+        let track = rpp_track(self)?;
+        Ok(kind
+            .chain(&track)
+            .map(RfxChain::from_chain)
+            .unwrap_or_default())
+    }
+}
+impl<'a> Track<'a, Mutable> {
+    /// Insert all FX of chain at index, or at the end if None.
+    pub fn load_fx_chain(
+        &mut self,
+        chain: &RfxChain,
+        kind: TrackFXChainKind,
+        index: impl Into<Option<usize>>,
+    ) -> ReaperResult<()> {
+        let mut track = rpp_track(self)?;
+        chain.insert_into(kind.chain_mut(&mut track), index.into(), GUID::new);
+        self.set_chunk(track.to_block().to_string(), true)
+    }
+}
+
+fn rpp_item_and_take(
+    take: &Take<impl ProbablyMutable>,
+) -> ReaperResult<(RppItem, usize)> {
+    let guid = take.guid();
+    let item = take.item().rpp_item()?;
+    let index = item
+        .takes
+        .iter()
+        .position(|t| t.guid == Some(guid))
+        .ok_or(ReaRsError::InvalidObject("take is not in item chunk"))?;
+    Ok((item, index))
+}
+
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// FX chain, as it would be saved to `.RfxChain` file.
+    pub fn fx_chain(&self) -> ReaperResult<RfxChain> {
+        let (item, index) = rpp_item_and_take(self)?;
+        Ok(item.takes[index]
+            .fx_chain
+            .as_ref()
+            .map(RfxChain::from_chain)
+            .unwrap_or_default())
+    }
+}
+impl<'a> Take<'a, Mutable> {
+    /// Insert all FX of chain at index, or at the end if None.
+    pub fn load_fx_chain(
+        &mut self,
+        chain: &RfxChain,
+        index: impl Into<Option<usize>>,
+    ) -> ReaperResult<()> {
+        let (mut item, take) = rpp_item_and_take(self)?;
+        let take_chain = item.takes[take]
+            .fx_chain
+            .get_or_insert_with(|| RppFxChain::new("TAKEFX"));
+        chain.insert_into(take_chain, index.into(), GUID::new);
+        Item::<Mutable>::new(self.item().project(), self.item().get())
+            .set_rpp_item(&item, true)
+    }
+}
+
+impl Project {
+    /// Monitoring FX chain (input FX of master track).
+    ///
+    /// Monitoring FX are not stored in track chunk, so they are copied
+    /// through temporary track.
+    pub fn monitoring_fx_chain(&mut self) -> ReaperResult<RfxChain> {
+        let master = self.get_master_track().get();
+        let temp = self.add_track(None, "");
+        let n_fx = unsafe {
+            Reaper::get().low().TrackFX_GetRecCount(master.as_ptr())
+        };
+        for idx in 0..n_fx {
+            unsafe {
+                Reaper::get().low().TrackFX_CopyToTrack(
+                    master.as_ptr(),
+                    REC_FX_FLAG as i32 + idx,
+                    temp.get().as_ptr(),
+                    idx,
+                    false,
+                )
+            };
+        }
+        let chain = temp.fx_chain(TrackFXChainKind::Main);
+        temp.delete();
+        chain
+    }
+    /// Insert all FX of chain to monitoring FX at index, or at the end if
+    /// None.
+    pub fn load_monitoring_fx_chain(
+        &mut self,
+        chain: &RfxChain,
+        index: impl Into<Option<usize>>,
+    ) -> ReaperResult<()> {
+        let master = self.get_master_track().get();
+        let n_fx = unsafe {
+            Reaper::get().low().TrackFX_GetRecCount(master.as_ptr())
+        };
+        let index = index.into().unwrap_or(n_fx as usize).min(n_fx as usize);
+        let mut temp = self.add_track(None, "");
+        let result = temp.load_fx_chain(chain, TrackFXChainKind::Main, None);
+        if result.is_ok() {
+            for idx in 0..chain.fx.len() {
+                unsafe {
+                    Reaper::get().low().TrackFX_CopyToTrack(
+                        temp.get().as_ptr(),
+                        idx as i32,
+                        master.as_ptr(),
+                        (REC_FX_FLAG + index + idx) as i32,
+                        false,
+                    )
+                };
+            }
+        }
+        temp.delete();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Position, RppItem, RppSource, RppTake, GUID};
+    use std::time::Duration;
+
+    const SYNTH: &str = include_str!("../fixtures/synth.RfxChain");
+
+    fn guid(count: u32) -> GUID {
+        let text = format!("{{00000000-0000-0000-0000-{:012X}}}", count);
+        GUID::parse_braced(&text).unwrap()
+    }
+
+    fn counter_guid() -> impl FnMut() -> GUID {
+        let mut count = 0;
+        move || {
+            count += 1;
+            guid(count)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chain = RfxChain::parse(SYNTH).unwrap();
+        assert_eq!(chain.fx.len(), 2);
+        assert_eq!(chain.fx[0].kind(), Some("VST"));
+        assert_eq!(chain.fx[0].preset_name.as_deref(), Some("stock - Init"));
+        assert_eq!(chain.fx[1].name(), Some("utility/volume"));
+        assert!(chain.fx[1].offline);
+        assert!(!chain.fx[1].bypassed);
+        assert_eq!(chain.to_string(), SYNTH);
+    }
+
+    #[test]
+    fn test_select() {
+        let chain = RfxChain::parse(SYNTH).unwrap();
+        let subset = chain.select([1, 0x1000000, 5]);
+        assert_eq!(subset.fx.len(), 2);
+        assert_eq!(subset.fx[0].name(), Some("utility/volume"));
+        assert_eq!(subset.fx[1].kind(), Some("VST"));
+        let guid =
+            GUID::parse_braced("{99999999-8888-7777-6666-555555555556}");
+        assert_eq!(subset.fx[0].guid, Some(guid.unwrap()));
+    }
+
+    #[test]
+    fn test_load_to_track() {
+        let chain = RfxChain::parse(SYNTH).unwrap();
+        let mut track = RppTrack::new("Synth");
+        let mut new_guid = counter_guid();
+        let main = TrackFXChainKind::Main.chain_mut(&mut track);
+        chain.insert_into(main, None, &mut new_guid);
+        // the same preset loaded twice gets other GUIDs.
+        chain.insert_into(main, None, &mut new_guid);
+        chain.select([1]).insert_into(
+            TrackFXChainKind::Input.chain_mut(&mut track),
+            Some(0),
+            &mut new_guid,
+        );
+        let track = RppTrack::from_block(&track.to_block());
+        let main = track.fx_chain.as_ref().unwrap();
+        assert_eq!(main.fx.len(), 4);
+        assert_eq!(main.fx[2].name(), chain.fx[0].name());
+        assert_eq!(main.fx[3].preset_name, chain.fx[1].preset_name);
+        let guids: Vec<_> = main.fx.iter().map(|fx| fx.guid).collect();
+        assert_eq!(guids, (1..=4).map(|n| Some(guid(n))).collect::<Vec<_>>());
+        let input = track.input_fx.as_ref().unwrap();
+        assert_eq!(input.fx.len(), 1);
+        assert_eq!(input.fx[0].name(), Some("utility/volume"));
+        assert_eq!(input.fx[0].guid, Some(guid(5)));
+        let block = track.to_block();
+        assert_eq!(block.block("FXCHAIN_REC").unwrap().name(), "FXCHAIN_REC");
+    }
+
+    #[test]
+    fn test_load_to_take() {
+        let chain = RfxChain::parse(SYNTH).unwrap().select([0]);
+        let source = RppSource::new_midi(960);
+        let mut item =
+            RppItem::new(Position::default(), Duration::from_secs(2));
+        item.takes.push(RppTake::new("Lead", source));
+        chain.insert_into(
+            item.takes[0]
+                .fx_chain
+                .get_or_insert_with(|| RppFxChain::new("TAKEFX")),
+            None,
+            counter_guid(),
+        );
+        let item = RppItem::from_block(&item.to_block());
+        let take_chain = item.takes[0].fx_chain.as_ref().unwrap();
+        let expected = chain.to_string().replace(
+            "99999999-8888-7777-6666-555555555555",
+            "00000000-0000-0000-0000-000000000001",
+        );
+        assert_eq!(RfxChain::from_chain(take_chain).to_string(), expected);
+        assert!(item.to_block().block("TAKEFX").is_some());
+    }
+}
@@ -0,0 +1,238 @@
+//! Arrange view grid and snapping.
+//!
+//! Grid restarts at every measure, so it follows tempo and time signature
+//! changes. Grid lines are computed through [TempoMap], so snapping can be
+//! tested with [crate::OfflineTempoMap].
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{
+//!     Direction, Grid, GridMode, OfflineTempoMap, Position, TimeSignature,
+//! };
+//!
+//! // 120 bpm: quarter is 0.5 s.
+//! let map = OfflineTempoMap::new(120.0, TimeSignature::new(4, 4), []);
+//! let grid = Grid::new(0.25, GridMode::Straight);
+//! let position = Position::from(0.7);
+//! assert_eq!(grid.snap(position, None, &map), Position::from(0.5));
+//! let next = grid.snap(position, Direction::Right, &map);
+//! assert_eq!(next, Position::from(1.0));
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Direction, Position, Project, Reaper, TempoMap};
+
+/// Tolerance (in quarters) for positions exactly on a grid line.
+const EPSILON: f64 = 1e-9;
+
+/// Action "Options: Toggle snapping".
+const SNAP_ACTION: u32 = 1157;
+/// Action "Options: Toggle grid lines".
+const GRID_LINES_ACTION: u32 = 40145;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GridMode {
+    Straight,
+    /// Every second line is moved by swing amount (-1.0 to 1.0) of half
+    /// grid division.
+    Swing(f64),
+    /// Grid lines only at measure starts.
+    Measure,
+}
+
+/// Arrange view grid.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Grid {
+    /// In whole notes: 0.25 is quarter, 1.0 / 6.0 is quarter triplet.
+    pub division: f64,
+    pub mode: GridMode,
+}
+impl Grid {
+    pub fn new(division: f64, mode: GridMode) -> Self {
+        Self { division, mode }
+    }
+
+    /// Grid lines (in quarters) from the measure start to the next
+    /// measure start, inclusive.
+    fn measure_lines(&self, measure: u32, map: &impl TempoMap) -> Vec<f64> {
+        let (start, _) = map.measure_start(measure);
+        let (end, _) = map.measure_start(measure + 1);
+        let step = self.division * 4.0;
+        let swing = match self.mode {
+            GridMode::Measure => return vec![start, end],
+            GridMode::Straight => 0.0,
+            GridMode::Swing(amount) => amount * step / 2.0,
+        };
+        let mut lines = vec![start];
+        if step > 0.0 {
+            let mut index = 1;
+            while start + index as f64 * step < end - EPSILON {
+                let line = start + index as f64 * step;
+                match index % 2 {
+                    1 => lines.push(line + swing),
+                    _ => lines.push(line),
+                }
+                index += 1;
+            }
+        }
+        lines.push(end);
+        lines
+    }
+
+    /// Grid line at or before (Left), at or after (Right), or the nearest
+    /// to position (None).
+    pub fn snap(
+        &self,
+        position: Position,
+        direction: impl Into<Option<Direction>>,
+        map: &impl TempoMap,
+    ) -> Position {
+        let quarters = map.quarters_at(position);
+        let measure = map.measure_at_quarters(quarters);
+        let lines = self.measure_lines(measure, map);
+        let previous = lines
+            .iter()
+            .rev()
+            .find(|line| **line <= quarters + EPSILON)
+            .unwrap_or(&lines[0]);
+        let next = lines
+            .iter()
+            .find(|line| **line >= quarters - EPSILON)
+            .unwrap_or(&lines[lines.len() - 1]);
+        let (previous, next) = (
+            map.position_at_quarters(*previous),
+            map.position_at_quarters(*next),
+        );
+        match direction.into() {
+            Some(Direction::Left) => previous,
+            Some(Direction::Right) => next,
+            None => {
+                let (time, before, after): (f64, f64, f64) =
+                    (position.into(), previous.into(), next.into());
+                match time - before <= after - time {
+                    true => previous,
+                    false => next,
+                }
+            }
+        }
+    }
+}
+
+impl Project {
+    pub fn grid(&self) -> Grid {
+        let (mut division, mut swing_mode, mut swing) = (0.0, 0, 0.0);
+        unsafe {
+            Reaper::get().low().GetSetProjectGrid(
+                self.context().to_raw(),
+                false,
+                &mut division,
+                &mut swing_mode,
+                &mut swing,
+            )
+        };
+        let mode = match swing_mode {
+            1 => GridMode::Swing(swing),
+            3 => GridMode::Measure,
+            _ => GridMode::Straight,
+        };
+        Grid::new(division, mode)
+    }
+    pub fn set_grid(&mut self, grid: Grid) {
+        let mut division = grid.division;
+        let (mut swing_mode, mut swing) = match grid.mode {
+            GridMode::Straight => (0, 0.0),
+            GridMode::Swing(amount) => (1, amount),
+            GridMode::Measure => (3, 0.0),
+        };
+        unsafe {
+            Reaper::get().low().GetSetProjectGrid(
+                self.context().to_raw(),
+                true,
+                &mut division,
+                &mut swing_mode,
+                &mut swing,
+            )
+        };
+    }
+
+    /// Snap state of the current project.
+    pub fn is_snap_enabled(&self) -> bool {
+        self.toggle_state(SNAP_ACTION)
+    }
+    pub fn set_snap_enabled(&mut self, enabled: bool) {
+        self.set_toggle_state(SNAP_ACTION, enabled)
+    }
+    /// Grid lines visibility in the current project.
+    pub fn is_grid_visible(&self) -> bool {
+        self.toggle_state(GRID_LINES_ACTION)
+    }
+    pub fn set_grid_visible(&mut self, visible: bool) {
+        self.set_toggle_state(GRID_LINES_ACTION, visible)
+    }
+}
+
+impl Position {
+    /// Grid line of the project grid: at or before (Left), at or after
+    /// (Right), or the nearest (None).
+    pub fn grid_line(
+        &self,
+        project: &Project,
+        direction: impl Into<Option<Direction>>,
+    ) -> Position {
+        project.grid().snap(*self, direction, project)
+    }
+    /// Nearest grid line if snap is enabled, as native editing does.
+    pub fn snapped(&self, project: &Project) -> Position {
+        match project.is_snap_enabled() {
+            true => self.grid_line(project, None),
+            false => *self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OfflineTempoMap, RppTempoMarker, TimeSignature};
+
+    fn map() -> OfflineTempoMap {
+        // 4/4 at 120 bpm, then 3/4 at 60 bpm from bar 2.
+        let mut waltz = RppTempoMarker::new(2.0, 60.0);
+        waltz.time_signature = Some(TimeSignature::new(3, 4));
+        OfflineTempoMap::new(120.0, TimeSignature::new(4, 4), [waltz])
+    }
+
+    fn snap(grid: Grid, secs: f64, direction: Option<Direction>) -> f64 {
+        grid.snap(Position::from(secs), direction, &map()).into()
+    }
+
+    #[test]
+    fn test_straight() {
+        let grid = Grid::new(0.25, GridMode::Straight);
+        assert_eq!(snap(grid, 0.7, None), 0.5);
+        assert_eq!(snap(grid, 0.8, None), 1.0);
+        assert_eq!(snap(grid, 0.7, Some(Direction::Left)), 0.5);
+        assert_eq!(snap(grid, 0.7, Some(Direction::Right)), 1.0);
+        assert_eq!(snap(grid, 1.0, Some(Direction::Left)), 1.0);
+        assert_eq!(snap(grid, 1.0, Some(Direction::Right)), 1.0);
+        // after tempo change quarter is 1 s.
+        assert_eq!(snap(grid, 3.4, None), 3.0);
+        assert_eq!(snap(grid, 4.6, Some(Direction::Left)), 4.0);
+        assert_eq!(snap(grid, 4.6, Some(Direction::Right)), 5.0);
+    }
+
+    #[test]
+    fn test_swing_and_measure() {
+        // half notes: in 3/4 the last line is cut by the next measure.
+        let grid = Grid::new(0.5, GridMode::Straight);
+        assert_eq!(snap(grid, 4.1, Some(Direction::Right)), 5.0);
+        let grid = Grid::new(0.25, GridMode::Swing(0.5));
+        assert_eq!(snap(grid, 0.5, Some(Direction::Right)), 0.625);
+        assert_eq!(snap(grid, 0.7, Some(Direction::Right)), 1.0);
+        let grid = Grid::new(0.25, GridMode::Measure);
+        assert_eq!(snap(grid, 1.2, None), 2.0);
+        assert_eq!(snap(grid, 0.9, None), 0.0);
+    }
+}
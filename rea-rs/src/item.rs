@@ -2,13 +2,13 @@ use crate::{
     ptr_wrappers::{MediaItem, MediaItemTake, MediaTrack},
     utils::WithNull,
     utils::{as_c_str, as_c_string, string_from_buf},
-    Color, Immutable, KnowsProject, Mutable, Position, ProbablyMutable,
-    Project, ReaRsError, Reaper, ReaperResult, Take, TimeMode, Track, Volume,
-    WithReaperPtr, GUID,
+    Chunk, ChunkNode, Color, Immutable, KnowsProject, Mutable, Position,
+    ProbablyMutable, Project, ReaRsError, Reaper, ReaperResult, RppItem,
+    RppTake, Take, TimeMode, Track, Volume, WithReaperPtr, GUID,
 };
 use int_enum::IntEnum;
 use serde_derive::{Deserialize, Serialize};
-use std::{marker::PhantomData, ptr::NonNull, time::Duration};
+use std::{fmt::Display, marker::PhantomData, ptr::NonNull, time::Duration};
 
 #[derive(Debug, PartialEq)]
 pub struct Item<'a, T: ProbablyMutable> {
@@ -262,6 +262,27 @@ impl<'a, T: ProbablyMutable> Item<'a, T> {
         let size = buf_size.into().unwrap_or(1024);
         self.get_info_string("P_NOTES", size as usize)
     }
+    /// Get item state chunk.
+    ///
+    /// If `is_undo`, the minimal chunk, used by undo system, is returned.
+    pub fn chunk(&self, is_undo: bool) -> ReaperResult<String> {
+        let size = i32::MAX;
+        let mut buf = vec![0_i8; size as usize];
+        let result = unsafe {
+            Reaper::get().low().GetItemStateChunk(
+                self.get().as_ptr(),
+                buf.as_mut_ptr(),
+                size,
+                is_undo,
+            )
+        };
+        match result {
+            false => {
+                Err(ReaRsError::UnsuccessfulOperation("Can not get chunk"))
+            }
+            true => Ok(string_from_buf(&buf)?),
+        }
+    }
     pub fn guid(&self) -> GUID {
         let guid_str = self
             .get_info_string("GUID", 50)
@@ -528,6 +549,96 @@ impl<'a> Item<'a, Mutable> {
         let guid_str = guid.to_string();
         self.set_info_string("GUID", guid_str)
     }
+    pub fn set_chunk(
+        &mut self,
+        chunk: impl Into<String>,
+        need_undo: bool,
+    ) -> ReaperResult<()> {
+        let mut chunk = chunk.into();
+        let result = unsafe {
+            Reaper::get().low().SetItemStateChunk(
+                self.get().as_ptr(),
+                as_c_str(chunk.with_null()).as_ptr(),
+                need_undo,
+            )
+        };
+        match result {
+            true => Ok(()),
+            false => {
+                Err(ReaRsError::UnsuccessfulOperation("Can not set chunk!"))
+            }
+        }
+    }
+}
+
+impl<'a, T: ProbablyMutable> Item<'a, T> {
+    /// [Item::chunk] as a tree.
+    pub fn chunk_tree(&self, is_undo: bool) -> ReaperResult<Chunk> {
+        self.chunk(is_undo)?.parse()
+    }
+    /// [Item::chunk] as [RppItem].
+    pub fn rpp_item(&self) -> ReaperResult<RppItem> {
+        let chunk = self.chunk_tree(false)?;
+        let root = chunk
+            .root()
+            .filter(|r| r.name() == "ITEM")
+            .ok_or(ReaRsError::InvalidObject("not an item chunk"))?;
+        Ok(RppItem::from_block(root))
+    }
+    /// Parts of [Item::chunk], that belong to takes, in take order.
+    ///
+    /// Every take chunk, except the first one, starts with `TAKE` line.
+    pub fn take_chunks(&self) -> ReaperResult<Vec<String>> {
+        let item = self.rpp_item()?;
+        Ok(item.takes.iter().map(|t| t.to_string()).collect())
+    }
+}
+impl<'a> Item<'a, Mutable> {
+    /// Replace item state by the tree, got from [Item::chunk_tree].
+    ///
+    /// Unchanged parts of the tree are written as they were read.
+    pub fn set_chunk_tree(
+        &mut self,
+        chunk: &Chunk,
+        need_undo: bool,
+    ) -> ReaperResult<()> {
+        self.set_chunk(chunk.to_string(), need_undo)
+    }
+    pub fn set_rpp_item(
+        &mut self,
+        item: &RppItem,
+        need_undo: bool,
+    ) -> ReaperResult<()> {
+        self.set_chunk(item.to_block().to_string(), need_undo)
+    }
+}
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// Part of [Item::chunk], that belongs to the take.
+    pub fn chunk(&self) -> ReaperResult<String> {
+        let guid = self.guid();
+        self.item()
+            .rpp_item()?
+            .takes
+            .iter()
+            .find(|t| t.guid == Some(guid))
+            .map(|t| t.to_string())
+            .ok_or(ReaRsError::InvalidObject("take is not in item chunk"))
+    }
+}
+
+impl Display for ChunkNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, 0)
+    }
+}
+/// Take part of item chunk, as returned by [Item::take_chunks].
+impl Display for RppTake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in self.to_nodes() {
+            node.fmt(f)?;
+        }
+        Ok(())
+    }
 }
 
 /// Holds two new items after [Item::split].
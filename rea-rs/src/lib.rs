@@ -202,6 +202,14 @@ pub use take::*;
 
 pub mod stretch_marker;
 pub use stretch_marker::*;
+pub mod tempo_marker;
+pub use tempo_marker::*;
+pub mod musical_time;
+pub use musical_time::*;
+pub mod timecode;
+pub use timecode::*;
+pub mod grid;
+pub use grid::*;
 
 pub mod source;
 pub use source::*;
@@ -218,6 +226,17 @@ pub use audio_accessor::*;
 pub mod envelope;
 pub use envelope::*;
 
+pub mod chunk;
+pub use chunk::*;
+
+pub mod rpp;
+pub use rpp::*;
+
+pub mod track_template;
+pub use track_template::*;
+pub mod fx_chain;
+pub use fx_chain::*;
+
 pub mod midi_editor;
 pub use midi_editor::*;
 
@@ -253,6 +272,10 @@ pub enum ReaRsError {
     UnsuccessfulOperation(&'static str),
     #[error("Error: {0}")]
     Str(&'static str),
+    #[error("Parse error at line {0}: {1}")]
+    Parse(usize, String),
+    #[error("IO error: {0}")]
+    Io(String),
 }
 
 pub type ReaperResult<T> = Result<T, ReaRsError>;
@@ -155,7 +155,38 @@
 //! }
 
 use serde_derive::{Deserialize, Serialize};
-use std::{fmt::Display, vec::IntoIter};
+use std::{fmt::Display, iter::Peekable, vec::IntoIter};
+
+pub mod borrowed;
+pub use borrowed::*;
+pub mod cc_envelope;
+pub use cc_envelope::*;
+pub mod cc_shape;
+pub use cc_shape::*;
+pub mod channel_map;
+pub use channel_map::*;
+pub mod harmony;
+pub use harmony::*;
+pub mod mpe;
+pub use mpe::*;
+pub mod notation;
+pub use notation::*;
+pub mod note_repair;
+pub use note_repair::*;
+pub mod parameter_number;
+pub use parameter_number::*;
+pub mod smf;
+pub use smf::*;
+pub mod sysex;
+pub use sysex::*;
+pub mod take_editor;
+pub use take_editor::*;
+pub mod text_dump;
+pub use text_dump::*;
+pub mod transform;
+pub use transform::*;
+pub mod tuning;
+pub use tuning::*;
 
 /// Basic MIDI Message functionality.
 pub trait MidiMessage: Display + Clone {
@@ -243,13 +274,18 @@ trait ShortMessage: MidiMessage {
     /// Ignores channel.
     fn starts_with_message(buf: &Vec<u8>) -> Option<()> {
         let msg = Self::message();
-        match (msg..msg + 15).contains(&buf[0]) {
-            true => Some(()),
-            false => None,
+        match buf.first().map(|status| (msg..=msg + 15).contains(status)) {
+            Some(true) => Some(()),
+            _ => None,
         }
     }
 }
 
+/// Start of `CCBZ` event, that holds Beizer data of the previous event.
+///
+/// See [CcShapeKind].
+const BEIZER_HEADER: [u8; 6] = [0xff, 0x0f, b'C', b'C', b'B', b'Z'];
+
 /// Unifies interface for events, supports CcShapeCurve.
 ///
 /// Currently, supports [CCMessage] and [ChannelPressureMessage].
@@ -332,6 +368,12 @@ impl ShortMessage for CCMessage {
     }
 }
 impl CCMessage {
+    pub fn new(channel: u8, cc_num: u8, value: u8) -> Self {
+        Self {
+            msg_buf: vec![0xb0 + channel - 1, cc_num, value],
+            beizer_buf: vec![],
+        }
+    }
     pub fn channel(&self) -> u8 {
         self.channel_private()
     }
@@ -510,8 +552,30 @@ impl Display for NoteOffMessage {
 )]
 pub struct PitchBendMessage {
     buf: Vec<u8>,
+    beizer_buf: Vec<u8>,
+}
+impl HasBeizer for PitchBendMessage {
+    fn msg_buf(&self) -> &Vec<u8> {
+        &self.buf
+    }
+
+    fn beizer_buf(&self) -> &Vec<u8> {
+        &self.beizer_buf
+    }
+    fn set_beizer_buf(&mut self, buf: Vec<u8>) {
+        self.beizer_buf = buf
+    }
 }
 impl PitchBendMessage {
+    /// Value is combined MSB\LSB. 8192 is the middle.
+    pub fn new(channel: u8, raw_value: u16) -> Self {
+        let mut msg = Self {
+            buf: vec![0xe0 + channel - 1, 0, 0],
+            beizer_buf: vec![],
+        };
+        msg.set_raw_value(raw_value);
+        msg
+    }
     pub fn channel(&self) -> u8 {
         self.channel_private()
     }
@@ -553,6 +617,8 @@ fn test_pb_values() {
     assert_eq!(pb.normalized_value(), 1.0);
     pb.set_normalized_value(-0.5);
     assert_eq!(pb.raw_value(), 4096);
+    assert!(PitchBendMessage::from_raw(vec![224]).is_none());
+    assert!(PitchBendMessage::from_raw(vec![224, 65]).is_none());
 }
 
 impl ShortMessage for PitchBendMessage {
@@ -562,12 +628,22 @@ impl ShortMessage for PitchBendMessage {
 }
 impl MidiMessage for PitchBendMessage {
     fn from_raw(buf: Vec<u8>) -> Option<Self> {
-        Self::is_short(&buf)?;
+        if buf.len() < 3 {
+            return None;
+        }
         Self::starts_with_message(&buf)?;
-        Some(Self { buf })
+        let beizer_buf = match buf.len() {
+            3 => vec![],
+            _ => Vec::from(&buf[3..]),
+        };
+        let buf = Vec::from(&buf[..3]);
+        Some(Self { buf, beizer_buf })
     }
     fn get_raw(&self) -> Vec<u8> {
-        self.buf.clone()
+        let mut buf = self.buf.clone();
+        let mut beizer = self.beizer_buf.clone();
+        buf.append(&mut beizer);
+        buf
     }
     fn borrow_raw(&self) -> &Vec<u8> {
         &self.buf
@@ -742,7 +818,8 @@ impl Display for AllSysMessage {
     }
 }
 
-/// Represents Text messages `0xf0, 0x01`
+/// Represents Text messages `0xf0, 0x01`, as well as other text meta-events
+/// `0xff, 0x01..0x0e` (lyrics, markers, cue points etc.).
 #[derive(
     Clone, PartialEq, PartialOrd, Debug, Default, Serialize, Deserialize,
 )]
@@ -750,26 +827,41 @@ pub struct TextMessage {
     buf: Vec<u8>,
 }
 impl TextMessage {
+    /// kind is the meta-event type: 1 — text, 5 — lyrics, 6 — marker etc.
+    pub fn new(kind: u8, text: impl Into<String>) -> Self {
+        let mut msg = Self {
+            buf: vec![0xff, kind],
+        };
+        msg.set_text(text);
+        msg
+    }
+    /// Meta-event type.
+    pub fn kind(&self) -> u8 {
+        self.buf[1]
+    }
     pub fn text(&self) -> String {
         String::from_utf8(self.get_raw()[2..].to_vec())
             .expect("Cannot decode text message to utf-8")
     }
+    /// Set text, keeping the kind of message.
     pub fn set_text(&mut self, text: impl Into<String>) {
         let mut text: String = text.into();
-        let mut buf = vec![0xf0, 0x01];
+        let mut buf = match self.buf.len() >= 2 {
+            true => self.buf[..2].to_vec(),
+            false => vec![0xff, 0x01],
+        };
         buf.append(unsafe { text.as_mut_vec() });
         self.buf = buf;
     }
 }
 impl MidiMessage for TextMessage {
     fn from_raw(buf: Vec<u8>) -> Option<Self> {
-        if buf[0] < 0xf0 {
+        if buf.len() < 2 || buf[0] < 0xf0 {
             return None;
         }
-        if buf[1] == 0x01 {
-            Some(Self { buf })
-        } else {
-            None
+        match (buf[0], buf[1]) {
+            (0xff, 0x01..=0x0e) | (_, 0x01) => Some(Self { buf }),
+            _ => None,
         }
     }
     fn get_raw(&self) -> Vec<u8> {
@@ -1257,7 +1349,7 @@ impl MidiEventBuilder {
     }
     /// Iter only through `MidiEvent<PitchBendMessage>`
     pub fn filter_pitch_bend(self) -> FilterPitchBend {
-        FilterPitchBend { midi_events: self }
+        FilterPitchBend::from(self)
     }
     /// Iter only through `MidiEvent<AfterTouchMessage>`
     pub fn filter_after_touch(self) -> FilterAfterTouch {
@@ -1313,6 +1405,32 @@ impl MidiEventBuilder {
     pub fn filter_notes(self) -> FilterNotes<Self> {
         FilterNotes::from(self)
     }
+    /// Iter only through [ParameterNumberEvent] (RPN, NRPN and 14-bit CC).
+    ///
+    /// # Note
+    ///
+    /// With reverse iteration, additional step of
+    /// [crate::midi::flatten_parameter_numbers] is required:
+    /// ```
+    /// use rea_rs::midi::*;
+    /// let events = vec![ParameterNumberEvent::new(
+    ///     0,
+    ///     false,
+    ///     false,
+    ///     1,
+    ///     ParameterNumberKind::PITCH_BEND_SENSITIVITY,
+    ///     12 << 7,
+    ///     true,
+    /// )]; // etc...
+    /// let raw = MidiEventConsumer::new(
+    ///     to_raw_midi_events(
+    ///         flatten_parameter_numbers(events.into_iter())
+    ///     )
+    /// );
+    /// ```
+    pub fn filter_parameter_numbers(self) -> FilterParameterNumbers<Self> {
+        FilterParameterNumbers::from(self)
+    }
 
     fn next_4(&mut self) -> Option<[u8; 4]> {
         match (
@@ -1603,11 +1721,13 @@ impl Iterator for FilterNoteOff {
 
 /// Iterates through Pitch events. Better not to use outside the module.
 pub struct FilterPitchBend {
-    midi_events: MidiEventBuilder,
+    midi_events: Peekable<MidiEventBuilder>,
 }
 impl From<MidiEventBuilder> for FilterPitchBend {
     fn from(value: MidiEventBuilder) -> Self {
-        Self { midi_events: value }
+        Self {
+            midi_events: value.peekable(),
+        }
     }
 }
 impl Iterator for FilterPitchBend {
@@ -1615,11 +1735,21 @@ impl Iterator for FilterPitchBend {
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = self.midi_events.next()?;
-        let message =
+        let mut message =
             match PitchBendMessage::from_raw(item.message().get_raw()) {
                 None => return self.next(),
                 Some(m) => m,
             };
+        if item.cc_shape_kind() == CcShapeKind::Beizer {
+            // Malformed or missing Beizer data is skipped.
+            let beizer = self.midi_events.next_if(|e| {
+                let buf = &e.message().buf;
+                buf.len() >= 12 && buf.starts_with(&BEIZER_HEADER)
+            });
+            if let Some(beizer) = beizer {
+                message.set_beizer_buf(beizer.message().get_raw());
+            }
+        }
         Some(MidiEvent::with_new_message(item, message))
     }
 }
@@ -1737,14 +1867,37 @@ impl Iterator for FilterAllSys {
 pub struct FilterNotes<T: Iterator<Item = MidiEvent<RawMidiMessage>>> {
     midi_events: T,
     note_ons: Vec<MidiEvent<NoteOnMessage>>,
+    orphan_note_offs: Vec<MidiEvent<NoteOffMessage>>,
+    fix_orphans: bool,
 }
 impl<T: Iterator<Item = MidiEvent<RawMidiMessage>>> FilterNotes<T> {
     pub fn new(events: T) -> Self {
         Self {
             midi_events: events,
             note_ons: Vec::new(),
+            orphan_note_offs: Vec::new(),
+            fix_orphans: true,
         }
     }
+    /// Do not yield notes for note-off events without note-on.
+    ///
+    /// By default, such notes are started at 0 ppq.
+    pub fn skip_orphan_note_offs(mut self) -> Self {
+        self.fix_orphans = false;
+        self
+    }
+    /// Note-on events, that have not got their note-off yet.
+    ///
+    /// After the iteration is finished — orphan note-ons.
+    pub fn pending_note_ons(&self) -> &Vec<MidiEvent<NoteOnMessage>> {
+        &self.note_ons
+    }
+    /// Note-off events without note-on, that have been met so far.
+    ///
+    /// Collected only if [FilterNotes::skip_orphan_note_offs] is set.
+    pub fn orphan_note_offs(&self) -> &Vec<MidiEvent<NoteOffMessage>> {
+        &self.orphan_note_offs
+    }
 }
 impl From<MidiEventBuilder> for FilterNotes<MidiEventBuilder> {
     fn from(value: MidiEventBuilder) -> Self {
@@ -1776,8 +1929,12 @@ impl<T: Iterator<Item = MidiEvent<RawMidiMessage>>> Iterator
                         && i.message().channel_private()
                             == off.message().channel_private()
                 }) {
-                    Some(on) => self.note_ons.swap_remove(on),
+                    Some(on) => self.note_ons.remove(on),
                     None => {
+                        if !self.fix_orphans {
+                            self.orphan_note_offs.push(off);
+                            return self.next();
+                        }
                         eprintln!("No Note On for note-off: {:?}", off);
                         let mut on = MidiEvent::with_new_message(
                             item,
@@ -1856,9 +2013,95 @@ mod tests {
     use crate::{
         flatten_events_with_beizer_curve, flatten_midi_notes, sorted_by_ppq,
         to_raw_midi_events, CCMessage, ChannelPressureMessage, MidiEvent,
-        MidiEventBuilder, MidiEventConsumer, MidiNoteEvent,
+        MidiEventBuilder, MidiEventConsumer, MidiMessage, MidiNoteEvent,
+        NoteOffMessage, NoteOnMessage, PitchBendMessage, TextMessage,
     };
 
+    #[test]
+    fn test_filter_notes_pairing() {
+        let notes_buf = [
+            0, 0, 0, 0, 0, 3, 0, 0, 0, 144, 60, 100, //
+            10, 0, 0, 0, 0, 3, 0, 0, 0, 144, 60, 90, //
+            10, 0, 0, 0, 0, 3, 0, 0, 0, 144, 60, 80, //
+            10, 0, 0, 0, 0, 3, 0, 0, 0, 128, 61, 0, // orphan
+            0, 0, 0, 0, 0, 3, 0, 0, 0, 128, 60, 0, //
+            10, 0, 0, 0, 0, 3, 0, 0, 0, 128, 60, 0, //
+            10, 0, 0, 0, 0, 3, 0, 0, 0, 128, 60, 0,
+        ];
+        let events = MidiEventBuilder::new(notes_buf.to_vec().into_iter());
+        // overlapping notes of the same pitch are paired first-in,
+        // first-out.
+        let mut filter = events.clone().filter_notes().skip_orphan_note_offs();
+        let notes: Vec<_> = filter
+            .by_ref()
+            .map(|n| (n.start_in_ppq, n.end_in_ppq, n.on_velocity))
+            .collect();
+        assert_eq!(notes, vec![(0, 30, 100), (10, 40, 90), (20, 50, 80)]);
+        assert_eq!(filter.orphan_note_offs().len(), 1);
+        assert_eq!(filter.orphan_note_offs()[0].message().note(), 61);
+        assert!(filter.pending_note_ons().is_empty());
+
+        let mut filter = events.filter_notes();
+        assert_eq!(filter.by_ref().count(), 4);
+        assert!(filter.orphan_note_offs().is_empty());
+    }
+
+    #[test]
+    fn test_pitch_bend_malformed_beizer() {
+        use crate::{CcShapeKind, HasBeizer, MidiBufWriter};
+        let mut writer = MidiBufWriter::new();
+        let beizer = CcShapeKind::Beizer;
+        let square = CcShapeKind::Square;
+        // Beizer data is missing: the next pitch bend is kept.
+        writer.push_raw(0, false, false, beizer, &[0xe0, 0, 64]);
+        writer.push_raw(10, false, false, beizer, &[0xe0, 0, 32]);
+        let mut tension = super::BEIZER_HEADER.to_vec();
+        tension.extend([b' ', 0]);
+        tension.extend(0.5_f32.to_le_bytes());
+        writer.push_raw(10, false, false, square, &tension);
+        // Beizer data is too short.
+        writer.push_raw(20, false, false, beizer, &[0xe0, 0, 16]);
+        writer.push_raw(20, false, false, square, &tension[..9]);
+        writer.push_raw(30, false, false, beizer, &[0xe0, 0, 8]);
+        let events = MidiEventBuilder::new(writer.finish().into_iter());
+        let bends: Vec<_> = events
+            .filter_pitch_bend()
+            .map(|e| (e.ppq_position(), e.message().beizer_tension()))
+            .collect();
+        assert_eq!(
+            bends,
+            vec![(0, None), (10, Some(0.5)), (20, None), (30, None)]
+        );
+    }
+
+    #[test]
+    fn test_channel_16() {
+        let on = NoteOnMessage::from_raw(vec![0x9f, 60, 100]).unwrap();
+        assert_eq!(on.channel(), 16);
+        let off = NoteOffMessage::from_raw(vec![0x8f, 60, 0]).unwrap();
+        assert_eq!(off.channel(), 16);
+        let pb = PitchBendMessage::from_raw(vec![0xef, 0, 64]).unwrap();
+        assert_eq!(pb.channel(), 16);
+        assert!(NoteOnMessage::from_raw(vec![0xa0, 60, 100]).is_none());
+    }
+
+    #[test]
+    fn test_text_kind() {
+        let mut text = TextMessage::default();
+        text.set_text("abc");
+        assert_eq!(text.get_raw(), vec![0xff, 0x01, b'a', b'b', b'c']);
+        let mut lyric =
+            TextMessage::from_raw(vec![0xff, 0x05, b'l', b'a']).unwrap();
+        assert_eq!(lyric.kind(), 5);
+        lyric.set_text("li");
+        assert_eq!(lyric.get_raw(), vec![0xff, 0x05, b'l', b'i']);
+        // legacy text event.
+        assert!(TextMessage::from_raw(vec![0xf0, 0x01, b'a']).is_some());
+        // notation is not a text message.
+        assert!(TextMessage::from_raw(vec![0xff, 0x0f, b'a']).is_none());
+        assert!(TextMessage::from_raw(vec![0xff]).is_none());
+    }
+
     #[test]
     fn test_flatten_notes() {
         let notes_buf = [
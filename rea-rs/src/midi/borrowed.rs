@@ -0,0 +1,591 @@
+//! Zero-copy reading and streaming writing of take raw MIDI.
+//!
+//! [crate::MidiEventBuilder] owns the whole buffer and allocates [RawMidiMessage]
+//! for every event. On dense takes it can be faster to borrow the buffer,
+//! got from [crate::Take::get_midi], and to look at events through
+//! [MidiEventRef] views. Views can be converted to owned [MidiEvent] only
+//! when needed by [MidiEventRef::to_event].
+//!
+//! [MidiBufWriter] builds the buffer for [crate::Take::set_midi] directly,
+//! without intermediate per-event vectors of [crate::MidiEventConsumer].
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let mut writer = MidiBufWriter::new();
+//! writer.push_raw(0, false, false, CcShapeKind::Linear, &[0xb0, 1, 10]);
+//! writer.push_raw(0, false, false, CcShapeKind::Square, &[0x90, 60, 100]);
+//! writer.push_raw(240, false, false, CcShapeKind::Square, &[0x80, 60, 0]);
+//! writer.push_raw(480, true, false, CcShapeKind::Square, &[0xb0, 1, 90]);
+//! let buf = writer.finish();
+//!
+//! let cc: Vec<_> = MidiEventRefBuilder::new(&buf)
+//!     .filter_cc()
+//!     .map(|e| (e.ppq_position(), e.data2().unwrap()))
+//!     .collect();
+//! assert_eq!(cc, vec![(0, 10), (480, 90)]);
+//!
+//! let notes: Vec<MidiNoteEvent> =
+//!     MidiEventRefBuilder::new(&buf).filter_notes().collect();
+//! assert_eq!(notes[0].end_in_ppq, 240);
+//! ```
+
+use super::BEIZER_HEADER;
+use crate::{
+    CcShapeKind, FilterNotes, FilterParameterNumbers, HasBeizer, MidiEvent,
+    MidiMessage, RawMidiMessage,
+};
+
+/// Raw flag byte of event with the given selection, mute and shape.
+pub(super) fn event_flag(
+    is_selected: bool,
+    is_muted: bool,
+    cc_shape_kind: CcShapeKind,
+) -> u8 {
+    is_selected as u8 | (is_muted as u8) << 1 | cc_shape_kind.to_raw()
+}
+
+/// Borrowed view of one event in take raw MIDI buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiEventRef<'a> {
+    position_in_ppq: u32,
+    flag: u8,
+    message: &'a [u8],
+    beizer: &'a [u8],
+}
+impl<'a> MidiEventRef<'a> {
+    pub fn ppq_position(&self) -> u32 {
+        self.position_in_ppq
+    }
+    pub fn selected(&self) -> bool {
+        self.flag & 1 != 0
+    }
+    pub fn muted(&self) -> bool {
+        self.flag & 2 != 0
+    }
+    /// Unknown shape bits are treated as [CcShapeKind::Square].
+    pub fn cc_shape_kind(&self) -> CcShapeKind {
+        CcShapeKind::from_raw(self.flag & 0b11110000).unwrap_or_default()
+    }
+    /// Raw flag byte.
+    pub fn flag(&self) -> u8 {
+        self.flag
+    }
+    /// Message bytes, without Beizer data.
+    pub fn message(&self) -> &'a [u8] {
+        self.message
+    }
+    /// Raw `CCBZ` event, attached by [FilterRef] to shaped events. Empty
+    /// if there is no Beizer data.
+    pub fn beizer_buf(&self) -> &'a [u8] {
+        self.beizer
+    }
+    pub fn beizer_tension(&self) -> Option<f64> {
+        let s = self.beizer.get(8..12)?;
+        Some(f32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f64)
+    }
+    /// The first byte of message.
+    pub fn status(&self) -> u8 {
+        self.message.first().copied().unwrap_or_default()
+    }
+    /// 1-based channel of channel message.
+    pub fn channel(&self) -> Option<u8> {
+        match self.status() {
+            0x80..=0xef => Some((self.status() & 0x0f) + 1),
+            _ => None,
+        }
+    }
+    /// The second byte of message: note, CC number, program etc.
+    pub fn data1(&self) -> Option<u8> {
+        self.message.get(1).copied()
+    }
+    /// The third byte of message: velocity, CC value etc.
+    pub fn data2(&self) -> Option<u8> {
+        self.message.get(2).copied()
+    }
+    /// Whether message is a Beizer tension of the previous event.
+    pub fn is_beizer(&self) -> bool {
+        self.message.starts_with(&BEIZER_HEADER)
+    }
+
+    /// Copy the view into [MidiEvent] of the given type.
+    ///
+    /// Beizer data is appended to message, so it is parsed the same way,
+    /// as in the [crate::MidiEventBuilder] filters.
+    pub fn to_event<T: MidiMessage>(&self) -> Option<MidiEvent<T>> {
+        if self.message.is_empty() {
+            return None;
+        }
+        let mut buf =
+            Vec::with_capacity(self.message.len() + self.beizer.len());
+        buf.extend_from_slice(self.message);
+        buf.extend_from_slice(self.beizer);
+        Some(MidiEvent::new(
+            self.ppq_position(),
+            self.selected(),
+            self.muted(),
+            self.cc_shape_kind(),
+            T::from_raw(buf)?,
+        ))
+    }
+    /// Copy the message (without Beizer data) into [RawMidiMessage] event.
+    pub fn to_raw_event(&self) -> MidiEvent<RawMidiMessage> {
+        MidiEvent::new(
+            self.ppq_position(),
+            self.selected(),
+            self.muted(),
+            self.cc_shape_kind(),
+            RawMidiMessage {
+                buf: self.message.to_vec(),
+            },
+        )
+    }
+}
+
+/// Iterates over borrowed take raw MIDI and yields [MidiEventRef].
+///
+/// Unlike [crate::MidiEventBuilder], malformed or truncated buffer does not panic:
+/// iteration just stops.
+#[derive(Debug, Clone, Copy)]
+pub struct MidiEventRefBuilder<'a> {
+    buf: &'a [u8],
+    current_ppq: u32,
+}
+impl<'a> MidiEventRefBuilder<'a> {
+    /// Accepts raw midi data, as described in the [crate::Take::get_midi]
+    /// doc.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            current_ppq: 0,
+        }
+    }
+    /// Iter only through CC events. Beizer data is attached.
+    pub fn filter_cc(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0xb0))
+    }
+    /// Iter only through Note On events.
+    pub fn filter_note_on(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0x90))
+    }
+    /// Iter only through Note Off events.
+    pub fn filter_note_off(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0x80))
+    }
+    /// Iter only through Pitch Bend events. Beizer data is attached.
+    pub fn filter_pitch_bend(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0xe0))
+    }
+    /// Iter only through polyphonic AfterTouch events.
+    pub fn filter_after_touch(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0xa0))
+    }
+    /// Iter only through Channel Pressure events. Beizer data is attached.
+    pub fn filter_channel_pressure(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0xd0))
+    }
+    /// Iter only through Program Change events.
+    pub fn filter_program_change(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Status(0xc0))
+    }
+    /// Iter only through SysEx and meta events, except Beizer data.
+    pub fn filter_all_sys(self) -> FilterRef<'a> {
+        FilterRef::new(self, RefFilterKind::Sys)
+    }
+    /// Iter through [crate::MidiNoteEvent].
+    ///
+    /// Only note events are copied.
+    pub fn filter_notes(self) -> FilterNotes<OwnedRefs<'a>> {
+        FilterNotes::new(OwnedRefs {
+            events: FilterRef::new(self, RefFilterKind::Notes),
+        })
+    }
+    /// Iter through [crate::ParameterNumberEvent].
+    ///
+    /// Only CC events are copied.
+    pub fn filter_parameter_numbers(
+        self,
+    ) -> FilterParameterNumbers<OwnedRefs<'a>> {
+        FilterParameterNumbers::new(OwnedRefs {
+            events: FilterRef::new(self, RefFilterKind::Status(0xb0)),
+        })
+    }
+    /// Copy every event into [RawMidiMessage] event, the same as
+    /// [crate::MidiEventBuilder] yields.
+    pub fn to_owned_events(self) -> OwnedRefs<'a> {
+        OwnedRefs {
+            events: FilterRef::new(self, RefFilterKind::All),
+        }
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.buf.get(..4)?;
+        self.buf = &self.buf[4..];
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+impl<'a> Iterator for MidiEventRefBuilder<'a> {
+    type Item = MidiEventRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.read_u32()?;
+        let (flag, rest) = self.buf.split_first()?;
+        self.buf = rest;
+        let length = self.read_u32()? as usize;
+        if length == 0 || length > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+        let (message, rest) = self.buf.split_at(length);
+        self.buf = rest;
+        self.current_ppq = self.current_ppq.wrapping_add(offset);
+        Some(MidiEventRef {
+            position_in_ppq: self.current_ppq,
+            flag: *flag,
+            message,
+            beizer: &[],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RefFilterKind {
+    All,
+    /// Channel message with the given high nibble of status byte.
+    Status(u8),
+    Notes,
+    Sys,
+}
+impl RefFilterKind {
+    fn accepts(&self, event: &MidiEventRef) -> bool {
+        let status = event.status();
+        let is_short = event.message.len() <= 3;
+        match self {
+            Self::All => true,
+            Self::Status(s) => is_short && status & 0xf0 == *s,
+            Self::Notes => is_short && matches!(status & 0xf0, 0x80 | 0x90),
+            Self::Sys => status >= 0xf0 && !event.is_beizer(),
+        }
+    }
+    fn has_beizer(&self) -> bool {
+        matches!(self, Self::Status(0xb0 | 0xd0 | 0xe0))
+    }
+}
+
+/// Iterates through [MidiEventRef] of one kind.
+///
+/// Created by [MidiEventRefBuilder] `filter_*` methods.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterRef<'a> {
+    events: MidiEventRefBuilder<'a>,
+    kind: RefFilterKind,
+}
+impl<'a> FilterRef<'a> {
+    fn new(events: MidiEventRefBuilder<'a>, kind: RefFilterKind) -> Self {
+        Self { events, kind }
+    }
+}
+impl<'a> Iterator for FilterRef<'a> {
+    type Item = MidiEventRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut event = self.events.next()?;
+            if !self.kind.accepts(&event) {
+                continue;
+            }
+            if self.kind.has_beizer()
+                && event.cc_shape_kind() == CcShapeKind::Beizer
+            {
+                let mut peek = self.events;
+                if let Some(beizer) = peek.next().filter(|e| e.is_beizer()) {
+                    event.beizer = beizer.message;
+                    self.events = peek;
+                }
+            }
+            return Some(event);
+        }
+    }
+}
+
+/// Copies borrowed events into [RawMidiMessage] events, so they can be
+/// passed to the generic filters, like [FilterNotes].
+#[derive(Debug, Clone, Copy)]
+pub struct OwnedRefs<'a> {
+    events: FilterRef<'a>,
+}
+impl<'a> Iterator for OwnedRefs<'a> {
+    type Item = MidiEvent<RawMidiMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next().map(|e| e.to_raw_event())
+    }
+}
+
+/// Writes events directly into take raw MIDI buffer.
+///
+/// Events should be pushed in order of their positions. The result of
+/// [MidiBufWriter::finish] can be passed to [crate::Take::set_midi].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MidiBufWriter {
+    buf: Vec<u8>,
+    last_ppq: u32,
+}
+impl MidiBufWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reserve buffer for `size` bytes.
+    pub fn with_capacity(size: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(size),
+            last_ppq: 0,
+        }
+    }
+    /// Write one event.
+    pub fn push_raw(
+        &mut self,
+        position_in_ppq: u32,
+        is_selected: bool,
+        is_muted: bool,
+        cc_shape_kind: CcShapeKind,
+        message: &[u8],
+    ) {
+        self.push_with_flag(
+            position_in_ppq,
+            event_flag(is_selected, is_muted, cc_shape_kind),
+            message,
+        )
+    }
+    /// Write one event with the raw flag byte, as got from
+    /// [MidiEventRef::flag].
+    pub fn push_with_flag(
+        &mut self,
+        position_in_ppq: u32,
+        flag: u8,
+        message: &[u8],
+    ) {
+        let offset = position_in_ppq.wrapping_sub(self.last_ppq);
+        self.last_ppq = position_in_ppq;
+        self.buf.reserve(9 + message.len());
+        self.buf.extend_from_slice(&offset.to_le_bytes());
+        self.buf.push(flag);
+        self.buf
+            .extend_from_slice(&(message.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(message);
+    }
+    /// Write event with the message as it is borrowed.
+    ///
+    /// For messages with Beizer data use [MidiBufWriter::push_shaped].
+    pub fn push<T: MidiMessage>(&mut self, event: &MidiEvent<T>) {
+        self.push_raw(
+            event.ppq_position(),
+            event.selected(),
+            event.muted(),
+            event.cc_shape_kind(),
+            event.message().borrow_raw(),
+        )
+    }
+    /// Write event and its Beizer data as a separate event, the same way
+    /// as [crate::midi::flatten_events_with_beizer_curve] does.
+    pub fn push_shaped<T: HasBeizer>(&mut self, event: &MidiEvent<T>) {
+        let message = event.message();
+        self.push_raw(
+            event.ppq_position(),
+            event.selected(),
+            event.muted(),
+            event.cc_shape_kind(),
+            message.msg_buf(),
+        );
+        if !message.beizer_buf().is_empty() {
+            self.push_raw(
+                event.ppq_position(),
+                event.selected(),
+                event.muted(),
+                CcShapeKind::Square,
+                message.beizer_buf(),
+            );
+        }
+    }
+    /// Write borrowed event together with attached Beizer data.
+    pub fn push_ref(&mut self, event: &MidiEventRef) {
+        self.push_raw(
+            event.ppq_position(),
+            event.selected(),
+            event.muted(),
+            event.cc_shape_kind(),
+            event.message(),
+        );
+        if !event.beizer_buf().is_empty() {
+            self.push_raw(
+                event.ppq_position(),
+                event.selected(),
+                event.muted(),
+                CcShapeKind::Square,
+                event.beizer_buf(),
+            );
+        }
+    }
+    /// Number of written bytes.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+impl<T: MidiMessage> Extend<MidiEvent<T>> for MidiBufWriter {
+    fn extend<I: IntoIterator<Item = MidiEvent<T>>>(&mut self, iter: I) {
+        for event in iter {
+            self.push(&event);
+        }
+    }
+}
+impl<'a> Extend<MidiEventRef<'a>> for MidiBufWriter {
+    fn extend<I: IntoIterator<Item = MidiEventRef<'a>>>(&mut self, iter: I) {
+        for event in iter {
+            self.push_ref(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CCMessage, MidiEventBuilder, MidiEventConsumer, MidiNoteEvent,
+        ParameterNumberKind,
+    };
+
+    /// The same buffer, written by [MidiEventConsumer].
+    fn consume(buf: &[u8]) -> Vec<u8> {
+        MidiEventConsumer::new(MidiEventBuilder::new(buf.to_vec().into_iter()))
+            .collect()
+    }
+
+    fn test_buf() -> Vec<u8> {
+        let mut cc = MidiEvent::new(
+            10,
+            true,
+            false,
+            CcShapeKind::Beizer,
+            CCMessage::new(2, 11, 64),
+        );
+        cc.message_mut().set_beizer_tension(-0.25);
+        let mut writer = MidiBufWriter::new();
+        writer.push_raw(0, false, false, CcShapeKind::Square, &[0x91, 60, 90]);
+        writer.push_shaped(&cc);
+        writer.push_raw(10, false, true, CcShapeKind::Square, &[0xc0, 5]);
+        writer.push_raw(20, false, false, CcShapeKind::Square, &[0x81, 60, 0]);
+        writer.push_raw(
+            30,
+            false,
+            false,
+            CcShapeKind::Square,
+            &[0xf0, 1, 0xf7],
+        );
+        writer.push_raw(
+            40,
+            false,
+            false,
+            CcShapeKind::Linear,
+            &[0xb0, 101, 0],
+        );
+        writer.push_raw(
+            40,
+            false,
+            false,
+            CcShapeKind::Square,
+            &[0xb0, 100, 0],
+        );
+        writer.push_raw(40, false, false, CcShapeKind::Square, &[0xb0, 6, 2]);
+        writer.push_raw(
+            50,
+            false,
+            false,
+            CcShapeKind::Square,
+            &[0xb0, 123, 0],
+        );
+        writer.finish()
+    }
+
+    #[test]
+    fn test_same_as_builder() {
+        let buf = test_buf();
+        let owned: Vec<_> =
+            MidiEventBuilder::new(buf.clone().into_iter()).collect();
+        let borrowed: Vec<_> =
+            MidiEventRefBuilder::new(&buf).to_owned_events().collect();
+        assert_eq!(owned, borrowed);
+        assert_eq!(consume(&buf), buf);
+
+        let mut writer = MidiBufWriter::new();
+        writer.extend(MidiEventRefBuilder::new(&buf));
+        assert_eq!(writer.finish(), buf);
+        let mut writer = MidiBufWriter::with_capacity(buf.len());
+        writer.extend(owned);
+        assert_eq!(writer.finish(), buf);
+    }
+
+    #[test]
+    fn test_filters() {
+        let buf = test_buf();
+        let cc: Vec<_> = MidiEventRefBuilder::new(&buf).filter_cc().collect();
+        assert_eq!(cc.len(), 5);
+        assert_eq!(cc[0].channel(), Some(2));
+        assert_eq!(cc[0].beizer_tension(), Some(-0.25));
+        assert!(cc[0].selected());
+        let typed: MidiEvent<CCMessage> = cc[0].to_event().unwrap();
+        let owned = MidiEventBuilder::new(buf.clone().into_iter())
+            .filter_cc()
+            .next()
+            .unwrap();
+        assert_eq!(typed, owned);
+
+        let program: Vec<_> = MidiEventRefBuilder::new(&buf)
+            .filter_program_change()
+            .collect();
+        assert_eq!(program.len(), 1);
+        assert!(program[0].muted());
+        assert_eq!(program[0].data1(), Some(5));
+        assert_eq!(program[0].data2(), None);
+
+        let sys: Vec<_> =
+            MidiEventRefBuilder::new(&buf).filter_all_sys().collect();
+        assert_eq!(sys.len(), 1);
+        assert_eq!(sys[0].message(), &[0xf0, 1, 0xf7]);
+
+        let notes: Vec<MidiNoteEvent> =
+            MidiEventRefBuilder::new(&buf).filter_notes().collect();
+        assert_eq!(
+            notes,
+            vec![MidiNoteEvent::new(0, 20, false, false, 2, 60, 90, 0)]
+        );
+        let params: Vec<_> = MidiEventRefBuilder::new(&buf)
+            .filter_parameter_numbers()
+            .collect();
+        let owned: Vec<_> = MidiEventBuilder::new(buf.clone().into_iter())
+            .filter_parameter_numbers()
+            .collect();
+        assert_eq!(params, owned);
+        assert_eq!(
+            params[1].kind,
+            ParameterNumberKind::PITCH_BEND_SENSITIVITY
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let buf = test_buf();
+        for end in 0..buf.len() {
+            let events = MidiEventRefBuilder::new(&buf[..end]).count();
+            assert!(events <= 9);
+        }
+        assert_eq!(MidiEventRefBuilder::new(&buf).count(), 10);
+    }
+}
@@ -0,0 +1,399 @@
+//! Conversion between CC lanes and automation envelopes.
+//!
+//! CC events become [EnvelopePoint] with project [Position], ready for
+//! [crate::Envelope::insert_point] (or [crate::Envelope::insert_points]),
+//! and envelope points become [CCMessage] events for
+//! [crate::Take::set_midi]. Shapes are converted one-to-one, Bezier
+//! tension is taken from [HasBeizer].
+//!
+//! Time is converted by closures, so any tempo map can be used. With a
+//! take, [crate::Take::ppq_to_position] and
+//! [crate::Take::position_to_ppq] are used by
+//! [crate::Take::cc_lane_envelope_points] and
+//! [crate::Take::set_cc_lane_from_envelope_points].
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{midi::*, EnvelopePointShape, Position};
+//! use std::time::Duration;
+//!
+//! // 960 ppq per quarter at 120 bpm.
+//! let to_position = |ppq: u32| {
+//!     Position::from(Duration::from_secs_f64(ppq as f64 / 1920.0))
+//! };
+//! let to_ppq =
+//!     |pos: Position| (pos.as_duration().as_secs_f64() * 1920.0) as u32;
+//!
+//! let cc = |ppq, shape, value| {
+//!     MidiEvent::new(ppq, false, false, shape, CCMessage::new(1, 1, value))
+//! };
+//! let lane = vec![
+//!     cc(0, CcShapeKind::Linear, 0),
+//!     cc(480, CcShapeKind::Linear, 64),
+//!     cc(960, CcShapeKind::Square, 127),
+//! ];
+//! let range = CcValueRange::default();
+//! let points = cc_lane_to_envelope_points(&lane, &range, to_position);
+//! assert_eq!(points[2].0, Position::from(0.5));
+//! assert_eq!(points[2].1.value, 1.0);
+//! assert_eq!(points[0].1.shape, EnvelopePointShape::Linear);
+//!
+//! // The middle point is almost on the line.
+//! let thinned = thin_envelope_points(&points, 0.01);
+//! assert_eq!(thinned.len(), 2);
+//!
+//! let back = envelope_points_to_cc_lane(&thinned, 1, 1, &range, to_ppq);
+//! assert_eq!(back[1].ppq_position(), 960);
+//! assert_eq!(back[1].message().cc_val(), 127);
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    flatten_events_with_beizer_curve, CCMessage, CcShapeKind, EnvelopePoint,
+    EnvelopePointShape, HasBeizer, MidiEvent, MidiEventConsumer, MidiMessage,
+    Mutable, Position, ProbablyMutable, RawMidiMessage, ReaperResult, Take,
+};
+
+use super::BEIZER_HEADER;
+
+impl From<CcShapeKind> for EnvelopePointShape {
+    fn from(value: CcShapeKind) -> Self {
+        match value {
+            CcShapeKind::Square => Self::Square,
+            CcShapeKind::Linear => Self::Linear,
+            CcShapeKind::SlowStartEnd => Self::SlowStartEnd,
+            CcShapeKind::FastStart => Self::FastStart,
+            CcShapeKind::FastEnd => Self::FastEnd,
+            CcShapeKind::Beizer => Self::Beizer,
+        }
+    }
+}
+impl From<EnvelopePointShape> for CcShapeKind {
+    fn from(value: EnvelopePointShape) -> Self {
+        match value {
+            EnvelopePointShape::Square => Self::Square,
+            EnvelopePointShape::Linear => Self::Linear,
+            EnvelopePointShape::SlowStartEnd => Self::SlowStartEnd,
+            EnvelopePointShape::FastStart => Self::FastStart,
+            EnvelopePointShape::FastEnd => Self::FastEnd,
+            EnvelopePointShape::Beizer => Self::Beizer,
+        }
+    }
+}
+
+/// Envelope values, that correspond to CC values 0 and 127.
+///
+/// Values are scaled linearly. `min` may be greater than `max` for
+/// inverted mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcValueRange {
+    pub min: f64,
+    pub max: f64,
+}
+impl Default for CcValueRange {
+    /// Normalized FX parameter range: `0.0..=1.0`.
+    fn default() -> Self {
+        Self::new(0.0, 1.0)
+    }
+}
+impl CcValueRange {
+    pub fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+    pub fn cc_to_value(&self, cc_value: f64) -> f64 {
+        self.min + (self.max - self.min) * cc_value / 127.0
+    }
+    /// Rounded and clamped CC value.
+    pub fn value_to_cc(&self, value: f64) -> u8 {
+        if self.max == self.min {
+            return 0;
+        }
+        let cc = (value - self.min) / (self.max - self.min) * 127.0;
+        cc.round().clamp(0.0, 127.0) as u8
+    }
+}
+
+/// Convert CC lane (events of one channel and controller) to envelope
+/// points.
+pub fn cc_lane_to_envelope_points(
+    lane: &[MidiEvent<CCMessage>],
+    range: &CcValueRange,
+    ppq_to_position: impl Fn(u32) -> Position,
+) -> Vec<(Position, EnvelopePoint)> {
+    lane.iter()
+        .map(|event| {
+            let msg = event.message();
+            let point = EnvelopePoint::new(
+                range.cc_to_value(msg.cc_val() as f64),
+                event.cc_shape_kind().into(),
+                msg.beizer_tension().unwrap_or(0.0),
+                event.selected(),
+            );
+            (ppq_to_position(event.ppq_position()), point)
+        })
+        .collect()
+}
+
+/// Convert envelope points to CC lane.
+///
+/// Points, that fall to the same ppq position, are collapsed to the last
+/// one.
+pub fn envelope_points_to_cc_lane(
+    points: &[(Position, EnvelopePoint)],
+    channel: u8,
+    cc_num: u8,
+    range: &CcValueRange,
+    position_to_ppq: impl Fn(Position) -> u32,
+) -> Vec<MidiEvent<CCMessage>> {
+    let mut lane: Vec<MidiEvent<CCMessage>> = Vec::new();
+    for (position, point) in points {
+        let mut msg =
+            CCMessage::new(channel, cc_num, range.value_to_cc(point.value));
+        if point.shape == EnvelopePointShape::Beizer {
+            msg.set_beizer_tension(point.tension as f32);
+        }
+        let event = MidiEvent::new(
+            position_to_ppq(*position),
+            point.selected,
+            false,
+            point.shape.into(),
+            msg,
+        );
+        match lane.last_mut() {
+            Some(last) if last.ppq_position() == event.ppq_position() => {
+                *last = event
+            }
+            _ => lane.push(event),
+        }
+    }
+    lane
+}
+
+/// Indices of points, that are kept after thinning.
+///
+/// Linear runs are approximated by straight lines, square runs by steps.
+/// The first and the last points are always kept.
+fn thinned_indices(
+    positions: &[f64],
+    values: &[f64],
+    shapes: &[CcShapeKind],
+    tolerance: f64,
+) -> Vec<usize> {
+    let len = positions.len();
+    if len < 3 {
+        return (0..len).collect();
+    }
+    let mut kept = vec![0];
+    let mut anchor = 0;
+    for idx in 1..len - 1 {
+        let next = idx + 1;
+        let shape = shapes[anchor];
+        let same_shape = shapes[anchor + 1..=idx].iter().all(|s| *s == shape);
+        let fits = |k: usize| match shape {
+            CcShapeKind::Square => {
+                (values[k] - values[anchor]).abs() <= tolerance
+            }
+            CcShapeKind::Linear => {
+                let span = positions[next] - positions[anchor];
+                if span <= 0.0 {
+                    return false;
+                }
+                let t = (positions[k] - positions[anchor]) / span;
+                let line =
+                    values[anchor] + (values[next] - values[anchor]) * t;
+                (values[k] - line).abs() <= tolerance
+            }
+            _ => false,
+        };
+        if !(same_shape && (anchor + 1..=idx).all(fits)) {
+            kept.push(idx);
+            anchor = idx;
+        }
+    }
+    kept.push(len - 1);
+    kept
+}
+
+/// Remove CC events, that can be restored by linear or square shape
+/// within `tolerance` (in CC values).
+pub fn thin_cc_lane(
+    lane: &[MidiEvent<CCMessage>],
+    tolerance: f64,
+) -> Vec<MidiEvent<CCMessage>> {
+    let positions: Vec<f64> =
+        lane.iter().map(|e| e.ppq_position() as f64).collect();
+    let values: Vec<f64> =
+        lane.iter().map(|e| e.message().cc_val() as f64).collect();
+    let shapes: Vec<CcShapeKind> =
+        lane.iter().map(|e| e.cc_shape_kind()).collect();
+    thinned_indices(&positions, &values, &shapes, tolerance)
+        .into_iter()
+        .map(|idx| lane[idx].clone())
+        .collect()
+}
+
+/// Remove envelope points, that can be restored by linear or square shape
+/// within `tolerance` (in envelope values).
+pub fn thin_envelope_points(
+    points: &[(Position, EnvelopePoint)],
+    tolerance: f64,
+) -> Vec<(Position, EnvelopePoint)> {
+    let positions: Vec<f64> = points
+        .iter()
+        .map(|(pos, _)| pos.as_duration().as_secs_f64())
+        .collect();
+    let values: Vec<f64> = points.iter().map(|(_, p)| p.value).collect();
+    let shapes: Vec<CcShapeKind> =
+        points.iter().map(|(_, p)| p.shape.into()).collect();
+    thinned_indices(&positions, &values, &shapes, tolerance)
+        .into_iter()
+        .map(|idx| points[idx])
+        .collect()
+}
+
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// Envelope points of the CC lane in project time.
+    pub fn cc_lane_envelope_points(
+        &self,
+        channel: u8,
+        cc_num: u8,
+        range: &CcValueRange,
+    ) -> ReaperResult<Vec<(Position, EnvelopePoint)>> {
+        let lane: Vec<MidiEvent<CCMessage>> = self
+            .iter_midi(None)?
+            .filter_cc()
+            .filter(|e| {
+                e.message().channel() == channel
+                    && e.message().cc_num() == cc_num
+            })
+            .collect();
+        Ok(cc_lane_to_envelope_points(&lane, range, |ppq| {
+            self.ppq_to_position(ppq as f64)
+        }))
+    }
+}
+impl<'a> Take<'a, Mutable> {
+    /// Replace the CC lane by events, made from envelope points.
+    ///
+    /// Other MIDI events are kept.
+    pub fn set_cc_lane_from_envelope_points(
+        &mut self,
+        channel: u8,
+        cc_num: u8,
+        points: &[(Position, EnvelopePoint)],
+        range: &CcValueRange,
+    ) -> ReaperResult<()> {
+        let lane = envelope_points_to_cc_lane(
+            points,
+            channel,
+            cc_num,
+            range,
+            |pos| self.position_to_ppq(pos).round().max(0.0) as u32,
+        );
+        let mut events: Vec<MidiEvent<RawMidiMessage>> = Vec::new();
+        let mut in_lane = false;
+        for event in self.iter_midi(None)? {
+            let buf = event.message().get_raw();
+            if buf.starts_with(&BEIZER_HEADER) {
+                if !in_lane {
+                    events.push(event);
+                }
+                continue;
+            }
+            in_lane = buf.len() == 3
+                && buf[0] == 0xb0 + channel - 1
+                && buf[1] == cc_num;
+            if !in_lane {
+                events.push(event);
+            }
+        }
+        events.extend(flatten_events_with_beizer_curve(lane.into_iter()));
+        // stable sort keeps Beizer data right after its event.
+        events.sort_by_key(|e| e.ppq_position());
+        self.set_midi(MidiEventConsumer::new(events.into_iter()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(pos: u32, shape: CcShapeKind, value: u8) -> MidiEvent<CCMessage> {
+        MidiEvent::new(pos, false, false, shape, CCMessage::new(2, 7, value))
+    }
+
+    fn to_position(ppq: u32) -> Position {
+        Position::from(ppq as f64 / 1000.0)
+    }
+
+    fn to_ppq(position: Position) -> u32 {
+        (position.as_duration().as_secs_f64() * 1000.0).round() as u32
+    }
+
+    #[test]
+    fn test_round_trip_with_beizer() {
+        let mut shaped = cc(100, CcShapeKind::Beizer, 30);
+        shaped.message_mut().set_beizer_tension(-0.5);
+        let lane = vec![
+            cc(0, CcShapeKind::Square, 0),
+            shaped,
+            cc(250, CcShapeKind::FastEnd, 127),
+        ];
+        let range = CcValueRange::new(-1.0, 1.0);
+        let points = cc_lane_to_envelope_points(&lane, &range, to_position);
+        assert_eq!(points[0].1.value, -1.0);
+        assert_eq!(points[1].1.shape, EnvelopePointShape::Beizer);
+        assert_eq!(points[1].1.tension, -0.5);
+        assert_eq!(points[2].1.shape, EnvelopePointShape::FastEnd);
+        let back = envelope_points_to_cc_lane(&points, 2, 7, &range, to_ppq);
+        assert_eq!(back, lane);
+    }
+
+    #[test]
+    fn test_value_range() {
+        let range = CcValueRange::new(1.0, 0.0);
+        assert_eq!(range.cc_to_value(127.0), 0.0);
+        assert_eq!(range.value_to_cc(0.0), 127);
+        assert_eq!(range.value_to_cc(2.0), 0);
+        assert_eq!(CcValueRange::new(0.5, 0.5).value_to_cc(0.5), 0);
+    }
+
+    #[test]
+    fn test_collapse_same_ppq() {
+        let point = |value| {
+            EnvelopePoint::new(value, EnvelopePointShape::Square, 0.0, false)
+        };
+        let points = vec![
+            (Position::from(0.0), point(0.0)),
+            (Position::from(0.1), point(0.0)),
+            (Position::from(0.1), point(1.0)),
+        ];
+        let lane = envelope_points_to_cc_lane(
+            &points,
+            1,
+            1,
+            &CcValueRange::default(),
+            to_ppq,
+        );
+        assert_eq!(lane.len(), 2);
+        assert_eq!(lane[1].message().cc_val(), 127);
+    }
+
+    #[test]
+    fn test_thinning() {
+        let mut lane: Vec<_> = (0..=10)
+            .map(|i| cc(i * 10, CcShapeKind::Linear, i as u8 * 10))
+            .collect();
+        lane.push(cc(110, CcShapeKind::Square, 0));
+        lane.push(cc(120, CcShapeKind::Square, 0));
+        lane.push(cc(130, CcShapeKind::Square, 1));
+        lane.push(cc(140, CcShapeKind::Square, 50));
+        let thinned = thin_cc_lane(&lane, 1.0);
+        let positions: Vec<u32> =
+            thinned.iter().map(|e| e.ppq_position()).collect();
+        assert_eq!(positions, vec![0, 100, 110, 140]);
+        assert_eq!(thin_cc_lane(&lane, 0.0).len(), 5);
+    }
+}
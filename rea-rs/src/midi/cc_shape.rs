@@ -0,0 +1,358 @@
+//! Evaluation of CC curves between events.
+//!
+//! In REAPER, every CC-like event ([CCMessage], [ChannelPressureMessage],
+//! [PitchBendMessage]) defines the shape of the curve to the next event of
+//! the same lane (the same channel and, for CC, the same controller). The
+//! shape is stored in the event flag as [CcShapeKind], and Bezier tension is
+//! stored in [HasBeizer::beizer_tension].
+//!
+//! # Note
+//!
+//! Square, linear, slow start/end and fast start/end shapes follow the
+//! REAPER formulas. REAPER does not expose its Bezier formula, so Bezier is
+//! evaluated by rea-rs model: quadratic Bezier curve from `(0, 0)` to
+//! `(1, 1)` with control point `((1 + tension) / 2, (1 - tension) / 2)`.
+//! Zero tension is linear, `1.0` and `-1.0` pull the curve to the lower
+//! right and upper left corners. The model is not checked against REAPER
+//! playback, so values of Bezier segments are approximate.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let lane = vec![
+//!     MidiEvent::new(
+//!         0,
+//!         false,
+//!         false,
+//!         CcShapeKind::Linear,
+//!         CCMessage::new(1, 1, 0),
+//!     ),
+//!     MidiEvent::new(
+//!         100,
+//!         false,
+//!         false,
+//!         CcShapeKind::Square,
+//!         CCMessage::new(1, 1, 100),
+//!     ),
+//! ];
+//! assert_eq!(cc_lane_value_at(&lane, 50), Some(50.0));
+//! let dense = rasterize_cc_lane(&lane, 10);
+//! assert_eq!(dense.len(), 11);
+//! assert_eq!(dense[3].message().cc_val(), 30);
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::{
+    CCMessage, CcShapeKind, ChannelPressureMessage, HasBeizer, MidiEvent,
+    PitchBendMessage,
+};
+
+/// Message, which value can be interpolated by CC shape.
+pub trait ShapedMessage: HasBeizer {
+    /// Numeric value of message: 0..127 for CC and pressure, 0..16383 for
+    /// pitch bend.
+    fn shape_value(&self) -> f64;
+    /// Copy of message with the new value (rounded and clamped). Bezier
+    /// data is not copied.
+    fn with_shape_value(&self, value: f64) -> Self;
+}
+impl ShapedMessage for CCMessage {
+    fn shape_value(&self) -> f64 {
+        self.cc_val() as f64
+    }
+    fn with_shape_value(&self, value: f64) -> Self {
+        CCMessage::new(
+            self.channel(),
+            self.cc_num(),
+            value.round().clamp(0.0, 127.0) as u8,
+        )
+    }
+}
+impl ShapedMessage for ChannelPressureMessage {
+    fn shape_value(&self) -> f64 {
+        self.pressure() as f64
+    }
+    fn with_shape_value(&self, value: f64) -> Self {
+        let mut msg = self.clone();
+        msg.set_beizer_buf(vec![]);
+        msg.set_pressure(value.round().clamp(0.0, 127.0) as u8);
+        msg
+    }
+}
+impl ShapedMessage for PitchBendMessage {
+    fn shape_value(&self) -> f64 {
+        self.raw_value() as f64
+    }
+    fn with_shape_value(&self, value: f64) -> Self {
+        PitchBendMessage::new(
+            self.channel(),
+            value.round().clamp(0.0, 16383.0) as u16,
+        )
+    }
+}
+
+/// Normalized (0.0..=1.0) curve progress at normalized position `t`.
+///
+/// `tension` is used only by [CcShapeKind::Beizer] and is in range
+/// `-1.0..=1.0`.
+pub fn cc_shape_curve(shape: CcShapeKind, tension: f64, t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    match shape {
+        CcShapeKind::Square => 0.0,
+        CcShapeKind::Linear => t,
+        CcShapeKind::SlowStartEnd => t * t * (3.0 - 2.0 * t),
+        CcShapeKind::FastStart => 1.0 - (1.0 - t).powi(3),
+        CcShapeKind::FastEnd => t.powi(3),
+        CcShapeKind::Beizer => quadratic_beizer(tension.clamp(-1.0, 1.0), t),
+    }
+}
+
+/// See module docs for the curve model.
+fn quadratic_beizer(tension: f64, t: f64) -> f64 {
+    // Control point: (0.5, 0.5) is linear, (1, 0) and (0, 1) are corners.
+    let cx = (1.0 + tension) / 2.0;
+    let cy = (1.0 - tension) / 2.0;
+    // solve x(s) = 2s(1-s)cx + s^2 = t
+    let a = 1.0 - 2.0 * cx;
+    let s = match a.abs() < 1e-9 {
+        true => t / (2.0 * cx),
+        false => {
+            let d = (4.0 * cx * cx + 4.0 * a * t).max(0.0);
+            (-2.0 * cx + d.sqrt()) / (2.0 * a)
+        }
+    };
+    let s = s.clamp(0.0, 1.0);
+    2.0 * s * (1.0 - s) * cy + s * s
+}
+
+/// Value of the curve between two events at the given position.
+///
+/// Shape of `from` event is used. Position is clamped to the segment.
+pub fn cc_shape_value<T: ShapedMessage>(
+    from: &MidiEvent<T>,
+    to: &MidiEvent<T>,
+    position: u32,
+) -> f64 {
+    let (start, end) = (from.ppq_position(), to.ppq_position());
+    let (v0, v1) = (from.message().shape_value(), to.message().shape_value());
+    if end <= start || position <= start {
+        return v0;
+    }
+    if position >= end {
+        return v1;
+    }
+    let t = (position - start) as f64 / (end - start) as f64;
+    let tension = from.message().beizer_tension().unwrap_or(0.0);
+    v0 + (v1 - v0) * cc_shape_curve(from.cc_shape_kind(), tension, t)
+}
+
+/// Value of the lane at position.
+///
+/// Lane should be sorted by position and contain events of one channel
+/// and controller. Returns None if lane is empty or position is before the
+/// first event.
+pub fn cc_lane_value_at<T: ShapedMessage>(
+    lane: &[MidiEvent<T>],
+    position: u32,
+) -> Option<f64> {
+    let idx = lane.partition_point(|e| e.ppq_position() <= position);
+    match idx {
+        0 => None,
+        i if i == lane.len() => Some(lane[i - 1].message().shape_value()),
+        i => Some(cc_shape_value(&lane[i - 1], &lane[i], position)),
+    }
+}
+
+/// Replace every shaped segment with events each `resolution` ticks,
+/// connected linearly.
+///
+/// Square segments are kept as is. Generated events have the selection and
+/// mute state of the segment start event. The last event of the lane is
+/// kept with its shape.
+pub fn rasterize_cc_lane<T: ShapedMessage>(
+    lane: &[MidiEvent<T>],
+    resolution: u32,
+) -> Vec<MidiEvent<T>> {
+    let resolution = resolution.max(1);
+    let mut out = Vec::new();
+    for pair in lane.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        if from.cc_shape_kind() == CcShapeKind::Square {
+            out.push(from.clone());
+            continue;
+        }
+        let mut position = from.ppq_position();
+        loop {
+            let value = cc_shape_value(from, to, position);
+            let mut event = MidiEvent::with_new_message(
+                from.clone(),
+                from.message().with_shape_value(value),
+            );
+            event.set_ppq_position(position);
+            event.set_cc_shape_kind(CcShapeKind::Linear);
+            out.push(event);
+            position += resolution;
+            if position >= to.ppq_position() {
+                break;
+            }
+        }
+    }
+    if let Some(last) = lane.last() {
+        out.push(last.clone());
+    }
+    out
+}
+
+/// Split CC events by lanes: (channel, controller number).
+///
+/// Order of events inside lane is kept.
+pub fn split_cc_lanes(
+    events: impl Iterator<Item = MidiEvent<CCMessage>>,
+) -> BTreeMap<(u8, u8), Vec<MidiEvent<CCMessage>>> {
+    let mut lanes: BTreeMap<(u8, u8), Vec<MidiEvent<CCMessage>>> =
+        BTreeMap::new();
+    for event in events {
+        let key = (event.message().channel(), event.message().cc_num());
+        lanes.entry(key).or_default().push(event);
+    }
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(pos: u32, shape: CcShapeKind, value: u8) -> MidiEvent<CCMessage> {
+        MidiEvent::new(pos, false, false, shape, CCMessage::new(1, 11, value))
+    }
+
+    #[test]
+    fn test_shapes() {
+        for shape in [
+            CcShapeKind::Linear,
+            CcShapeKind::SlowStartEnd,
+            CcShapeKind::FastStart,
+            CcShapeKind::FastEnd,
+            CcShapeKind::Beizer,
+        ] {
+            assert_eq!(cc_shape_curve(shape, 0.7, 0.0), 0.0);
+            assert!((cc_shape_curve(shape, -0.7, 1.0) - 1.0).abs() < 1e-9);
+        }
+        assert_eq!(cc_shape_curve(CcShapeKind::Square, 0.0, 0.9), 0.0);
+        assert_eq!(cc_shape_curve(CcShapeKind::SlowStartEnd, 0.0, 0.5), 0.5);
+        assert_eq!(cc_shape_curve(CcShapeKind::FastEnd, 0.0, 0.5), 0.125);
+        assert_eq!(cc_shape_curve(CcShapeKind::FastStart, 0.0, 0.5), 0.875);
+        // zero tension is linear
+        let b = cc_shape_curve(CcShapeKind::Beizer, 0.0, 0.3);
+        assert!((b - 0.3).abs() < 1e-9);
+        // positive tension is slow start
+        assert!(cc_shape_curve(CcShapeKind::Beizer, 0.5, 0.3) < 0.3);
+        assert!(cc_shape_curve(CcShapeKind::Beizer, -0.5, 0.3) > 0.3);
+    }
+
+    #[test]
+    fn test_beizer_values() {
+        let curve =
+            |tension, t| cc_shape_curve(CcShapeKind::Beizer, tension, t);
+        // Points of the model curve, got from its parametric form at s=0.5.
+        for (tension, t, value) in [
+            (1.0, 0.75, 0.25),
+            (-1.0, 0.25, 0.75),
+            (0.5, 0.625, 0.375),
+            (-0.5, 0.375, 0.625),
+        ] {
+            assert!((curve(tension, t) - value).abs() < 1e-9, "{}", tension);
+        }
+        // tension is clamped
+        assert_eq!(curve(3.0, 0.75), curve(1.0, 0.75));
+        // opposite tensions are symmetric
+        for tension in [0.2, 0.7, 1.0] {
+            for t in [0.1, 0.4, 0.9] {
+                let sym = 1.0 - curve(-tension, 1.0 - t);
+                assert!((curve(tension, t) - sym).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lane_value() {
+        let mut beizer = cc(100, CcShapeKind::Beizer, 100);
+        beizer.message_mut().set_beizer_tension(0.5);
+        let lane = vec![
+            cc(0, CcShapeKind::Square, 10),
+            beizer,
+            cc(200, CcShapeKind::FastEnd, 0),
+            cc(300, CcShapeKind::Square, 80),
+        ];
+        assert_eq!(cc_lane_value_at(&lane, 0), Some(10.0));
+        assert_eq!(cc_lane_value_at(&lane, 99), Some(10.0));
+        assert_eq!(cc_lane_value_at(&lane, 100), Some(100.0));
+        let mid = cc_lane_value_at(&lane, 150).unwrap();
+        assert!(mid > 50.0 && mid < 100.0);
+        assert_eq!(cc_lane_value_at(&lane, 250), Some(10.0));
+        assert_eq!(cc_lane_value_at(&lane, 1000), Some(80.0));
+        assert_eq!(cc_lane_value_at::<CCMessage>(&[], 10), None);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let lane = vec![
+            cc(0, CcShapeKind::Square, 0),
+            cc(10, CcShapeKind::FastEnd, 0),
+            cc(40, CcShapeKind::Square, 127),
+        ];
+        let dense = rasterize_cc_lane(&lane, 10);
+        let values: Vec<_> = dense
+            .iter()
+            .map(|e| (e.ppq_position(), e.message().cc_val()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![(0, 0), (10, 0), (20, 5), (30, 38), (40, 127)]
+        );
+        assert!(dense[1..4]
+            .iter()
+            .all(|e| e.cc_shape_kind() == CcShapeKind::Linear));
+
+        let pb = vec![
+            MidiEvent::new(
+                0,
+                false,
+                false,
+                CcShapeKind::Linear,
+                PitchBendMessage::new(2, 0),
+            ),
+            MidiEvent::new(
+                4,
+                false,
+                false,
+                CcShapeKind::Square,
+                PitchBendMessage::new(2, 16383),
+            ),
+        ];
+        let dense = rasterize_cc_lane(&pb, 2);
+        assert_eq!(dense[1].message().raw_value(), 8192);
+        assert_eq!(dense[1].message().channel(), 2);
+    }
+
+    #[test]
+    fn test_split_lanes() {
+        let events = vec![
+            cc(0, CcShapeKind::Square, 0),
+            MidiEvent::new(
+                5,
+                false,
+                false,
+                CcShapeKind::Square,
+                CCMessage::new(2, 11, 3),
+            ),
+            cc(10, CcShapeKind::Square, 1),
+        ];
+        let lanes = split_cc_lanes(events.into_iter());
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(lanes[&(1, 11)].len(), 2);
+    }
+}
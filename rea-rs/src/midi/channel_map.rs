@@ -0,0 +1,511 @@
+//! Split, merge and remap MIDI channels of raw event streams.
+//!
+//! [ChannelRemap] moves or drops channels and filters notes by range.
+//! [remap_channels] applies it to one stream, [merge_midi_events] — to
+//! several streams, combined into one. [split_by_channel] makes a separate
+//! stream for every channel.
+//!
+//! Notes are remapped as [MidiNoteEvent] pairs, so note-on and note-off
+//! are always moved together. If merged notes with the same channel and
+//! pitch overlap, they are trimmed by
+//! [crate::midi::trim_note_overlaps]. Beizer curves follow their events.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let drums = flatten_midi_notes(
+//!     vec![MidiNoteEvent::new(0, 240, false, false, 1, 36, 100, 0)]
+//!         .into_iter(),
+//! );
+//! let bass = flatten_midi_notes(
+//!     vec![
+//!         MidiNoteEvent::new(0, 480, false, false, 1, 40, 100, 0),
+//!         MidiNoteEvent::new(0, 480, false, false, 1, 100, 100, 0),
+//!     ]
+//!     .into_iter(),
+//! );
+//! let merged = merge_midi_events(vec![
+//!     (drums.collect(), ChannelRemap::new().map_channel(1, 10)),
+//!     (bass.collect(), ChannelRemap::new().note_range(0..=60)),
+//! ]);
+//! let split = split_by_channel(merged.into_iter());
+//! assert_eq!(split.channels(), vec![1, 10]);
+//! assert_eq!(split.channel_events(1, false).len(), 2);
+//! assert_eq!(split.channel_events(10, false).len(), 2);
+//! ```
+
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    flatten_midi_notes, AfterTouchMessage, CCMessage, ChannelPressureMessage,
+    FilterNotes, GetLength, MidiEvent, MidiEventConsumer, MidiNoteEvent,
+    Mutable, NoteOffMessage, NoteOnMessage, PitchBendMessage, Position,
+    ProbablyMutable, ProgramChangeMessage, RawMidiMessage, ReaperResult, Take,
+    Track,
+};
+
+use super::{trim_note_overlaps, ShortMessage, BEIZER_HEADER};
+
+fn short_channel<T: ShortMessage>(buf: &[u8]) -> Option<u8> {
+    Some(T::from_raw(buf.to_vec())?.channel_private())
+}
+
+fn set_short_channel<T: ShortMessage>(
+    msg: &mut RawMidiMessage,
+    channel: u8,
+) -> Option<()> {
+    let mut typed = T::from_raw(msg.buf.clone())?;
+    typed.set_channel_private(channel);
+    msg.buf = typed.get_raw();
+    Some(())
+}
+
+impl RawMidiMessage {
+    /// 1-based channel, if message is a channel message.
+    pub fn channel(&self) -> Option<u8> {
+        let buf = self.buf.as_slice();
+        match buf.first()? & 0xf0 {
+            0x80 => short_channel::<NoteOffMessage>(buf),
+            0x90 => short_channel::<NoteOnMessage>(buf),
+            0xa0 => short_channel::<AfterTouchMessage>(buf),
+            0xb0 => short_channel::<CCMessage>(buf),
+            0xc0 => short_channel::<ProgramChangeMessage>(buf),
+            0xd0 => short_channel::<ChannelPressureMessage>(buf),
+            0xe0 => short_channel::<PitchBendMessage>(buf),
+            _ => None,
+        }
+    }
+    /// Set 1-based channel of channel message.
+    ///
+    /// Returns `None` and leaves message untouched, if it is not a channel
+    /// message.
+    pub fn set_channel(&mut self, channel: u8) -> Option<()> {
+        assert!((1..=16).contains(&channel), "channel is 1-based");
+        match self.buf.first()? & 0xf0 {
+            0x80 => set_short_channel::<NoteOffMessage>(self, channel),
+            0x90 => set_short_channel::<NoteOnMessage>(self, channel),
+            0xa0 => set_short_channel::<AfterTouchMessage>(self, channel),
+            0xb0 => set_short_channel::<CCMessage>(self, channel),
+            0xc0 => set_short_channel::<ProgramChangeMessage>(self, channel),
+            0xd0 => set_short_channel::<ChannelPressureMessage>(self, channel),
+            0xe0 => set_short_channel::<PitchBendMessage>(self, channel),
+            _ => None,
+        }
+    }
+    fn is_note(&self) -> bool {
+        matches!(self.buf.first().map(|b| b & 0xf0), Some(0x80 | 0x90))
+    }
+    fn is_beizer(&self) -> bool {
+        self.buf.starts_with(&BEIZER_HEADER)
+    }
+}
+
+/// Which channels and notes are kept, and where channels are moved.
+///
+/// By default, every channel is kept as it is, as well as all notes and
+/// non-channel events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelRemap {
+    /// Target of every 1-based channel, `None` drops the channel.
+    targets: [Option<u8>; 16],
+    notes: RangeInclusive<u8>,
+    keep_system: bool,
+}
+impl Default for ChannelRemap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ChannelRemap {
+    pub fn new() -> Self {
+        let mut targets = [None; 16];
+        for (idx, target) in targets.iter_mut().enumerate() {
+            *target = Some(idx as u8 + 1);
+        }
+        Self {
+            targets,
+            notes: 0..=127,
+            keep_system: true,
+        }
+    }
+    /// Move all events of channel `from` to channel `to`.
+    pub fn map_channel(mut self, from: u8, to: u8) -> Self {
+        assert!((1..=16).contains(&to), "channel is 1-based");
+        self.targets[Self::index(from)] = Some(to);
+        self
+    }
+    /// Drop all events of the channel.
+    pub fn drop_channel(mut self, channel: u8) -> Self {
+        self.targets[Self::index(channel)] = None;
+        self
+    }
+    /// Drop all channels, except given.
+    pub fn only_channels(
+        mut self,
+        channels: impl IntoIterator<Item = u8>,
+    ) -> Self {
+        let keep: Vec<u8> = channels.into_iter().collect();
+        for channel in 1..=16 {
+            if !keep.contains(&channel) {
+                self.targets[Self::index(channel)] = None;
+            }
+        }
+        self
+    }
+    /// Drop notes (and poly aftertouch) outside of the range.
+    pub fn note_range(mut self, notes: RangeInclusive<u8>) -> Self {
+        self.notes = notes;
+        self
+    }
+    /// Drop SysEx, meta and other non-channel events.
+    pub fn drop_system_events(mut self) -> Self {
+        self.keep_system = false;
+        self
+    }
+    /// Target channel for the 1-based source channel.
+    pub fn target(&self, channel: u8) -> Option<u8> {
+        self.targets[Self::index(channel)]
+    }
+    pub fn contains_note(&self, note: u8) -> bool {
+        self.notes.contains(&note)
+    }
+
+    fn index(channel: u8) -> usize {
+        assert!((1..=16).contains(&channel), "channel is 1-based");
+        channel as usize - 1
+    }
+
+    /// Remap event, that is not a note-on or note-off.
+    fn remap_event(
+        &self,
+        mut event: MidiEvent<RawMidiMessage>,
+    ) -> Option<MidiEvent<RawMidiMessage>> {
+        let channel = match event.message().channel() {
+            None => return self.keep_system.then_some(event),
+            Some(channel) => channel,
+        };
+        let target = self.target(channel)?;
+        let msg = event.message_mut();
+        if msg.buf[0] & 0xf0 == 0xa0 && !self.contains_note(msg.buf[1]) {
+            return None;
+        }
+        msg.set_channel(target);
+        Some(event)
+    }
+
+    fn remap_note(&self, mut note: MidiNoteEvent) -> Option<MidiNoteEvent> {
+        if !self.contains_note(note.note) {
+            return None;
+        }
+        note.channel = self.target(note.channel)?;
+        Some(note)
+    }
+}
+
+/// Notes and the rest of events of one or more streams.
+#[derive(Debug, Default)]
+struct RemappedParts {
+    notes: Vec<MidiNoteEvent>,
+    others: Vec<MidiEvent<RawMidiMessage>>,
+}
+impl RemappedParts {
+    fn extend(
+        &mut self,
+        events: impl Iterator<Item = MidiEvent<RawMidiMessage>>,
+        remap: &ChannelRemap,
+    ) {
+        let mut note_events = Vec::new();
+        // Beizer data is dropped together with its event.
+        let mut previous_kept = false;
+        for event in events {
+            if event.message().is_note() {
+                note_events.push(event);
+                previous_kept = false;
+                continue;
+            }
+            if event.message().is_beizer() {
+                if previous_kept {
+                    self.others.push(event);
+                }
+                continue;
+            }
+            match remap.remap_event(event) {
+                Some(event) => {
+                    self.others.push(event);
+                    previous_kept = true;
+                }
+                None => previous_kept = false,
+            }
+        }
+        self.notes.extend(
+            FilterNotes::new(note_events.into_iter())
+                .skip_orphan_note_offs()
+                .filter_map(|note| remap.remap_note(note)),
+        );
+    }
+
+    fn into_events(mut self) -> Vec<MidiEvent<RawMidiMessage>> {
+        trim_note_overlaps(&mut self.notes);
+        let mut events: Vec<(u8, MidiEvent<RawMidiMessage>)> =
+            flatten_midi_notes(self.notes.into_iter())
+                .map(|e| match e.message().buf[0] & 0xf0 {
+                    0x90 => (2, e),
+                    _ => (0, e),
+                })
+                .collect();
+        events.extend(self.others.into_iter().map(|e| (1, e)));
+        // stable sort keeps Beizer data right after its event.
+        events.sort_by_key(|(priority, e)| (e.ppq_position(), *priority));
+        events.into_iter().map(|(_, e)| e).collect()
+    }
+}
+
+/// Remap channels and filter notes of the stream.
+///
+/// Notes without note-on or note-off are dropped.
+pub fn remap_channels(
+    events: impl Iterator<Item = MidiEvent<RawMidiMessage>>,
+    remap: &ChannelRemap,
+) -> Vec<MidiEvent<RawMidiMessage>> {
+    let mut parts = RemappedParts::default();
+    parts.extend(events, remap);
+    parts.into_events()
+}
+
+/// Merge several streams into one, remapping each by its own
+/// [ChannelRemap].
+pub fn merge_midi_events(
+    streams: impl IntoIterator<
+        Item = (Vec<MidiEvent<RawMidiMessage>>, ChannelRemap),
+    >,
+) -> Vec<MidiEvent<RawMidiMessage>> {
+    let mut parts = RemappedParts::default();
+    for (events, remap) in streams {
+        parts.extend(events.into_iter(), &remap);
+    }
+    parts.into_events()
+}
+
+/// Events of a stream, split by channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelSplit {
+    /// Events of every 1-based channel, in the original order.
+    pub by_channel: BTreeMap<u8, Vec<MidiEvent<RawMidiMessage>>>,
+    /// SysEx, meta and other non-channel events.
+    pub system: Vec<MidiEvent<RawMidiMessage>>,
+}
+impl ChannelSplit {
+    /// Channels, that have at least one event, in ascending order.
+    pub fn channels(&self) -> Vec<u8> {
+        self.by_channel.keys().copied().collect()
+    }
+    /// Events of the channel, optionally with non-channel events.
+    pub fn channel_events(
+        &self,
+        channel: u8,
+        with_system: bool,
+    ) -> Vec<MidiEvent<RawMidiMessage>> {
+        let mut events =
+            self.by_channel.get(&channel).cloned().unwrap_or_default();
+        if with_system {
+            events.extend(self.system.iter().cloned());
+            // stable sort keeps Beizer data right after its event.
+            events.sort_by_key(|e| e.ppq_position());
+        }
+        events
+    }
+}
+
+/// Split stream by channels. Beizer data stays with its event.
+pub fn split_by_channel(
+    events: impl Iterator<Item = MidiEvent<RawMidiMessage>>,
+) -> ChannelSplit {
+    let mut split = ChannelSplit::default();
+    let mut previous: Option<u8> = None;
+    for event in events {
+        if event.message().is_beizer() {
+            match previous {
+                Some(channel) => split.by_channel.entry(channel),
+                None => {
+                    split.system.push(event);
+                    continue;
+                }
+            }
+            .or_default()
+            .push(event);
+            continue;
+        }
+        previous = event.message().channel();
+        match previous {
+            Some(channel) => {
+                split.by_channel.entry(channel).or_default().push(event)
+            }
+            None => split.system.push(event),
+        }
+    }
+    split
+}
+
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// Split take MIDI by channel.
+    pub fn split_midi_by_channel(&self) -> ReaperResult<ChannelSplit> {
+        Ok(split_by_channel(self.iter_midi(None)?))
+    }
+}
+impl<'a> Take<'a, Mutable> {
+    /// Remap channels and filter notes of take MIDI.
+    pub fn remap_midi_channels(
+        &mut self,
+        remap: &ChannelRemap,
+    ) -> ReaperResult<()> {
+        let events = remap_channels(self.iter_midi(None)?, remap);
+        self.set_midi(MidiEventConsumer::new(events.into_iter()).collect())
+    }
+}
+
+impl<'a> Track<'a, Mutable> {
+    /// Create one new MIDI item for every channel of the split.
+    ///
+    /// If `with_system` is true, non-channel events are written to every
+    /// item. Returns channels in order of created items.
+    pub fn add_midi_items_per_channel(
+        &mut self,
+        start: impl Into<Position>,
+        length: impl GetLength,
+        split: &ChannelSplit,
+        with_system: bool,
+    ) -> ReaperResult<Vec<u8>> {
+        let start = start.into();
+        let length = length.get_length(start);
+        let channels = split.channels();
+        for channel in channels.iter() {
+            let events = split.channel_events(*channel, with_system);
+            let mut item = self.add_midi_item(start, length);
+            let mut take = item.active_take_mut();
+            take.set_name(format!("channel {}", channel));
+            take.set_midi(
+                MidiEventConsumer::new(events.into_iter()).collect(),
+            )?;
+        }
+        Ok(channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CcShapeKind, MidiBufWriter, MidiEventBuilder, MidiMessage};
+
+    fn raw(pos: u32, msg: &[u8]) -> MidiEvent<RawMidiMessage> {
+        MidiEvent::new(
+            pos,
+            false,
+            false,
+            CcShapeKind::Square,
+            RawMidiMessage::from_raw(msg.to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_raw_channel() {
+        let mut msg = RawMidiMessage::from_raw(vec![0xb3, 1, 2]).unwrap();
+        assert_eq!(msg.channel(), Some(4));
+        assert_eq!(msg.set_channel(16), Some(()));
+        assert_eq!(msg.get_raw(), vec![0xbf, 1, 2]);
+        let mut sysex = RawMidiMessage::from_raw(vec![0xf0, 1, 0xf7]).unwrap();
+        assert_eq!(sysex.channel(), None);
+        assert_eq!(sysex.set_channel(2), None);
+        assert_eq!(sysex.get_raw(), vec![0xf0, 1, 0xf7]);
+    }
+
+    #[test]
+    fn test_remap_keeps_pairs_and_beizer() {
+        let mut writer = MidiBufWriter::new();
+        let shape = CcShapeKind::Square;
+        writer.push_raw(0, false, false, shape, &[0x90, 60, 100]);
+        writer.push_raw(0, false, false, shape, &[0x91, 72, 100]);
+        writer.push_raw(10, false, false, CcShapeKind::Beizer, &[0xb1, 1, 5]);
+        let mut beizer = super::BEIZER_HEADER.to_vec();
+        beizer.push(0);
+        beizer.extend(0.5_f32.to_le_bytes());
+        writer.push_raw(10, false, false, shape, &beizer);
+        writer.push_raw(10, false, false, shape, &[0xb2, 1, 5]);
+        writer.push_raw(10, false, false, shape, &beizer);
+        writer.push_raw(20, false, false, shape, &[0xff, 0x01, 0x41]);
+        writer.push_raw(480, false, false, shape, &[0x80, 60, 0]);
+        writer.push_raw(480, false, false, shape, &[0x81, 72, 0]);
+        let buf = writer.finish();
+
+        let remap = ChannelRemap::new()
+            .map_channel(2, 5)
+            .drop_channel(3)
+            .note_range(0..=64)
+            .drop_system_events();
+        let events =
+            remap_channels(MidiEventBuilder::new(buf.into_iter()), &remap);
+        let raw: Vec<Vec<u8>> =
+            events.iter().map(|e| e.message().get_raw()).collect();
+        assert_eq!(
+            raw,
+            vec![
+                vec![0x90, 60, 100],
+                vec![0xb4, 1, 5],
+                beizer.clone(),
+                vec![0x80, 60, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_beizer_after_note_is_dropped() {
+        let mut beizer = super::BEIZER_HEADER.to_vec();
+        beizer.push(0);
+        beizer.extend(0.5_f32.to_le_bytes());
+        let events = vec![
+            raw(0, &[0xb0, 1, 5]),
+            raw(0, &[0x90, 60, 100]),
+            raw(0, &beizer),
+            raw(480, &[0x80, 60, 0]),
+        ];
+        let events = remap_channels(events.into_iter(), &ChannelRemap::new());
+        assert!(events.iter().all(|e| !e.message().is_beizer()));
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_trims_overlapping_notes() {
+        let a = vec![raw(0, &[0x90, 60, 100]), raw(480, &[0x80, 60, 0])];
+        let b = vec![raw(240, &[0x92, 60, 90]), raw(720, &[0x82, 60, 0])];
+        let merged = merge_midi_events(vec![
+            (a, ChannelRemap::new()),
+            (b, ChannelRemap::new().map_channel(3, 1)),
+        ]);
+        let notes: Vec<MidiNoteEvent> =
+            FilterNotes::new(merged.into_iter()).collect();
+        assert_eq!(notes.len(), 2);
+        assert_eq!((notes[0].start_in_ppq, notes[0].end_in_ppq), (0, 240));
+        assert_eq!((notes[1].start_in_ppq, notes[1].end_in_ppq), (240, 720));
+        assert!(notes.iter().all(|n| n.channel == 1));
+    }
+
+    #[test]
+    fn test_split_channels() {
+        let events = vec![
+            raw(0, &[0x90, 60, 100]),
+            raw(0, &[0xf0, 1, 0xf7]),
+            raw(0, &[0xc9, 5]),
+            raw(480, &[0x80, 60, 0]),
+        ];
+        let split = split_by_channel(events.into_iter());
+        assert_eq!(split.channels(), vec![1, 10]);
+        assert_eq!(split.channel_events(1, false).len(), 2);
+        assert_eq!(split.system.len(), 1);
+        let with_system = split.channel_events(10, true);
+        assert_eq!(with_system.len(), 2);
+        assert_eq!(with_system[0].message().get_raw(), vec![0xc9, 5]);
+    }
+}
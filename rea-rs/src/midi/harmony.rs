@@ -0,0 +1,603 @@
+//! Chord and scale analysis of [MidiNoteEvent] collections.
+//!
+//! Notes, that start together, are grouped into [ChordEvent], and each group
+//! is identified as [Chord] with root, quality and inversion. The key of the
+//! whole set is estimated by Krumhansl-Schmuckler algorithm, using
+//! pitch-class histogram, weighted by note length.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let note = |start, pitch| {
+//!     MidiNoteEvent::new(start, start + 480, false, false, 1, pitch, 100, 0)
+//! };
+//! let notes = vec![
+//!     // C major
+//!     note(0, 60),
+//!     note(0, 64),
+//!     note(0, 67),
+//!     // F major, second inversion
+//!     note(480, 60),
+//!     note(480, 65),
+//!     note(480, 69),
+//!     // G7
+//!     note(960, 55),
+//!     note(960, 59),
+//!     note(960, 62),
+//!     note(960, 65),
+//! ];
+//! let report = HarmonyReport::new(&notes, 10);
+//! let names: Vec<String> = report
+//!     .chords
+//!     .iter()
+//!     .map(|c| c.chord.as_ref().unwrap().to_string())
+//!     .collect();
+//! assert_eq!(names, vec!["C", "F/C", "G7"]);
+//! assert_eq!(report.key.unwrap().scale.to_string(), "C major");
+//! assert!(report.out_of_scale.is_empty());
+//! ```
+
+use std::fmt::Display;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{MidiNoteEvent, ProbablyMutable, ReaperResult, Take};
+
+/// One of 12 notes of octave, `0` is C.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+pub struct PitchClass(u8);
+impl PitchClass {
+    pub const NAMES: [&'static str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+
+    /// Value is wrapped to `0..12`.
+    pub fn new(value: u8) -> Self {
+        Self(value % 12)
+    }
+    /// Pitch class of MIDI note.
+    pub fn from_note(note: u8) -> Self {
+        Self::new(note)
+    }
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+    /// Name with sharps: `C`, `C#`, `D`...
+    pub fn name(&self) -> &'static str {
+        Self::NAMES[self.0 as usize]
+    }
+    /// Interval in semitones up to the other pitch class.
+    pub fn interval_to(&self, other: PitchClass) -> u8 {
+        (other.0 + 12 - self.0) % 12
+    }
+    /// Transposed up by semitones.
+    pub fn transposed(&self, semitones: u8) -> Self {
+        Self::new(self.0 + semitones % 12)
+    }
+}
+impl Display for PitchClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Chord type, identified by intervals from the root.
+///
+/// Variants are listed in the order of preference for ambiguous sets of
+/// notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus4,
+    Sus2,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    HalfDiminished7,
+    Diminished7,
+    Augmented7,
+    Major6,
+    Minor6,
+    /// Root and fifth.
+    Power,
+}
+impl ChordQuality {
+    pub const ALL: [ChordQuality; 16] = [
+        Self::Major,
+        Self::Minor,
+        Self::Diminished,
+        Self::Augmented,
+        Self::Sus4,
+        Self::Sus2,
+        Self::Dominant7,
+        Self::Major7,
+        Self::Minor7,
+        Self::MinorMajor7,
+        Self::HalfDiminished7,
+        Self::Diminished7,
+        Self::Augmented7,
+        Self::Major6,
+        Self::Minor6,
+        Self::Power,
+    ];
+
+    /// Intervals of chord tones from the root, in semitones.
+    ///
+    /// Index of the interval is the inversion, in which this tone is in
+    /// the bass.
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Sus4 => &[0, 5, 7],
+            Self::Sus2 => &[0, 2, 7],
+            Self::Dominant7 => &[0, 4, 7, 10],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+            Self::MinorMajor7 => &[0, 3, 7, 11],
+            Self::HalfDiminished7 => &[0, 3, 6, 10],
+            Self::Diminished7 => &[0, 3, 6, 9],
+            Self::Augmented7 => &[0, 4, 8, 10],
+            Self::Major6 => &[0, 4, 7, 9],
+            Self::Minor6 => &[0, 3, 7, 9],
+            Self::Power => &[0, 7],
+        }
+    }
+
+    /// Suffix of chord symbol, e.g. `m7` for [ChordQuality::Minor7].
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Major => "",
+            Self::Minor => "m",
+            Self::Diminished => "dim",
+            Self::Augmented => "aug",
+            Self::Sus4 => "sus4",
+            Self::Sus2 => "sus2",
+            Self::Dominant7 => "7",
+            Self::Major7 => "maj7",
+            Self::Minor7 => "m7",
+            Self::MinorMajor7 => "mMaj7",
+            Self::HalfDiminished7 => "m7b5",
+            Self::Diminished7 => "dim7",
+            Self::Augmented7 => "aug7",
+            Self::Major6 => "6",
+            Self::Minor6 => "m6",
+            Self::Power => "5",
+        }
+    }
+}
+
+/// Identified chord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Chord {
+    pub root: PitchClass,
+    pub quality: ChordQuality,
+    /// The lowest sounding note.
+    pub bass: PitchClass,
+    /// 0 is root position, 1 is the first inversion etc.
+    pub inversion: u8,
+}
+impl Chord {
+    /// Identify chord by MIDI note numbers.
+    ///
+    /// Doubled notes are ignored. Returns None if the set of pitch classes
+    /// does not match any [ChordQuality] exactly. For ambiguous sets
+    /// (e.g. C6 and Am7) the chord with the root in the bass is preferred.
+    pub fn identify(notes: &[u8]) -> Option<Self> {
+        let bass = PitchClass::from_note(*notes.iter().min()?);
+        let mut classes: Vec<PitchClass> =
+            notes.iter().map(|n| PitchClass::from_note(*n)).collect();
+        classes.sort();
+        classes.dedup();
+        let mut roots = vec![bass];
+        roots.extend(classes.iter().filter(|pc| **pc != bass));
+        for root in roots {
+            let mut intervals: Vec<u8> =
+                classes.iter().map(|pc| root.interval_to(*pc)).collect();
+            intervals.sort();
+            let quality = match ChordQuality::ALL
+                .iter()
+                .find(|q| q.intervals() == intervals.as_slice())
+            {
+                Some(quality) => *quality,
+                None => continue,
+            };
+            let inversion = quality
+                .intervals()
+                .iter()
+                .position(|i| *i == root.interval_to(bass))
+                .expect("bass is a chord tone")
+                as u8;
+            return Some(Self {
+                root,
+                quality,
+                bass,
+                inversion,
+            });
+        }
+        None
+    }
+
+    /// Chord symbol, e.g. `Am7/G`.
+    pub fn name(&self) -> String {
+        let mut name = format!("{}{}", self.root, self.quality.suffix());
+        if self.bass != self.root {
+            name += &format!("/{}", self.bass);
+        }
+        name
+    }
+}
+impl Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Group of notes, that start together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChordEvent {
+    pub start_in_ppq: u32,
+    /// The latest end of the group notes.
+    pub end_in_ppq: u32,
+    /// Indices of notes in the analyzed slice.
+    pub notes: Vec<usize>,
+    /// Sorted unique MIDI note numbers.
+    pub pitches: Vec<u8>,
+    /// None if notes do not form known chord.
+    pub chord: Option<Chord>,
+}
+
+/// Group notes, which starts are within `tolerance` ticks from the first
+/// note of the group.
+///
+/// Groups are returned in the order of start. Single notes form groups
+/// without chord.
+pub fn group_chords(
+    notes: &[MidiNoteEvent],
+    tolerance: u32,
+) -> Vec<ChordEvent> {
+    let mut order: Vec<usize> = (0..notes.len()).collect();
+    order.sort_by_key(|i| (notes[*i].start_in_ppq, notes[*i].note));
+    let mut groups: Vec<ChordEvent> = Vec::new();
+    for idx in order {
+        let note = &notes[idx];
+        match groups.last_mut() {
+            Some(group)
+                if note.start_in_ppq - group.start_in_ppq <= tolerance =>
+            {
+                group.end_in_ppq = group.end_in_ppq.max(note.end_in_ppq);
+                group.notes.push(idx);
+            }
+            _ => groups.push(ChordEvent {
+                start_in_ppq: note.start_in_ppq,
+                end_in_ppq: note.end_in_ppq,
+                notes: vec![idx],
+                pitches: Vec::new(),
+                chord: None,
+            }),
+        }
+    }
+    for group in groups.iter_mut() {
+        group.pitches = group.notes.iter().map(|i| notes[*i].note).collect();
+        group.pitches.sort();
+        group.pitches.dedup();
+        group.chord = Chord::identify(&group.pitches);
+    }
+    groups
+}
+
+/// Scale (mode) type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScaleKind {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+impl ScaleKind {
+    /// Intervals of scale degrees from the root, in semitones.
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Self::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            Self::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Self::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Self::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Self::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Self::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Self::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Self::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Self::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Major => "major",
+            Self::NaturalMinor => "minor",
+            Self::HarmonicMinor => "harmonic minor",
+            Self::MelodicMinor => "melodic minor",
+            Self::Dorian => "dorian",
+            Self::Phrygian => "phrygian",
+            Self::Lydian => "lydian",
+            Self::Mixolydian => "mixolydian",
+            Self::Locrian => "locrian",
+            Self::MajorPentatonic => "major pentatonic",
+            Self::MinorPentatonic => "minor pentatonic",
+            Self::Blues => "blues",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Scale {
+    pub root: PitchClass,
+    pub kind: ScaleKind,
+}
+impl Scale {
+    pub fn new(root: PitchClass, kind: ScaleKind) -> Self {
+        Self { root, kind }
+    }
+    /// Pitch classes of the scale, starting from the root.
+    pub fn pitch_classes(&self) -> Vec<PitchClass> {
+        self.kind
+            .intervals()
+            .iter()
+            .map(|i| self.root.transposed(*i))
+            .collect()
+    }
+    /// Whether MIDI note belongs to the scale.
+    pub fn contains(&self, note: u8) -> bool {
+        let interval = self.root.interval_to(PitchClass::from_note(note));
+        self.kind.intervals().contains(&interval)
+    }
+}
+impl Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.root, self.kind.name())
+    }
+}
+
+/// Key candidate with its correlation to the notes (`-1.0..=1.0`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeyEstimate {
+    /// Either [ScaleKind::Major] or [ScaleKind::NaturalMinor].
+    pub scale: Scale,
+    pub correlation: f64,
+}
+
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Total length of notes per pitch class. Zero-length notes count as one
+/// tick.
+pub fn pitch_class_histogram(notes: &[MidiNoteEvent]) -> [f64; 12] {
+    let mut histogram = [0.0; 12];
+    for note in notes {
+        let length = note.end_in_ppq.saturating_sub(note.start_in_ppq).max(1);
+        histogram[PitchClass::from_note(note.note).get() as usize] +=
+            length as f64;
+    }
+    histogram
+}
+
+fn correlation(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+    let mean_a = a.iter().sum::<f64>() / 12.0;
+    let mean_b = b.iter().sum::<f64>() / 12.0;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b.iter()) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+/// All 24 major and minor keys, sorted from the most probable.
+///
+/// Empty if notes are empty or all pitch classes are equally used.
+pub fn rank_keys(notes: &[MidiNoteEvent]) -> Vec<KeyEstimate> {
+    let histogram = pitch_class_histogram(notes);
+    let first = histogram[0];
+    if histogram.iter().all(|v| *v == first) {
+        return Vec::new();
+    }
+    let mut keys = Vec::with_capacity(24);
+    for (kind, profile) in [
+        (ScaleKind::Major, MAJOR_PROFILE),
+        (ScaleKind::NaturalMinor, MINOR_PROFILE),
+    ] {
+        for root in 0..12 {
+            let mut rotated = [0.0; 12];
+            for (interval, weight) in profile.iter().enumerate() {
+                rotated[(root + interval) % 12] = *weight;
+            }
+            keys.push(KeyEstimate {
+                scale: Scale::new(PitchClass::new(root as u8), kind),
+                correlation: correlation(&histogram, &rotated),
+            });
+        }
+    }
+    keys.sort_by(|a, b| b.correlation.total_cmp(&a.correlation));
+    keys
+}
+
+/// The most probable major or minor key.
+pub fn estimate_key(notes: &[MidiNoteEvent]) -> Option<KeyEstimate> {
+    rank_keys(notes).into_iter().next()
+}
+
+/// Indices of notes, that do not belong to scale.
+pub fn out_of_scale_notes(
+    notes: &[MidiNoteEvent],
+    scale: &Scale,
+) -> Vec<usize> {
+    notes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !scale.contains(n.note))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Chords, key and out-of-key notes of the notes set.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HarmonyReport {
+    pub chords: Vec<ChordEvent>,
+    pub key: Option<KeyEstimate>,
+    /// Indices of notes, that do not belong to the estimated key.
+    pub out_of_scale: Vec<usize>,
+}
+impl HarmonyReport {
+    /// `chord_tolerance` is passed to [group_chords].
+    pub fn new(notes: &[MidiNoteEvent], chord_tolerance: u32) -> Self {
+        let key = estimate_key(notes);
+        Self {
+            chords: group_chords(notes, chord_tolerance),
+            out_of_scale: key
+                .map(|k| out_of_scale_notes(notes, &k.scale))
+                .unwrap_or_default(),
+            key,
+        }
+    }
+}
+
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// Analyze chords and key of all take notes.
+    ///
+    /// Indices of report refer to the returned notes.
+    ///
+    /// see [HarmonyReport::new]
+    pub fn analyze_harmony(
+        &self,
+        chord_tolerance: u32,
+    ) -> ReaperResult<(Vec<MidiNoteEvent>, HarmonyReport)> {
+        let notes: Vec<MidiNoteEvent> =
+            self.iter_midi(None)?.filter_notes().collect();
+        let report = HarmonyReport::new(&notes, chord_tolerance);
+        Ok((notes, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(start: u32, pitch: u8) -> MidiNoteEvent {
+        MidiNoteEvent::new(start, start + 100, false, false, 1, pitch, 90, 0)
+    }
+
+    fn name(notes: &[u8]) -> Option<String> {
+        Chord::identify(notes).map(|c| c.name())
+    }
+
+    #[test]
+    fn test_identify() {
+        assert_eq!(name(&[60, 64, 67, 72]), Some("C".to_string()));
+        assert_eq!(name(&[64, 67, 72]), Some("C/E".to_string()));
+        assert_eq!(name(&[57, 60, 64, 67]), Some("Am7".to_string()));
+        assert_eq!(name(&[60, 64, 67, 69]), Some("C6".to_string()));
+        assert_eq!(name(&[55, 62, 60]), Some("Gsus4".to_string()));
+        assert_eq!(name(&[59, 62, 65, 68]), Some("Bdim7".to_string()));
+        assert_eq!(name(&[61, 68]), Some("C#5".to_string()));
+        assert_eq!(name(&[60, 61, 62]), None);
+        assert_eq!(name(&[60]), None);
+        assert_eq!(name(&[]), None);
+        let chord = Chord::identify(&[53, 59, 62, 67]).unwrap();
+        assert_eq!(chord.quality, ChordQuality::Dominant7);
+        assert_eq!(chord.root, PitchClass::new(7));
+        assert_eq!(chord.inversion, 3);
+    }
+
+    #[test]
+    fn test_group_chords() {
+        let notes = vec![
+            note(5, 64),
+            note(0, 60),
+            note(3, 67),
+            note(100, 62),
+            note(200, 62),
+            note(200, 65),
+            note(210, 69),
+        ];
+        let groups = group_chords(&notes, 5);
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups[0].notes, vec![1, 2, 0]);
+        assert_eq!(groups[0].end_in_ppq, 105);
+        assert_eq!(groups[0].chord.unwrap().name(), "C");
+        assert_eq!(groups[1].chord, None);
+        assert_eq!(groups[2].pitches, vec![62, 65]);
+        assert_eq!(groups[3].notes, vec![6]);
+    }
+
+    #[test]
+    fn test_key() {
+        // A natural minor scale with long tonic and dominant.
+        let mut notes: Vec<MidiNoteEvent> = [57, 59, 60, 62, 64, 65, 67]
+            .iter()
+            .enumerate()
+            .map(|(i, p)| note(i as u32 * 100, *p))
+            .collect();
+        notes.push(MidiNoteEvent::new(700, 1500, false, false, 1, 57, 90, 0));
+        notes.push(MidiNoteEvent::new(700, 1100, false, false, 1, 64, 90, 0));
+        notes.push(note(1500, 68));
+        let key = estimate_key(&notes).unwrap();
+        assert_eq!(key.scale.to_string(), "A minor");
+        assert_eq!(out_of_scale_notes(&notes, &key.scale), vec![9]);
+        assert_eq!(rank_keys(&notes).len(), 24);
+        assert_eq!(estimate_key(&[]), None);
+    }
+
+    #[test]
+    fn test_scale() {
+        let scale = Scale::new(PitchClass::new(2), ScaleKind::Major);
+        assert_eq!(
+            scale
+                .pitch_classes()
+                .iter()
+                .map(|pc| pc.name())
+                .collect::<Vec<_>>(),
+            vec!["D", "E", "F#", "G", "A", "B", "C#"]
+        );
+        assert!(scale.contains(66));
+        assert!(!scale.contains(65));
+        assert_eq!(
+            Scale::new(PitchClass::new(9), ScaleKind::Blues).to_string(),
+            "A blues"
+        );
+    }
+}
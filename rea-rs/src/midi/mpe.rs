@@ -0,0 +1,703 @@
+//! MPE (MIDI Polyphonic Expression) model.
+//!
+//! MPE zone consists of the master channel (1 for the lower zone, 16 for the
+//! upper one) and member channels, each of them carries one sounding note
+//! with its own pitch bend, timbre (CC 74) and channel pressure.
+//!
+//! [MpeTakeEvents] groups these per-note expressions with their
+//! [MidiNoteEvent], and [allocate_mpe_channels] distributes notes over member
+//! channels before writing them back by [MpeTakeEvents::to_raw_events].
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let zone = MpeZone::lower(3);
+//! let mut notes = vec![
+//!     MpeNote::new(MidiNoteEvent::new(0, 960, false, false, 1, 60, 90, 0)),
+//!     MpeNote::new(MidiNoteEvent::new(0, 960, false, false, 1, 64, 90, 0)),
+//! ];
+//! notes[1].pitch_bend.push(MidiEvent::new(
+//!     480,
+//!     false,
+//!     false,
+//!     CcShapeKind::Square,
+//!     PitchBendMessage::new(1, 8192 + 4096),
+//! ));
+//! allocate_mpe_channels(&zone, &mut notes);
+//! assert_eq!(notes[0].note.channel, 2);
+//! assert_eq!(notes[1].note.channel, 3);
+//! assert_eq!(notes[1].pitch_bend[0].message().channel(), 3);
+//! assert_eq!(notes[1].pitch_offset_at(&zone, 600), 24.0);
+//!
+//! let events = MpeTakeEvents::new(zone, notes);
+//! let raw = events.to_raw_events();
+//! let back =
+//!     MpeTakeEvents::from_builder(zone, MidiEventBuilder::new(
+//!         MidiEventConsumer::new(raw.into_iter())
+//!             .collect::<Vec<u8>>()
+//!             .into_iter(),
+//!     ));
+//! assert_eq!(back.notes.len(), 2);
+//! assert_eq!(back.notes[1].pitch_bend.len(), 1);
+//! ```
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    cc_lane_value_at, flatten_events_with_beizer_curve, flatten_midi_notes,
+    flatten_parameter_numbers, CCMessage, ChannelPressureMessage, MidiEvent,
+    MidiEventBuilder, MidiNoteEvent, ParameterNumberEvent,
+    ParameterNumberKind, PitchBendMessage, RawMidiMessage,
+};
+
+/// CC, used by MPE as the third dimension of per-note control.
+pub const MPE_TIMBRE_CC: u8 = 74;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MpeZoneKind {
+    /// Master channel is 1, members start from channel 2.
+    Lower,
+    /// Master channel is 16, members start from channel 15 downwards.
+    Upper,
+}
+
+/// MPE zone configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MpeZone {
+    pub kind: MpeZoneKind,
+    /// Number of member channels: 1..=15.
+    pub member_channels: u8,
+    /// Pitch bend range of member channels in semitones. Default is 48.
+    pub pitch_bend_range: u8,
+    /// Pitch bend range of the master channel in semitones. Default is 2.
+    pub master_pitch_bend_range: u8,
+}
+impl MpeZone {
+    pub fn new(kind: MpeZoneKind, member_channels: u8) -> Self {
+        Self {
+            kind,
+            member_channels: member_channels.clamp(1, 15),
+            pitch_bend_range: 48,
+            master_pitch_bend_range: 2,
+        }
+    }
+    pub fn lower(member_channels: u8) -> Self {
+        Self::new(MpeZoneKind::Lower, member_channels)
+    }
+    pub fn upper(member_channels: u8) -> Self {
+        Self::new(MpeZoneKind::Upper, member_channels)
+    }
+    pub fn with_pitch_bend_range(mut self, semitones: u8) -> Self {
+        self.pitch_bend_range = semitones;
+        self
+    }
+    pub fn with_master_pitch_bend_range(mut self, semitones: u8) -> Self {
+        self.master_pitch_bend_range = semitones;
+        self
+    }
+
+    /// 1-based master channel.
+    pub fn master_channel(&self) -> u8 {
+        match self.kind {
+            MpeZoneKind::Lower => 1,
+            MpeZoneKind::Upper => 16,
+        }
+    }
+    /// 1-based member channels.
+    pub fn member_channels(&self) -> RangeInclusive<u8> {
+        match self.kind {
+            MpeZoneKind::Lower => 2..=1 + self.member_channels,
+            MpeZoneKind::Upper => 16 - self.member_channels..=15,
+        }
+    }
+    pub fn is_member_channel(&self, channel: u8) -> bool {
+        self.member_channels().contains(&channel)
+    }
+
+    /// MPE Configuration Message: RPN 6 on the master channel.
+    pub fn configuration_message(
+        &self,
+        position_in_ppq: u32,
+    ) -> ParameterNumberEvent {
+        ParameterNumberEvent::new(
+            position_in_ppq,
+            false,
+            false,
+            self.master_channel(),
+            ParameterNumberKind::MPE_CONFIGURATION,
+            (self.member_channels as u16) << 7,
+            false,
+        )
+    }
+    /// Configuration message, followed by pitch bend sensitivity (RPN 0)
+    /// for master and every member channel.
+    pub fn configuration_events(
+        &self,
+        position_in_ppq: u32,
+    ) -> Vec<ParameterNumberEvent> {
+        let sensitivity = |channel: u8, semitones: u8| {
+            ParameterNumberEvent::new(
+                position_in_ppq,
+                false,
+                false,
+                channel,
+                ParameterNumberKind::PITCH_BEND_SENSITIVITY,
+                (semitones as u16) << 7,
+                true,
+            )
+        };
+        let mut events = vec![self.configuration_message(position_in_ppq)];
+        events.push(sensitivity(
+            self.master_channel(),
+            self.master_pitch_bend_range,
+        ));
+        for channel in self.member_channels() {
+            events.push(sensitivity(channel, self.pitch_bend_range));
+        }
+        events
+    }
+    /// [MpeZone::configuration_events] as CC events.
+    pub fn configuration_cc_events(
+        &self,
+        position_in_ppq: u32,
+    ) -> Vec<MidiEvent<CCMessage>> {
+        flatten_parameter_numbers(
+            self.configuration_events(position_in_ppq).into_iter(),
+        )
+        .collect()
+    }
+}
+
+/// Zones, found in the parameter number events.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
+pub struct MpeConfiguration {
+    pub lower: Option<MpeZone>,
+    pub upper: Option<MpeZone>,
+}
+impl MpeConfiguration {
+    /// Read MPE Configuration Messages and pitch bend sensitivity.
+    ///
+    /// Configuration with zero member channels removes the zone. Zone,
+    /// that overlaps the other zone, shrinks it, as MPE specification
+    /// requires.
+    pub fn from_parameter_numbers(
+        events: impl Iterator<Item = ParameterNumberEvent>,
+    ) -> Self {
+        let mut config = Self::default();
+        for event in events {
+            match event.kind {
+                ParameterNumberKind::MPE_CONFIGURATION => {
+                    config.apply_configuration(event.channel, event.msb())
+                }
+                ParameterNumberKind::PITCH_BEND_SENSITIVITY => {
+                    config.apply_sensitivity(event.channel, event.msb())
+                }
+                _ => (),
+            }
+        }
+        config
+    }
+
+    fn apply_configuration(&mut self, channel: u8, members: u8) {
+        let zone = match (channel, members) {
+            (1 | 16, 0) => None,
+            (1, n) => Some(MpeZone::lower(n)),
+            (16, n) => Some(MpeZone::upper(n)),
+            _ => return,
+        };
+        match channel {
+            1 => {
+                self.lower = zone;
+                if let (Some(lower), Some(upper)) =
+                    (self.lower, &mut self.upper)
+                {
+                    upper.member_channels = upper
+                        .member_channels
+                        .min(14u8.saturating_sub(lower.member_channels));
+                }
+            }
+            _ => {
+                self.upper = zone;
+                if let (Some(upper), Some(lower)) =
+                    (self.upper, &mut self.lower)
+                {
+                    lower.member_channels = lower
+                        .member_channels
+                        .min(14u8.saturating_sub(upper.member_channels));
+                }
+            }
+        }
+        for zone in [&mut self.lower, &mut self.upper] {
+            if zone.map(|z| z.member_channels == 0).unwrap_or(false) {
+                *zone = None;
+            }
+        }
+    }
+
+    fn apply_sensitivity(&mut self, channel: u8, semitones: u8) {
+        for zone in [&mut self.lower, &mut self.upper].into_iter().flatten() {
+            if zone.master_channel() == channel {
+                zone.master_pitch_bend_range = semitones;
+            } else if zone.is_member_channel(channel) {
+                zone.pitch_bend_range = semitones;
+            }
+        }
+    }
+}
+
+/// Note with its per-note expressions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MpeNote {
+    pub note: MidiNoteEvent,
+    pub pitch_bend: Vec<MidiEvent<PitchBendMessage>>,
+    /// CC 74 events.
+    pub timbre: Vec<MidiEvent<CCMessage>>,
+    pub pressure: Vec<MidiEvent<ChannelPressureMessage>>,
+}
+impl MpeNote {
+    pub fn new(note: MidiNoteEvent) -> Self {
+        Self {
+            note,
+            pitch_bend: Vec::new(),
+            timbre: Vec::new(),
+            pressure: Vec::new(),
+        }
+    }
+
+    /// Pitch offset in semitones at position, including curve shapes.
+    pub fn pitch_offset_at(&self, zone: &MpeZone, position: u32) -> f64 {
+        match cc_lane_value_at(&self.pitch_bend, position) {
+            None => 0.0,
+            Some(value) => {
+                (value - 8192.0) / 8192.0 * zone.pitch_bend_range as f64
+            }
+        }
+    }
+
+    /// Move note and expressions to another channel.
+    pub fn set_channel(&mut self, channel: u8) {
+        self.note.channel = channel;
+        for event in self.pitch_bend.iter_mut() {
+            event.message_mut().set_channel(channel);
+        }
+        for event in self.timbre.iter_mut() {
+            event.message_mut().set_channel(channel);
+        }
+        for event in self.pressure.iter_mut() {
+            event.message_mut().set_channel(channel);
+        }
+    }
+
+    fn first_position(&self) -> u32 {
+        let expressions = self
+            .pitch_bend
+            .iter()
+            .map(|e| e.ppq_position())
+            .chain(self.timbre.iter().map(|e| e.ppq_position()))
+            .chain(self.pressure.iter().map(|e| e.ppq_position()));
+        expressions
+            .min()
+            .unwrap_or(self.note.start_in_ppq)
+            .min(self.note.start_in_ppq)
+    }
+}
+
+/// Take MIDI, grouped by MPE zone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MpeTakeEvents {
+    pub zone: MpeZone,
+    /// Notes, sorted by start.
+    pub notes: Vec<MpeNote>,
+    /// Pitch bend of the master channel and not grouped events.
+    pub global_pitch_bend: Vec<MidiEvent<PitchBendMessage>>,
+    /// All CC, except grouped CC 74.
+    pub global_cc: Vec<MidiEvent<CCMessage>>,
+    pub global_pressure: Vec<MidiEvent<ChannelPressureMessage>>,
+}
+impl MpeTakeEvents {
+    pub fn new(zone: MpeZone, mut notes: Vec<MpeNote>) -> Self {
+        notes.sort_by_key(|n| (n.note.start_in_ppq, n.note.note));
+        Self {
+            zone,
+            notes,
+            global_pitch_bend: Vec::new(),
+            global_cc: Vec::new(),
+            global_pressure: Vec::new(),
+        }
+    }
+
+    /// Group expressions of member channels with notes.
+    ///
+    /// Event belongs to the note on the same channel, that is sounding at
+    /// the event position. Event between notes belongs to the next note
+    /// (MPE sends initial expression state before note-on). Others are
+    /// global.
+    pub fn from_parts(
+        zone: MpeZone,
+        notes: impl Iterator<Item = MidiNoteEvent>,
+        pitch_bend: impl Iterator<Item = MidiEvent<PitchBendMessage>>,
+        cc: impl Iterator<Item = MidiEvent<CCMessage>>,
+        pressure: impl Iterator<Item = MidiEvent<ChannelPressureMessage>>,
+    ) -> Self {
+        let mut events = Self::new(zone, notes.map(MpeNote::new).collect());
+        let owners = NoteOwners::new(zone, &events.notes);
+        for event in pitch_bend {
+            match owners.find(event.message().channel(), event.ppq_position())
+            {
+                Some(idx) => events.notes[idx].pitch_bend.push(event),
+                None => events.global_pitch_bend.push(event),
+            }
+        }
+        for event in cc {
+            let owner = match event.message().cc_num() == MPE_TIMBRE_CC {
+                true => owners
+                    .find(event.message().channel(), event.ppq_position()),
+                false => None,
+            };
+            match owner {
+                Some(idx) => events.notes[idx].timbre.push(event),
+                None => events.global_cc.push(event),
+            }
+        }
+        for event in pressure {
+            match owners.find(event.message().channel(), event.ppq_position())
+            {
+                Some(idx) => events.notes[idx].pressure.push(event),
+                None => events.global_pressure.push(event),
+            }
+        }
+        events
+    }
+
+    /// Read all notes and expressions from take MIDI.
+    pub fn from_builder(zone: MpeZone, events: MidiEventBuilder) -> Self {
+        Self::from_parts(
+            zone,
+            events.clone().filter_notes(),
+            events.clone().filter_pitch_bend(),
+            events.clone().filter_cc(),
+            events.filter_channel_pressure(),
+        )
+    }
+
+    /// All events as raw events, sorted by position.
+    ///
+    /// At the same position note-offs go first, then expressions, then
+    /// note-ons, so the initial note state is sent before the note starts.
+    pub fn to_raw_events(&self) -> Vec<MidiEvent<RawMidiMessage>> {
+        let notes =
+            flatten_midi_notes(self.notes.iter().map(|n| n.note.clone()));
+        let mut events: Vec<(u8, MidiEvent<RawMidiMessage>)> = notes
+            .map(|e| match e.message().buf[0] & 0xf0 {
+                0x90 => (2, e),
+                _ => (0, e),
+            })
+            .collect();
+        let pitch_bend = self
+            .notes
+            .iter()
+            .flat_map(|n| n.pitch_bend.iter())
+            .chain(self.global_pitch_bend.iter())
+            .cloned();
+        let cc = self
+            .notes
+            .iter()
+            .flat_map(|n| n.timbre.iter())
+            .chain(self.global_cc.iter())
+            .cloned();
+        let pressure = self
+            .notes
+            .iter()
+            .flat_map(|n| n.pressure.iter())
+            .chain(self.global_pressure.iter())
+            .cloned();
+        events.extend(
+            flatten_events_with_beizer_curve(pitch_bend)
+                .chain(flatten_events_with_beizer_curve(cc))
+                .chain(flatten_events_with_beizer_curve(pressure))
+                .map(|e| (1, e)),
+        );
+        // stable sort keeps Beizer data right after its event.
+        events.sort_by_key(|(priority, e)| (e.ppq_position(), *priority));
+        events.into_iter().map(|(_, e)| e).collect()
+    }
+}
+
+/// Index of notes by channel, for grouping expressions.
+struct NoteOwners {
+    zone: MpeZone,
+    /// (note index, start, end), sorted by start.
+    by_channel: HashMap<u8, Vec<(usize, u32, u32)>>,
+}
+impl NoteOwners {
+    fn new(zone: MpeZone, notes: &[MpeNote]) -> Self {
+        let mut by_channel: HashMap<u8, Vec<(usize, u32, u32)>> =
+            HashMap::new();
+        for (idx, note) in notes.iter().enumerate() {
+            by_channel.entry(note.note.channel).or_default().push((
+                idx,
+                note.note.start_in_ppq,
+                note.note.end_in_ppq,
+            ));
+        }
+        Self { zone, by_channel }
+    }
+    fn find(&self, channel: u8, position: u32) -> Option<usize> {
+        if !self.zone.is_member_channel(channel) {
+            return None;
+        }
+        let notes = self.by_channel.get(&channel)?;
+        let split = notes.partition_point(|(_, start, _)| *start <= position);
+        if split > 0 {
+            let (current, _, end) = notes[split - 1];
+            if end > position {
+                return Some(current);
+            }
+        }
+        notes.get(split).map(|(idx, _, _)| *idx)
+    }
+}
+
+/// Distribute notes over member channels of the zone.
+///
+/// Notes are sorted by start. Every note gets the member channel, that was
+/// released the longest time ago. If all channels are busy, the oldest
+/// sounding note is cut at the start of the new one, and its channel is
+/// reused. Expressions are moved together with notes.
+pub fn allocate_mpe_channels(zone: &MpeZone, notes: &mut [MpeNote]) {
+    notes.sort_by_key(|n| (n.note.start_in_ppq, n.note.note));
+    // (channel, note index, release position)
+    let mut channels: Vec<(u8, Option<usize>, u32)> =
+        zone.member_channels().map(|ch| (ch, None, 0)).collect();
+    for idx in 0..notes.len() {
+        let start = notes[idx].note.start_in_ppq;
+        let first = notes[idx].first_position();
+        for (_, owner, release) in channels.iter_mut() {
+            if let Some(owner_idx) = owner {
+                if notes[*owner_idx].note.end_in_ppq <= first {
+                    *release = notes[*owner_idx].note.end_in_ppq;
+                    *owner = None;
+                }
+            }
+        }
+        let free = channels
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, owner, _))| owner.is_none())
+            .min_by_key(|(_, (_, _, release))| *release)
+            .map(|(slot, _)| slot);
+        let slot = match free {
+            Some(slot) => slot,
+            None => {
+                let (slot, stolen) = channels
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(slot, (_, owner, _))| {
+                        Some((slot, (*owner)?))
+                    })
+                    .min_by_key(|(_, owner)| notes[*owner].note.start_in_ppq)
+                    .expect("zone has at least one member channel");
+                let end = &mut notes[stolen].note.end_in_ppq;
+                *end = (*end).min(start);
+                slot
+            }
+        };
+        channels[slot].1 = Some(idx);
+        notes[idx].set_channel(channels[slot].0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CcShapeKind, MidiEventConsumer, MidiMessage};
+
+    fn note(start: u32, end: u32, pitch: u8) -> MpeNote {
+        MpeNote::new(MidiNoteEvent::new(
+            start, end, false, false, 1, pitch, 90, 0,
+        ))
+    }
+
+    fn pb(pos: u32, ch: u8, value: u16) -> MidiEvent<PitchBendMessage> {
+        MidiEvent::new(
+            pos,
+            false,
+            false,
+            CcShapeKind::Square,
+            PitchBendMessage::new(ch, value),
+        )
+    }
+
+    #[test]
+    fn test_zone() {
+        let lower = MpeZone::lower(5);
+        assert_eq!(lower.master_channel(), 1);
+        assert_eq!(lower.member_channels(), 2..=6);
+        let upper = MpeZone::upper(3);
+        assert_eq!(upper.master_channel(), 16);
+        assert_eq!(upper.member_channels(), 13..=15);
+        assert!(!upper.is_member_channel(16));
+
+        let cc: Vec<_> = lower
+            .configuration_cc_events(0)
+            .iter()
+            .map(|e| {
+                (
+                    e.message().channel(),
+                    e.message().cc_num(),
+                    e.message().cc_val(),
+                )
+            })
+            .collect();
+        assert_eq!(&cc[..3], &[(1, 101, 0), (1, 100, 6), (1, 6, 5)]);
+        assert_eq!(cc.len(), 3 + 4 * 6);
+        assert_eq!(cc[cc.len() - 2], (6, 6, 48));
+    }
+
+    #[test]
+    fn test_configuration() {
+        let mut events = MpeZone::lower(10)
+            .with_pitch_bend_range(24)
+            .configuration_events(0);
+        events.extend(MpeZone::upper(7).configuration_events(10));
+        let config = MpeConfiguration::from_parameter_numbers(
+            events.clone().into_iter(),
+        );
+        assert_eq!(config.upper, Some(MpeZone::upper(7)));
+        let lower = config.lower.unwrap();
+        assert_eq!(lower.member_channels, 7);
+        assert_eq!(lower.pitch_bend_range, 24);
+
+        events.push(ParameterNumberEvent::new(
+            20,
+            false,
+            false,
+            16,
+            ParameterNumberKind::MPE_CONFIGURATION,
+            0,
+            false,
+        ));
+        let config =
+            MpeConfiguration::from_parameter_numbers(events.into_iter());
+        assert_eq!(config.upper, None);
+
+        // 15 members take all channels: the other zone is dropped.
+        let config = MpeConfiguration::from_parameter_numbers(
+            [
+                MpeZone::upper(15).configuration_message(0),
+                MpeZone::lower(15).configuration_message(10),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(config.lower, Some(MpeZone::lower(15)));
+        assert_eq!(config.upper, None);
+    }
+
+    #[test]
+    fn test_allocate() {
+        let zone = MpeZone::lower(2);
+        let mut notes = vec![
+            note(0, 100, 60),
+            note(0, 50, 64),
+            note(60, 200, 67),
+            note(150, 300, 72),
+        ];
+        notes[3].pitch_bend.push(pb(140, 1, 0));
+        allocate_mpe_channels(&zone, &mut notes);
+        let result: Vec<_> = notes
+            .iter()
+            .map(|n| (n.note.note, n.note.channel, n.note.end_in_ppq))
+            .collect();
+        assert_eq!(
+            result,
+            vec![(60, 2, 100), (64, 3, 50), (67, 3, 200), (72, 2, 300)]
+        );
+        assert_eq!(notes[3].pitch_bend[0].message().channel(), 2);
+
+        let mut notes =
+            vec![note(0, 100, 60), note(10, 100, 62), note(20, 100, 64)];
+        allocate_mpe_channels(&zone, &mut notes);
+        assert_eq!(notes[0].note.end_in_ppq, 20);
+        assert_eq!(notes[2].note.channel, notes[0].note.channel);
+    }
+
+    #[test]
+    fn test_grouping() {
+        let zone = MpeZone::lower(15);
+        let notes = vec![
+            MidiNoteEvent::new(10, 100, false, false, 2, 60, 90, 0),
+            MidiNoteEvent::new(200, 300, false, false, 2, 62, 90, 0),
+            MidiNoteEvent::new(10, 100, false, false, 3, 64, 90, 0),
+        ];
+        let pitch_bend = vec![
+            pb(0, 1, 100),
+            pb(10, 2, 8192),
+            pb(50, 2, 9000),
+            pb(150, 2, 8192),
+            pb(400, 2, 8192),
+            pb(50, 3, 0),
+        ];
+        let cc = vec![
+            MidiEvent::new(
+                20,
+                false,
+                false,
+                CcShapeKind::Square,
+                CCMessage::new(3, 74, 5),
+            ),
+            MidiEvent::new(
+                20,
+                false,
+                false,
+                CcShapeKind::Square,
+                CCMessage::new(3, 1, 5),
+            ),
+        ];
+        let pressure = vec![MidiEvent::new(
+            250,
+            false,
+            false,
+            CcShapeKind::Square,
+            ChannelPressureMessage::from_raw(vec![0xd1, 30]).unwrap(),
+        )];
+        let events = MpeTakeEvents::from_parts(
+            zone,
+            notes.into_iter(),
+            pitch_bend.into_iter(),
+            cc.into_iter(),
+            pressure.into_iter(),
+        );
+        let [a, b, c] = [&events.notes[0], &events.notes[1], &events.notes[2]];
+        assert_eq!((a.note.note, b.note.note, c.note.note), (60, 64, 62));
+        assert_eq!(a.pitch_bend.len(), 2);
+        assert_eq!(b.pitch_bend.len(), 1);
+        assert_eq!(b.timbre.len(), 1);
+        assert_eq!(c.pitch_bend.len(), 1);
+        assert_eq!(c.pressure.len(), 1);
+        assert_eq!(events.global_pitch_bend.len(), 2);
+        assert_eq!(events.global_cc.len(), 1);
+        assert_eq!(b.pitch_offset_at(&zone, 60), -48.0);
+        assert_eq!(a.pitch_offset_at(&zone, 0), 0.0);
+
+        let raw = events.to_raw_events();
+        let positions: Vec<_> = raw.iter().map(|e| e.ppq_position()).collect();
+        let mut sorted = positions.clone();
+        sorted.sort();
+        assert_eq!(positions, sorted);
+        // Pitch bend before note-on at the same position.
+        assert_eq!(raw[1].message().buf[0], 0xe1);
+        assert_eq!(raw[2].message().buf[0] & 0xf0, 0x90);
+        let buf: Vec<u8> = MidiEventConsumer::new(raw.into_iter()).collect();
+        let back = MpeTakeEvents::from_builder(
+            zone,
+            MidiEventBuilder::new(buf.into_iter()),
+        );
+        assert_eq!(back, events);
+    }
+}
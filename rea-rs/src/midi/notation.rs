@@ -0,0 +1,684 @@
+//! Typed representation of REAPER notation events.
+//!
+//! Notation event (`0xff 0x0f` meta-event) holds text of the form:
+//! - `NOTE <channel 0-15> <pitch> key value key value ...`
+//! - `TRAC key value key value ...`
+//!
+//! For example: `NOTE 0 60 articulation staccato text "a b"` or
+//! `TRAC dynamic crescendo len 1.000`.
+//!
+//! Every `key value` pair is parsed into [NotationProperty]. Values may be
+//! quoted with `"` if they contain spaces (`\"` and `\\` escape quote and
+//! backslash inside quotes). Parsed notation keeps the original text of
+//! every property, so `TypedNotation::from_str(text)?.to_string()` gives
+//! exactly the same text, and only added or changed properties are
+//! written in the normalized form.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let msg = NotationMessage::from(TypedNotation::note(
+//!     1,
+//!     60,
+//!     vec![
+//!         NotationProperty::Articulation(Articulation::Staccato),
+//!         NotationProperty::Text("sotto voce".to_string()),
+//!     ],
+//! ));
+//! assert_eq!(
+//!     msg.get_raw()[2..].to_vec(),
+//!     b"NOTE 0 60 articulation staccato text \"sotto voce\"".to_vec()
+//! );
+//! let notation = msg.typed_notation().unwrap();
+//! assert_eq!(notation.articulations(), vec![&Articulation::Staccato]);
+//! ```
+
+use std::{fmt::Display, ops::Range, str::FromStr};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{MidiMessage, NotationMessage, ReaRsError, ReaperResult};
+
+/// Builds enum of known names with fallback to `Other(String)`.
+macro_rules! notation_names {
+    (
+        $(#[$meta:meta])*
+        $name:ident { $($variant:ident => $string:literal),* $(,)? }
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+        )]
+        pub enum $name {
+            $($variant,)*
+            /// Name, that is not known by rea-rs.
+            Other(String),
+        }
+        impl $name {
+            pub fn from_name(name: &str) -> Self {
+                match name {
+                    $($string => Self::$variant,)*
+                    other => Self::Other(other.to_string()),
+                }
+            }
+            pub fn name(&self) -> &str {
+                match self {
+                    $(Self::$variant => $string,)*
+                    Self::Other(name) => name.as_str(),
+                }
+            }
+        }
+    };
+}
+
+notation_names!(
+    /// Note articulation (`articulation` key).
+    Articulation {
+        Accent => "accent",
+        Marcato => "marcato",
+        Staccato => "staccato",
+        Staccatissimo => "staccatissimo",
+        Tenuto => "tenuto",
+        Portato => "portato",
+        Fermata => "fermata",
+        UpBow => "upbow",
+        DownBow => "downbow",
+        Harmonic => "harmonic",
+        Stopped => "stopped",
+        Open => "open",
+    }
+);
+
+notation_names!(
+    /// Dynamics marks and hairpins (`dynamic` key).
+    Dynamic {
+        Pppp => "pppp",
+        Ppp => "ppp",
+        Pp => "pp",
+        P => "p",
+        Mp => "mp",
+        Mf => "mf",
+        F => "f",
+        Ff => "ff",
+        Fff => "fff",
+        Ffff => "ffff",
+        Fp => "fp",
+        Fz => "fz",
+        Sf => "sf",
+        Sfz => "sfz",
+        Sffz => "sffz",
+        Rfz => "rfz",
+        Crescendo => "crescendo",
+        Diminuendo => "diminuendo",
+    }
+);
+
+notation_names!(
+    /// Ornaments (`ornament` key).
+    Ornament {
+        Trill => "trill",
+        Mordent => "mordent",
+        InvertedMordent => "mordent_inverted",
+        Turn => "turn",
+        InvertedTurn => "turn_inverted",
+        Tremolo => "tremolo",
+        Arpeggio => "arpeggio",
+        Glissando => "glissando",
+    }
+);
+
+notation_names!(
+    /// Staff clef (`clef` key).
+    Clef {
+        Treble => "treble",
+        Bass => "bass",
+        Alto => "alto",
+        Tenor => "tenor",
+        Percussion => "percussion",
+        TrebleOctaveDown => "treble8vb",
+        BassOctaveDown => "bass8vb",
+    }
+);
+
+notation_names!(
+    /// Beaming of the note (`beam` key).
+    BeamMode {
+        Auto => "auto",
+        Start => "start",
+        Continue => "continue",
+        Break => "break",
+        None => "none",
+    }
+);
+
+notation_names!(
+    /// Tie of the note (`tie` key).
+    TieMode {
+        Start => "1",
+        None => "0",
+        LetRing => "letring",
+    }
+);
+
+/// Key signature (`key` key).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeySignature {
+    /// Positive — number of sharps, negative — number of flats.
+    Accidentals(i8),
+    /// Any other representation.
+    Other(String),
+}
+impl KeySignature {
+    pub fn from_name(name: &str) -> Self {
+        match name.parse::<i8>() {
+            Ok(n) if n.to_string() == name => Self::Accidentals(n),
+            _ => Self::Other(name.to_string()),
+        }
+    }
+    pub fn name(&self) -> String {
+        match self {
+            Self::Accidentals(n) => n.to_string(),
+            Self::Other(name) => name.clone(),
+        }
+    }
+}
+
+/// One `key value` pair of notation event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NotationProperty {
+    Articulation(Articulation),
+    Ornament(Ornament),
+    Dynamic(Dynamic),
+    /// Length of dynamic hairpin or other spanning notation in quarters.
+    Length(f64),
+    /// Text, attached to the note or to the track.
+    Text(String),
+    /// Lyric syllable.
+    Lyric(String),
+    /// Custom notation text.
+    Custom(String),
+    /// 1-based voice number.
+    Voice(u8),
+    /// Staff (0 — default, other values — cross-staff notes).
+    Staff(i32),
+    Beam(BeamMode),
+    Tie(TieMode),
+    KeySignature(KeySignature),
+    Clef(Clef),
+    /// Any other pair.
+    Other {
+        key: String,
+        value: String,
+    },
+    /// The last key without value.
+    Flag(String),
+}
+impl NotationProperty {
+    fn from_pair(key: &str, value: String) -> Self {
+        match key {
+            "articulation" => {
+                Self::Articulation(Articulation::from_name(&value))
+            }
+            "ornament" => Self::Ornament(Ornament::from_name(&value)),
+            "dynamic" => Self::Dynamic(Dynamic::from_name(&value)),
+            "text" => Self::Text(value),
+            "lyric" => Self::Lyric(value),
+            "custom" => Self::Custom(value),
+            "beam" => Self::Beam(BeamMode::from_name(&value)),
+            "tie" => Self::Tie(TieMode::from_name(&value)),
+            "key" => Self::KeySignature(KeySignature::from_name(&value)),
+            "clef" => Self::Clef(Clef::from_name(&value)),
+            "len" if is_canonical_length(&value) => {
+                Self::Length(value.parse().expect("checked"))
+            }
+            "voice" if is_canonical_int::<u8>(&value) => {
+                Self::Voice(value.parse().expect("checked"))
+            }
+            "staff" if is_canonical_int::<i32>(&value) => {
+                Self::Staff(value.parse().expect("checked"))
+            }
+            _ => Self::Other {
+                key: key.to_string(),
+                value,
+            },
+        }
+    }
+
+    /// Key and value as they are written to the event.
+    pub fn to_pair(&self) -> (String, Option<String>) {
+        let (key, value) = match self {
+            Self::Articulation(v) => ("articulation", v.name().to_string()),
+            Self::Ornament(v) => ("ornament", v.name().to_string()),
+            Self::Dynamic(v) => ("dynamic", v.name().to_string()),
+            Self::Length(v) => ("len", format!("{:.3}", v)),
+            Self::Text(v) => return ("text".to_string(), Some(v.clone())),
+            Self::Lyric(v) => ("lyric", v.clone()),
+            Self::Custom(v) => ("custom", v.clone()),
+            Self::Voice(v) => ("voice", v.to_string()),
+            Self::Staff(v) => ("staff", v.to_string()),
+            Self::Beam(v) => ("beam", v.name().to_string()),
+            Self::Tie(v) => ("tie", v.name().to_string()),
+            Self::KeySignature(v) => ("key", v.name()),
+            Self::Clef(v) => ("clef", v.name().to_string()),
+            Self::Other { key, value } => {
+                return (key.clone(), Some(value.clone()))
+            }
+            Self::Flag(key) => return (key.clone(), None),
+        };
+        (key.to_string(), Some(value))
+    }
+}
+
+fn is_canonical_length(value: &str) -> bool {
+    match value.parse::<f64>() {
+        Ok(v) => format!("{:.3}", v) == value,
+        Err(_) => false,
+    }
+}
+
+fn is_canonical_int<T: FromStr + ToString>(value: &str) -> bool {
+    match value.parse::<T>() {
+        Ok(v) => v.to_string() == value,
+        Err(_) => false,
+    }
+}
+
+/// Fully parsed notation event.
+///
+/// See [module doc](crate::midi::notation).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TypedNotation {
+    /// Note notation. Channel is 1-based: 1..=16. Channel 0 is written
+    /// as channel 1.
+    Note {
+        channel: u8,
+        note: u8,
+        properties: Vec<NotationProperty>,
+        source: NotationSource,
+    },
+    /// Track-level notation (`TRAC`).
+    Track(Vec<NotationProperty>, NotationSource),
+    /// Notation of unknown kind: the whole text.
+    Unknown(String),
+}
+impl TypedNotation {
+    /// Note notation. Channel is 1-based.
+    pub fn note(
+        channel: u8,
+        note: u8,
+        properties: Vec<NotationProperty>,
+    ) -> Self {
+        Self::Note {
+            channel,
+            note,
+            properties,
+            source: NotationSource::default(),
+        }
+    }
+    pub fn track(properties: Vec<NotationProperty>) -> Self {
+        Self::Track(properties, NotationSource::default())
+    }
+    pub fn properties(&self) -> &[NotationProperty] {
+        match self {
+            Self::Note { properties, .. } => properties,
+            Self::Track(properties, _) => properties,
+            Self::Unknown(_) => &[],
+        }
+    }
+    /// None for [TypedNotation::Unknown].
+    pub fn properties_mut(&mut self) -> Option<&mut Vec<NotationProperty>> {
+        match self {
+            Self::Note { properties, .. } => Some(properties),
+            Self::Track(properties, _) => Some(properties),
+            Self::Unknown(_) => None,
+        }
+    }
+    pub fn articulations(&self) -> Vec<&Articulation> {
+        self.properties()
+            .iter()
+            .filter_map(|p| match p {
+                NotationProperty::Articulation(a) => Some(a),
+                _ => None,
+            })
+            .collect()
+    }
+    pub fn dynamics(&self) -> Vec<&Dynamic> {
+        self.properties()
+            .iter()
+            .filter_map(|p| match p {
+                NotationProperty::Dynamic(d) => Some(d),
+                _ => None,
+            })
+            .collect()
+    }
+    /// The first text property, if any.
+    pub fn text(&self) -> Option<&str> {
+        self.properties().iter().find_map(|p| match p {
+            NotationProperty::Text(t) => Some(t.as_str()),
+            _ => None,
+        })
+    }
+    /// The first lyric property, if any.
+    pub fn lyric(&self) -> Option<&str> {
+        self.properties().iter().find_map(|p| match p {
+            NotationProperty::Lyric(t) => Some(t.as_str()),
+            _ => None,
+        })
+    }
+    /// Voice of note or track. None if not set.
+    pub fn voice(&self) -> Option<u8> {
+        self.properties().iter().find_map(|p| match p {
+            NotationProperty::Voice(v) => Some(*v),
+            _ => None,
+        })
+    }
+}
+impl FromStr for TypedNotation {
+    type Err = ReaRsError;
+
+    fn from_str(text: &str) -> ReaperResult<Self> {
+        let tokens = tokenize(text)?;
+        let value = |idx: usize| tokens[idx].0.as_str();
+        let (header, is_note) = match tokens.first().map(|t| t.0.as_str()) {
+            Some("NOTE") => (3, true),
+            Some("TRAC") => (1, false),
+            _ => return Ok(Self::Unknown(text.to_string())),
+        };
+        if tokens.len() < header {
+            return Err(ReaRsError::InvalidObject("Notation is too short"));
+        }
+        let head = match is_note {
+            false => None,
+            true => Some((
+                match value(1).parse::<u8>() {
+                    Ok(channel) if channel <= 15 => channel + 1,
+                    _ => {
+                        return Err(ReaRsError::InvalidObject(
+                            "Should be channel number 0-15",
+                        ))
+                    }
+                },
+                value(2).parse::<u8>().map_err(|_| {
+                    ReaRsError::InvalidObject("Should be note number")
+                })?,
+            )),
+        };
+        let mut source = NotationSource {
+            header: text[..tokens[header - 1].1.end].to_string(),
+            head,
+            pairs: Vec::new(),
+            tail: text[tokens[tokens.len() - 1].1.end..].to_string(),
+        };
+        let mut pairs = Vec::new();
+        let mut idx = header;
+        while idx < tokens.len() {
+            let start = tokens[idx - 1].1.end;
+            let (property, end) = match tokens.get(idx + 1) {
+                Some((value, span)) => (
+                    NotationProperty::from_pair(&tokens[idx].0, value.clone()),
+                    span.end,
+                ),
+                None => (
+                    NotationProperty::Flag(tokens[idx].0.clone()),
+                    tokens[idx].1.end,
+                ),
+            };
+            source
+                .pairs
+                .push((property.clone(), text[start..end].to_string()));
+            pairs.push(property);
+            idx += 2;
+        }
+        match head {
+            Some((channel, note)) => Ok(Self::Note {
+                channel,
+                note,
+                properties: pairs,
+                source,
+            }),
+            None => Ok(Self::Track(pairs, source)),
+        }
+    }
+}
+impl Display for TypedNotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (head, source) = match self {
+            Self::Note {
+                channel,
+                note,
+                source,
+                ..
+            } => (Some((*channel, *note)), source),
+            Self::Track(_, source) => (None, source),
+            Self::Unknown(text) => return write!(f, "{}", text),
+        };
+        // Header is kept as is, if channel and note are untouched.
+        match head {
+            _ if !source.header.is_empty() && source.head == head => {
+                write!(f, "{}", source.header)?
+            }
+            Some((channel, note)) => {
+                write!(f, "NOTE {} {}", channel.saturating_sub(1), note)?
+            }
+            None => write!(f, "TRAC")?,
+        }
+        let mut unused = source.pairs.iter();
+        for property in self.properties() {
+            // Untouched properties are written as they were.
+            let mut search = unused.clone();
+            if let Some((_, raw)) = search.find(|(p, _)| p == property) {
+                unused = search;
+                write!(f, "{}", raw)?;
+                continue;
+            }
+            let (key, value) = property.to_pair();
+            write!(f, " {}", key)?;
+            if let Some(value) = value {
+                let always = matches!(property, NotationProperty::Text(_));
+                write!(f, " {}", quoted(value, always))?;
+            }
+        }
+        write!(f, "{}", source.tail)
+    }
+}
+
+/// Original text of parsed notation.
+///
+/// Used to write untouched parts exactly as they were. Does not take part
+/// in comparison.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotationSource {
+    /// Text up to the end of the last header token.
+    header: String,
+    /// Parsed channel and note of the header.
+    head: Option<(u8, u8)>,
+    /// Parsed property and its text with leading whitespace.
+    pairs: Vec<(NotationProperty, String)>,
+    /// Whitespace after the last token.
+    tail: String,
+}
+impl PartialEq for NotationSource {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+fn quoted(value: String, always: bool) -> String {
+    let needs_quotes = always
+        || value.is_empty()
+        || value.starts_with('"')
+        || value.contains(char::is_whitespace);
+    match needs_quotes {
+        true => {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{}\"", escaped)
+        }
+        false => value,
+    }
+}
+
+/// Split by whitespace, respecting `"` quotes.
+///
+/// Returns unquoted tokens with their byte ranges in text.
+fn tokenize(text: &str) -> ReaperResult<Vec<(String, Range<usize>)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let (start, first) = match chars.next() {
+            None => return Ok(tokens),
+            Some(c) => c,
+        };
+        let mut token = String::new();
+        let end = if first == '"' {
+            loop {
+                match chars.next() {
+                    None => {
+                        return Err(ReaRsError::InvalidObject(
+                            "Unclosed quote in notation",
+                        ))
+                    }
+                    Some((idx, '"')) => break idx + 1,
+                    Some((_, '\\')) => {
+                        match chars.next_if(|(_, c)| *c == '"' || *c == '\\') {
+                            Some((_, c)) => token.push(c),
+                            None => token.push('\\'),
+                        }
+                    }
+                    Some((_, c)) => token.push(c),
+                }
+            }
+        } else {
+            token.push(first);
+            while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace())
+            {
+                token.push(c);
+            }
+            chars.peek().map(|(idx, _)| *idx).unwrap_or(text.len())
+        };
+        tokens.push((token, start..end));
+    }
+}
+
+impl From<TypedNotation> for NotationMessage {
+    fn from(value: TypedNotation) -> Self {
+        Self {
+            buf: NotationMessage::text_to_buf(value.to_string()),
+        }
+    }
+}
+impl NotationMessage {
+    /// Parse notation with all properties.
+    ///
+    /// See [crate::midi::notation]
+    pub fn typed_notation(&self) -> ReaperResult<TypedNotation> {
+        let text = String::from_utf8(self.borrow_raw()[2..].to_vec())
+            .map_err(|_| {
+                ReaRsError::InvalidObject("Notation is not valid UTF-8")
+            })?;
+        TypedNotation::from_str(&text)
+    }
+    pub fn set_typed_notation(&mut self, notation: TypedNotation) {
+        self.set_text(notation.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for text in [
+            "NOTE 0 57 text \"text notation\"",
+            "NOTE 0 59 custom test",
+            "NOTE 0 60 articulation staccato",
+            "TRAC dynamic crescendo len 1.000",
+            "NOTE 15 127 voice 2 staff -1 beam break tie 1 lyric Hel-",
+            "NOTE 3 64 ornament trill articulation unknown_one foo \"a b\"",
+            "TRAC clef bass key -3 something",
+            "KSIG 0 0",
+        ] {
+            let notation = TypedNotation::from_str(text).unwrap();
+            assert_eq!(notation.to_string(), text);
+            let msg = NotationMessage::from(notation.clone());
+            assert_eq!(msg.typed_notation().unwrap(), notation);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_not_normalized() {
+        for text in [
+            "NOTE 0 60  text abc   articulation staccato\tlyric \"x\" ",
+            "NOTE  00 060 len 1.5 voice 02",
+            " TRAC key \"-3\"  clef  \"\"",
+            "NOTE 0 60 text \"say \\\"hi\\\" \\\\ c:\\d\"",
+        ] {
+            let notation = TypedNotation::from_str(text).unwrap();
+            assert_eq!(notation.to_string(), text);
+        }
+        let mut notation =
+            TypedNotation::from_str("NOTE 0  60  voice 02  len 1.5  custom a")
+                .unwrap();
+        if let TypedNotation::Note { properties, .. } = &mut notation {
+            properties[1] = NotationProperty::Length(2.0);
+            properties.push(NotationProperty::Text("\"q\" \\".to_string()));
+        }
+        assert_eq!(
+            notation.to_string(),
+            "NOTE 0  60  voice 02 len 2.000  custom a text \"\\\"q\\\" \\\\\""
+        );
+        let parsed = TypedNotation::from_str(&notation.to_string()).unwrap();
+        assert_eq!(parsed.text(), Some("\"q\" \\"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let notation = TypedNotation::from_str(
+            "NOTE 1 62 articulation accent text \"con  brio\" voice 2 len x",
+        )
+        .unwrap();
+        assert_eq!(
+            notation,
+            TypedNotation::note(
+                2,
+                62,
+                vec![
+                    NotationProperty::Articulation(Articulation::Accent),
+                    NotationProperty::Text("con  brio".to_string()),
+                    NotationProperty::Voice(2),
+                    NotationProperty::Other {
+                        key: "len".to_string(),
+                        value: "x".to_string()
+                    },
+                ]
+            )
+        );
+        assert_eq!(notation.text(), Some("con  brio"));
+        assert_eq!(notation.voice(), Some(2));
+        let track =
+            TypedNotation::from_str("TRAC dynamic mf clef treble key 2")
+                .unwrap();
+        assert_eq!(track.dynamics(), vec![&Dynamic::Mf]);
+        assert_eq!(
+            track.properties()[2],
+            NotationProperty::KeySignature(KeySignature::Accidentals(2))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(TypedNotation::from_str("NOTE 0").is_err());
+        assert!(TypedNotation::from_str("NOTE x 60").is_err());
+        assert!(TypedNotation::from_str("NOTE 16 60").is_err());
+        assert!(TypedNotation::from_str("NOTE 255 60 text x").is_err());
+        let note = TypedNotation::note(0, 60, Vec::new());
+        assert_eq!(note.to_string(), "NOTE 0 60");
+        assert!(TypedNotation::from_str("NOTE 0 60 text \"abc").is_err());
+        let msg =
+            NotationMessage::from_raw(vec![0xff, 0x0f, 0xc3, 0x28]).unwrap();
+        assert!(msg.typed_notation().is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Analysis and repair of [MidiNoteEvent] collections.
+//!
+//! Overlapping notes of the same pitch and channel produce stuck or cut
+//! notes after [crate::midi::flatten_midi_notes], because note-offs are
+//! paired with note-ons in order. [NoteReport] finds such problems, and
+//! repair functions fix them in-place.
+//!
+//! All repair functions sort notes by start position (then by channel and
+//! pitch).
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let mut notes = vec![
+//!     MidiNoteEvent::new(0, 480, false, false, 1, 60, 100, 0),
+//!     MidiNoteEvent::new(240, 720, false, false, 1, 60, 100, 0),
+//!     MidiNoteEvent::new(240, 240, false, false, 1, 64, 100, 0),
+//! ];
+//! let report = NoteReport::new(&notes);
+//! assert_eq!(report.overlaps.len(), 1);
+//! assert_eq!(report.zero_length, vec![2]);
+//!
+//! remove_zero_length_notes(&mut notes);
+//! trim_note_overlaps(&mut notes);
+//! assert!(NoteReport::new(&notes).is_clean());
+//! assert_eq!(notes[0].end_in_ppq, 240);
+//! ```
+
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    FilterNotes, MidiEvent, MidiNoteEvent, NoteOffMessage, NoteOnMessage,
+    RawMidiMessage,
+};
+
+/// Two notes of the same channel and pitch, that sound simultaneously.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteOverlap {
+    /// Index of the earlier note.
+    pub first: usize,
+    /// Index of the later note.
+    pub second: usize,
+    /// Notes start at the same position.
+    pub is_duplicate: bool,
+}
+
+/// Problems, found in notes.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NoteReport {
+    /// Indices refer to the analyzed notes.
+    pub overlaps: Vec<NoteOverlap>,
+    /// Indices of notes with `end <= start`.
+    pub zero_length: Vec<usize>,
+    /// Note-ons without note-offs (only from raw events).
+    pub orphan_note_ons: Vec<MidiEvent<NoteOnMessage>>,
+    /// Note-offs without note-ons (only from raw events).
+    pub orphan_note_offs: Vec<MidiEvent<NoteOffMessage>>,
+}
+impl NoteReport {
+    pub fn new(notes: &[MidiNoteEvent]) -> Self {
+        Self {
+            overlaps: find_note_overlaps(notes),
+            zero_length: notes
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| n.end_in_ppq <= n.start_in_ppq)
+                .map(|(idx, _)| idx)
+                .collect(),
+            orphan_note_ons: Vec::new(),
+            orphan_note_offs: Vec::new(),
+        }
+    }
+
+    /// Pair raw events by [FilterNotes] and analyze the result.
+    ///
+    /// Returned notes do not include orphans. Indices of report refer to
+    /// them.
+    pub fn from_raw_events(
+        events: impl Iterator<Item = MidiEvent<RawMidiMessage>>,
+    ) -> (Vec<MidiNoteEvent>, Self) {
+        let mut filter = FilterNotes::new(events).skip_orphan_note_offs();
+        let notes: Vec<MidiNoteEvent> = filter.by_ref().collect();
+        let mut report = Self::new(&notes);
+        report.orphan_note_ons = filter.pending_note_ons().clone();
+        report.orphan_note_offs = filter.orphan_note_offs().clone();
+        (notes, report)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.overlaps.is_empty()
+            && self.zero_length.is_empty()
+            && self.orphan_note_ons.is_empty()
+            && self.orphan_note_offs.is_empty()
+    }
+}
+
+/// Find overlaps of notes with the same channel and pitch.
+pub fn find_note_overlaps(notes: &[MidiNoteEvent]) -> Vec<NoteOverlap> {
+    let mut by_key: HashMap<(u8, u8), Vec<usize>> = HashMap::new();
+    for (idx, note) in notes.iter().enumerate() {
+        by_key
+            .entry((note.channel, note.note))
+            .or_default()
+            .push(idx);
+    }
+    let mut overlaps = Vec::new();
+    for indices in by_key.values_mut() {
+        indices.sort_by_key(|i| (notes[*i].start_in_ppq, *i));
+        // The note with the latest end among the previous ones.
+        let mut longest: Option<usize> = None;
+        for idx in indices.iter() {
+            let note = &notes[*idx];
+            if let Some(prev) = longest {
+                let prev_note = &notes[prev];
+                if note.start_in_ppq < prev_note.end_in_ppq
+                    || note.start_in_ppq == prev_note.start_in_ppq
+                {
+                    overlaps.push(NoteOverlap {
+                        first: prev,
+                        second: *idx,
+                        is_duplicate: note.start_in_ppq
+                            == prev_note.start_in_ppq,
+                    });
+                }
+                if note.end_in_ppq <= prev_note.end_in_ppq {
+                    continue;
+                }
+            }
+            longest = Some(*idx);
+        }
+    }
+    overlaps.sort_by_key(|o| (o.first, o.second));
+    overlaps
+}
+
+fn sort_notes(notes: &mut [MidiNoteEvent]) {
+    notes.sort_by_key(|n| (n.start_in_ppq, n.channel, n.note));
+}
+
+/// Remove notes with `end <= start`.
+pub fn remove_zero_length_notes(notes: &mut Vec<MidiNoteEvent>) {
+    notes.retain(|n| n.end_in_ppq > n.start_in_ppq);
+    sort_notes(notes);
+}
+
+/// Notes with the same channel, pitch and start are merged into one: the
+/// longest, with maximum velocity and selected if any was selected.
+pub fn merge_duplicate_notes(notes: &mut Vec<MidiNoteEvent>) {
+    sort_notes(notes);
+    let mut merged: Vec<MidiNoteEvent> = Vec::with_capacity(notes.len());
+    for note in notes.drain(..) {
+        match merged
+            .iter_mut()
+            .rev()
+            .take_while(|m| m.start_in_ppq == note.start_in_ppq)
+            .find(|m| m.channel == note.channel && m.note == note.note)
+        {
+            Some(m) => {
+                m.end_in_ppq = m.end_in_ppq.max(note.end_in_ppq);
+                m.on_velocity = m.on_velocity.max(note.on_velocity);
+                m.is_selected |= note.is_selected;
+                m.is_muted &= note.is_muted;
+            }
+            None => merged.push(note),
+        }
+    }
+    *notes = merged;
+}
+
+/// Cut every note at the start of the next note with the same channel
+/// and pitch. Duplicates are merged.
+pub fn trim_note_overlaps(notes: &mut Vec<MidiNoteEvent>) {
+    merge_duplicate_notes(notes);
+    let mut next_start: HashMap<(u8, u8), u32> = HashMap::new();
+    for note in notes.iter_mut().rev() {
+        let key = (note.channel, note.note);
+        if let Some(next) = next_start.get(&key) {
+            note.end_in_ppq = note.end_in_ppq.min(*next);
+        }
+        next_start.insert(key, note.start_in_ppq);
+    }
+}
+
+/// Extend (or cut) every note to the start of the next note of the same
+/// channel.
+///
+/// If `max_gap` is given, notes, followed by a longer pause, are not
+/// extended. Notes, that start together (chords), are treated as one.
+pub fn make_notes_legato(
+    notes: &mut Vec<MidiNoteEvent>,
+    max_gap: impl Into<Option<u32>>,
+) {
+    let max_gap = max_gap.into();
+    sort_notes(notes);
+    let mut next_start: HashMap<u8, u32> = HashMap::new();
+    let mut current_start: HashMap<u8, u32> = HashMap::new();
+    for note in notes.iter_mut().rev() {
+        let channel = note.channel;
+        if current_start.get(&channel) != Some(&note.start_in_ppq) {
+            if let Some(start) = current_start.get(&channel) {
+                next_start.insert(channel, *start);
+            }
+            current_start.insert(channel, note.start_in_ppq);
+        }
+        let next = match next_start.get(&channel) {
+            Some(next) => *next,
+            None => continue,
+        };
+        let gap = next.saturating_sub(note.end_in_ppq);
+        if max_gap.map(|max| gap > max).unwrap_or(false) {
+            continue;
+        }
+        note.end_in_ppq = next;
+    }
+    trim_note_overlaps(notes);
+}
+
+/// Set all notes to the same length.
+pub fn set_notes_length(notes: &mut [MidiNoteEvent], length: u32) {
+    for note in notes.iter_mut() {
+        note.end_in_ppq = note.start_in_ppq + length.max(1);
+    }
+    sort_notes(notes);
+}
+
+/// Limit number of simultaneously sounding notes on every channel.
+///
+/// When a new note starts and the limit is reached, the earliest of
+/// sounding notes is cut at the new note start. Notes, that would become
+/// zero-length, are removed.
+pub fn limit_notes_polyphony(
+    notes: &mut Vec<MidiNoteEvent>,
+    max_voices: usize,
+) {
+    let max_voices = max_voices.max(1);
+    sort_notes(notes);
+    // sounding note indices per channel, in start order.
+    let mut sounding: HashMap<u8, Vec<usize>> = HashMap::new();
+    for idx in 0..notes.len() {
+        let (channel, start) = (notes[idx].channel, notes[idx].start_in_ppq);
+        let voices = sounding.entry(channel).or_default();
+        voices.retain(|v| notes[*v].end_in_ppq > start);
+        while voices.len() >= max_voices {
+            let stolen = voices.remove(0);
+            notes[stolen].end_in_ppq = start;
+        }
+        voices.push(idx);
+    }
+    remove_zero_length_notes(notes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        flatten_midi_notes, sorted_by_ppq, to_raw_midi_events, CcShapeKind,
+        MidiMessage,
+    };
+
+    fn note(start: u32, end: u32, channel: u8, pitch: u8) -> MidiNoteEvent {
+        MidiNoteEvent::new(start, end, false, false, channel, pitch, 100, 0)
+    }
+
+    fn positions(notes: &[MidiNoteEvent]) -> Vec<(u32, u32, u8)> {
+        notes
+            .iter()
+            .map(|n| (n.start_in_ppq, n.end_in_ppq, n.note))
+            .collect()
+    }
+
+    #[test]
+    fn test_report() {
+        let notes = vec![
+            note(0, 100, 1, 60),
+            note(50, 80, 1, 60),
+            note(90, 200, 1, 60),
+            note(90, 200, 2, 60),
+            note(300, 300, 1, 62),
+        ];
+        let report = NoteReport::new(&notes);
+        assert_eq!(
+            report.overlaps,
+            vec![
+                NoteOverlap {
+                    first: 0,
+                    second: 1,
+                    is_duplicate: false
+                },
+                NoteOverlap {
+                    first: 0,
+                    second: 2,
+                    is_duplicate: false
+                },
+            ]
+        );
+        assert_eq!(report.zero_length, vec![4]);
+
+        let mut raw: Vec<_> =
+            flatten_midi_notes(notes[..4].iter().cloned()).collect();
+        raw.push(MidiEvent::new(
+            400,
+            false,
+            false,
+            CcShapeKind::Square,
+            NoteOnMessage::new(3, 70, 100).as_raw_message(),
+        ));
+        raw.push(MidiEvent::new(
+            500,
+            false,
+            false,
+            CcShapeKind::Square,
+            NoteOffMessage::new(3, 71, 0).as_raw_message(),
+        ));
+        let (notes, report) =
+            NoteReport::from_raw_events(sorted_by_ppq(raw.into_iter()));
+        assert_eq!(notes.len(), 4);
+        assert_eq!(report.orphan_note_ons.len(), 1);
+        assert_eq!(report.orphan_note_offs.len(), 1);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_trim_and_merge() {
+        let mut notes = vec![
+            note(0, 100, 1, 60),
+            note(0, 150, 1, 60),
+            note(50, 80, 1, 60),
+            note(60, 70, 1, 61),
+        ];
+        notes[1].on_velocity = 120;
+        trim_note_overlaps(&mut notes);
+        assert_eq!(
+            positions(&notes),
+            vec![(0, 50, 60), (50, 80, 60), (60, 70, 61)]
+        );
+        assert_eq!(notes[0].on_velocity, 120);
+        assert!(NoteReport::new(&notes).is_clean());
+        // no more stuck notes after flattening
+        let raw: Vec<_> = to_raw_midi_events(sorted_by_ppq(
+            flatten_midi_notes(notes.clone().into_iter()),
+        ))
+        .collect();
+        let (back, report) = NoteReport::from_raw_events(raw.into_iter());
+        assert!(report.is_clean());
+        assert_eq!(back.len(), 3);
+    }
+
+    #[test]
+    fn test_legato_and_length() {
+        let mut notes = vec![
+            note(0, 10, 1, 60),
+            note(0, 10, 1, 64),
+            note(100, 300, 1, 62),
+            note(1000, 1010, 1, 60),
+            note(50, 60, 2, 60),
+        ];
+        make_notes_legato(&mut notes, 500);
+        assert_eq!(
+            positions(&notes),
+            vec![
+                (0, 100, 60),
+                (0, 100, 64),
+                (50, 60, 60),
+                (100, 300, 62),
+                (1000, 1010, 60)
+            ]
+        );
+        set_notes_length(&mut notes, 20);
+        assert!(notes.iter().all(|n| n.end_in_ppq - n.start_in_ppq == 20));
+    }
+
+    #[test]
+    fn test_polyphony() {
+        let mut notes = vec![
+            note(0, 100, 1, 60),
+            note(10, 100, 1, 64),
+            note(20, 100, 1, 67),
+            note(20, 100, 2, 67),
+            note(20, 100, 1, 72),
+        ];
+        limit_notes_polyphony(&mut notes, 2);
+        assert_eq!(
+            positions(&notes),
+            vec![
+                (0, 20, 60),
+                (10, 20, 64),
+                (20, 100, 67),
+                (20, 100, 72),
+                (20, 100, 67)
+            ]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! RPN, NRPN and 14-bit CC aggregation.
+//!
+//! Raw CC stream is folded into [ParameterNumberEvent] objects by
+//! [FilterParameterNumbers] (see
+//! [crate::midi::MidiEventBuilder::filter_parameter_numbers]), and
+//! unfolded back to [CCMessage] events by [flatten_parameter_numbers].
+//!
+//! Rules of aggregation:
+//! - CC 101/100 select RPN, CC 99/98 select NRPN. RPN 127/127 (null)
+//!   deselects parameter.
+//! - CC 6 (data entry MSB) with optional CC 38 (data entry LSB) produces
+//!   event for the selected parameter. Data entry without selected
+//!   parameter is skipped.
+//! - CC 0..31 (MSB) with optional CC 32..63 (LSB) produces 14-bit CC
+//!   event. LSB without MSB refines the last MSB value of the controller.
+//! - MSB is considered as finished, if the next CC message on the same
+//!   channel is not the corresponding LSB, or if any event at the later
+//!   position appeared.
+//! - All other events (including CC 96/97 increment/decrement) are skipped.
+
+use std::collections::VecDeque;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    CCMessage, CcShapeKind, MidiEvent, MidiEventBuilder, MidiMessage,
+    RawMidiMessage,
+};
+
+/// Kind of parameter, which value is carried by [ParameterNumberEvent].
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum ParameterNumberKind {
+    /// Registered parameter number (14-bit).
+    Rpn(u16),
+    /// Non-registered parameter number (14-bit).
+    Nrpn(u16),
+    /// MSB controller number (0..31). LSB is sent as `number + 32`.
+    Cc14Bit(u8),
+}
+impl ParameterNumberKind {
+    pub const PITCH_BEND_SENSITIVITY: Self = Self::Rpn(0);
+    pub const FINE_TUNING: Self = Self::Rpn(1);
+    pub const COARSE_TUNING: Self = Self::Rpn(2);
+    pub const TUNING_PROGRAM: Self = Self::Rpn(3);
+    pub const TUNING_BANK: Self = Self::Rpn(4);
+    pub const MODULATION_DEPTH_RANGE: Self = Self::Rpn(5);
+    /// MPE Configuration Message.
+    pub const MPE_CONFIGURATION: Self = Self::Rpn(6);
+    /// RPN null, used to deselect parameter after data entry.
+    pub const RPN_NULL: Self = Self::Rpn(0x3fff);
+}
+
+/// Special Event type, holds parameter value, that represents 2-4 raw CC
+/// Events.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct ParameterNumberEvent {
+    pub position_in_ppq: u32,
+    pub is_selected: bool,
+    pub is_muted: bool,
+    /// 1-based
+    pub channel: u8,
+    pub kind: ParameterNumberKind,
+    /// 14-bit value: `msb << 7 | lsb`.
+    pub value: u16,
+    /// If false — only MSB is sent (and was received).
+    pub has_lsb: bool,
+}
+impl ParameterNumberEvent {
+    pub fn new(
+        position_in_ppq: u32,
+        is_selected: bool,
+        is_muted: bool,
+        channel: u8,
+        kind: ParameterNumberKind,
+        value: u16,
+        has_lsb: bool,
+    ) -> Self {
+        Self {
+            position_in_ppq,
+            is_selected,
+            is_muted,
+            channel,
+            kind,
+            value,
+            has_lsb,
+        }
+    }
+    pub fn msb(&self) -> u8 {
+        (self.value >> 7) as u8 & 0x7f
+    }
+    pub fn lsb(&self) -> u8 {
+        self.value as u8 & 0x7f
+    }
+
+    /// Unfold to CC events in order of sending.
+    pub fn to_cc_events(&self) -> Vec<MidiEvent<CCMessage>> {
+        let cc = |num: u8, value: u8| {
+            MidiEvent::new(
+                self.position_in_ppq,
+                self.is_selected,
+                self.is_muted,
+                CcShapeKind::Square,
+                CCMessage::new(self.channel, num, value),
+            )
+        };
+        let mut out = Vec::with_capacity(4);
+        let (data_msb, data_lsb) = match self.kind {
+            ParameterNumberKind::Rpn(number) => {
+                out.push(cc(101, (number >> 7) as u8 & 0x7f));
+                out.push(cc(100, number as u8 & 0x7f));
+                (6, 38)
+            }
+            ParameterNumberKind::Nrpn(number) => {
+                out.push(cc(99, (number >> 7) as u8 & 0x7f));
+                out.push(cc(98, number as u8 & 0x7f));
+                (6, 38)
+            }
+            ParameterNumberKind::Cc14Bit(number) => {
+                (number & 0x1f, (number & 0x1f) + 32)
+            }
+        };
+        out.push(cc(data_msb, self.msb()));
+        if self.has_lsb {
+            out.push(cc(data_lsb, self.lsb()));
+        }
+        out
+    }
+}
+
+/// Convert ParameterNumber events to CC events.
+///
+/// Use [crate::midi::to_raw_midi_events] to get raw events.
+pub fn flatten_parameter_numbers(
+    iter: impl Iterator<Item = ParameterNumberEvent>,
+) -> impl Iterator<Item = MidiEvent<CCMessage>> {
+    iter.flat_map(|i| i.to_cc_events().into_iter())
+}
+
+#[derive(Debug, Clone)]
+struct PendingMsb {
+    event: MidiEvent<CCMessage>,
+    kind: ParameterNumberKind,
+    lsb_cc: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChannelState {
+    rpn: [u8; 2],
+    nrpn: [u8; 2],
+    selected: Option<ParameterNumberKind>,
+    data_msb: Option<u8>,
+    cc_msb: [Option<u8>; 32],
+    pending: Option<PendingMsb>,
+}
+
+/// Iterates through RPN, NRPN and 14-bit CC events.
+///
+/// Better not to use outside the module.
+pub struct FilterParameterNumbers<
+    T: Iterator<Item = MidiEvent<RawMidiMessage>>,
+> {
+    midi_events: T,
+    channels: [ChannelState; 16],
+    out: VecDeque<ParameterNumberEvent>,
+}
+impl<T: Iterator<Item = MidiEvent<RawMidiMessage>>> FilterParameterNumbers<T> {
+    pub fn new(events: T) -> Self {
+        Self {
+            midi_events: events,
+            channels: Default::default(),
+            out: VecDeque::new(),
+        }
+    }
+
+    fn flush(&mut self, ch_idx: usize) {
+        if let Some(pending) = self.channels[ch_idx].pending.take() {
+            let msb = pending.event.message().cc_val();
+            self.out.push_back(Self::build(
+                &pending.event,
+                pending.kind,
+                (msb as u16) << 7,
+                false,
+            ));
+        }
+    }
+
+    fn flush_before(&mut self, position: u32) {
+        for ch_idx in 0..16 {
+            let is_old = match &self.channels[ch_idx].pending {
+                Some(p) => p.event.ppq_position() < position,
+                None => false,
+            };
+            if is_old {
+                self.flush(ch_idx);
+            }
+        }
+    }
+
+    fn build(
+        event: &MidiEvent<CCMessage>,
+        kind: ParameterNumberKind,
+        value: u16,
+        has_lsb: bool,
+    ) -> ParameterNumberEvent {
+        ParameterNumberEvent::new(
+            event.ppq_position(),
+            event.selected(),
+            event.muted(),
+            event.message().channel(),
+            kind,
+            value,
+            has_lsb,
+        )
+    }
+
+    fn process(&mut self, event: MidiEvent<CCMessage>) {
+        let ch_idx = event.message().channel() as usize - 1;
+        let (num, val) = (event.message().cc_num(), event.message().cc_val());
+        let is_lsb_of_pending = match &self.channels[ch_idx].pending {
+            Some(p) => p.lsb_cc == num,
+            None => false,
+        };
+        if is_lsb_of_pending {
+            let pending = self.channels[ch_idx].pending.take().unwrap();
+            let msb = pending.event.message().cc_val() as u16;
+            self.out.push_back(Self::build(
+                &pending.event,
+                pending.kind,
+                msb << 7 | val as u16,
+                true,
+            ));
+            return;
+        }
+        self.flush(ch_idx);
+        let state = &mut self.channels[ch_idx];
+        match num {
+            99 | 98 => {
+                state.nrpn[(num == 98) as usize] = val;
+                let number =
+                    (state.nrpn[0] as u16) << 7 | state.nrpn[1] as u16;
+                state.selected = Some(ParameterNumberKind::Nrpn(number));
+                state.data_msb = None;
+            }
+            101 | 100 => {
+                state.rpn[(num == 100) as usize] = val;
+                let number = (state.rpn[0] as u16) << 7 | state.rpn[1] as u16;
+                state.selected = match ParameterNumberKind::Rpn(number) {
+                    ParameterNumberKind::RPN_NULL => None,
+                    kind => Some(kind),
+                };
+                state.data_msb = None;
+            }
+            6 => {
+                if let Some(kind) = state.selected {
+                    state.data_msb = Some(val);
+                    state.pending = Some(PendingMsb {
+                        event,
+                        kind,
+                        lsb_cc: 38,
+                    });
+                }
+            }
+            38 => {
+                if let (Some(kind), Some(msb)) =
+                    (state.selected, state.data_msb)
+                {
+                    let value = (msb as u16) << 7 | val as u16;
+                    self.out.push_back(Self::build(&event, kind, value, true));
+                }
+            }
+            0..=31 => {
+                state.cc_msb[num as usize] = Some(val);
+                state.pending = Some(PendingMsb {
+                    event,
+                    kind: ParameterNumberKind::Cc14Bit(num),
+                    lsb_cc: num + 32,
+                });
+            }
+            32..=63 => {
+                if let Some(msb) = state.cc_msb[num as usize - 32] {
+                    let kind = ParameterNumberKind::Cc14Bit(num - 32);
+                    let value = (msb as u16) << 7 | val as u16;
+                    self.out.push_back(Self::build(&event, kind, value, true));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+impl From<MidiEventBuilder> for FilterParameterNumbers<MidiEventBuilder> {
+    fn from(value: MidiEventBuilder) -> Self {
+        Self::new(value)
+    }
+}
+impl<T: Iterator<Item = MidiEvent<RawMidiMessage>>> Iterator
+    for FilterParameterNumbers<T>
+{
+    type Item = ParameterNumberEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.out.pop_front() {
+                return Some(event);
+            }
+            let item = match self.midi_events.next() {
+                Some(item) => item,
+                None => {
+                    for ch_idx in 0..16 {
+                        self.flush(ch_idx);
+                    }
+                    return self.out.pop_front();
+                }
+            };
+            self.flush_before(item.ppq_position());
+            let raw = item.message().get_raw();
+            if raw.is_empty() {
+                continue;
+            }
+            if let Some(msg) = CCMessage::from_raw(raw) {
+                self.process(MidiEvent::with_new_message(item, msg));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_raw_midi_events, MidiEventConsumer};
+
+    fn cc(pos: u32, ch: u8, num: u8, val: u8) -> MidiEvent<RawMidiMessage> {
+        MidiEvent::new(
+            pos,
+            false,
+            false,
+            CcShapeKind::Square,
+            CCMessage::new(ch, num, val).as_raw_message(),
+        )
+    }
+
+    #[test]
+    fn test_rpn_nrpn() {
+        let events = vec![
+            cc(0, 1, 101, 0),
+            cc(0, 1, 100, 0),
+            cc(0, 1, 6, 12),
+            cc(0, 1, 38, 0),
+            cc(10, 2, 99, 1),
+            cc(10, 2, 98, 2),
+            cc(10, 2, 6, 3),
+            cc(20, 2, 6, 4),
+            cc(30, 1, 101, 127),
+            cc(30, 1, 100, 127),
+            cc(30, 1, 6, 100),
+        ];
+        let params: Vec<_> =
+            FilterParameterNumbers::new(events.into_iter()).collect();
+        assert_eq!(
+            params,
+            vec![
+                ParameterNumberEvent::new(
+                    0,
+                    false,
+                    false,
+                    1,
+                    ParameterNumberKind::PITCH_BEND_SENSITIVITY,
+                    12 << 7,
+                    true
+                ),
+                ParameterNumberEvent::new(
+                    10,
+                    false,
+                    false,
+                    2,
+                    ParameterNumberKind::Nrpn(1 << 7 | 2),
+                    3 << 7,
+                    false
+                ),
+                ParameterNumberEvent::new(
+                    20,
+                    false,
+                    false,
+                    2,
+                    ParameterNumberKind::Nrpn(1 << 7 | 2),
+                    4 << 7,
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_14_bit_cc() {
+        let events = vec![
+            cc(0, 1, 1, 64),
+            cc(0, 3, 7, 100),
+            cc(0, 1, 33, 1),
+            cc(5, 1, 33, 2),
+            cc(10, 1, 74, 2),
+        ];
+        let params: Vec<_> =
+            FilterParameterNumbers::new(events.into_iter()).collect();
+        let values: Vec<_> = params
+            .iter()
+            .map(|p| (p.position_in_ppq, p.channel, p.kind, p.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (0, 1, ParameterNumberKind::Cc14Bit(1), 64 << 7 | 1),
+                (0, 3, ParameterNumberKind::Cc14Bit(7), 100 << 7),
+                (5, 1, ParameterNumberKind::Cc14Bit(1), 64 << 7 | 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let params = vec![
+            ParameterNumberEvent::new(
+                0,
+                true,
+                false,
+                5,
+                ParameterNumberKind::Rpn(0x1234),
+                0x2345,
+                true,
+            ),
+            ParameterNumberEvent::new(
+                10,
+                false,
+                true,
+                16,
+                ParameterNumberKind::Cc14Bit(11),
+                0x3f80,
+                false,
+            ),
+            ParameterNumberEvent::new(
+                20,
+                false,
+                false,
+                1,
+                ParameterNumberKind::Nrpn(0x3fff),
+                0x0001,
+                true,
+            ),
+        ];
+        let raw: Vec<u8> = MidiEventConsumer::new(to_raw_midi_events(
+            flatten_parameter_numbers(params.clone().into_iter()),
+        ))
+        .collect();
+        let back: Vec<_> = MidiEventBuilder::new(raw.into_iter())
+            .filter_parameter_numbers()
+            .collect();
+        assert_eq!(back, params);
+    }
+}
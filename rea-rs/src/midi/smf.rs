@@ -0,0 +1,759 @@
+//! Standard MIDI File (format 0 and 1) reading and writing.
+//!
+//! SMF tracks are represented as the same [MidiEvent] streams, that are
+//! produced by [MidiEventBuilder] and consumed by [MidiEventConsumer], so
+//! file contents can be passed to a take and back without any intermediate
+//! representation. Everything works on plain byte buffers.
+//!
+//! Meta events are kept in the REAPER form: `0xff, type, data...` (without
+//! variable-length size), as REAPER does in the take buffer. SysEx packets
+//! are kept the same way: `0xf0, data...`, and continuation (escape)
+//! packets as `0xf7, data...`. Event flags (selection, mute, CC shape) have
+//! no representation in SMF and are lost on writing.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//! use rea_rs::TimeSignature;
+//!
+//! let notes = vec![
+//!     MidiNoteEvent::new(0, 480, false, false, 1, 60, 100, 0),
+//!     MidiNoteEvent::new(480, 960, false, false, 1, 64, 100, 0),
+//! ];
+//! let mut conductor = SmfTrack::default();
+//! conductor.push_tempo(SmfTempo::new(0, 120.0));
+//! conductor.push_time_signature(SmfTimeSignature::new(
+//!     0,
+//!     TimeSignature::new(3, 4),
+//! ));
+//! let smf = Smf::new(
+//!     SmfFormat::MultiTrack,
+//!     480,
+//!     vec![conductor, SmfTrack::from_notes(notes.clone().into_iter())],
+//! );
+//! let bytes = smf.to_bytes().unwrap();
+//!
+//! let smf = Smf::from_bytes(&bytes).unwrap().rescaled(960);
+//! assert_eq!(smf.tempo_map()[0].bpm, 120.0);
+//! assert_eq!(smf.tracks()[1].notes()[1].start_in_ppq, 960);
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    flatten_midi_notes, CcShapeKind, FilterNotes, MidiEvent, MidiEventBuilder,
+    MidiEventConsumer, MidiMessage, MidiNoteEvent, RawMidiMessage, ReaRsError,
+    ReaperResult, TimeSignature,
+};
+
+/// SMF layout, stored in the `MThd` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SmfFormat {
+    /// Format 0: all channels in one track.
+    SingleTrack,
+    /// Format 1: several simultaneous tracks. The first track is,
+    /// conventionally, the "conductor" track with tempo map.
+    MultiTrack,
+}
+impl SmfFormat {
+    pub fn from_raw(value: u16) -> ReaperResult<Self> {
+        match value {
+            0 => Ok(Self::SingleTrack),
+            1 => Ok(Self::MultiTrack),
+            _ => Err(ReaRsError::InvalidObject(
+                "Only SMF format 0 and 1 are supported",
+            )),
+        }
+    }
+    pub fn to_raw(&self) -> u16 {
+        match self {
+            Self::SingleTrack => 0,
+            Self::MultiTrack => 1,
+        }
+    }
+}
+
+/// Standard MIDI File contents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Smf {
+    format: SmfFormat,
+    ppq: u16,
+    tracks: Vec<SmfTrack>,
+}
+impl Smf {
+    /// `ppq` is amount of ticks per quarter note.
+    pub fn new(format: SmfFormat, ppq: u16, tracks: Vec<SmfTrack>) -> Self {
+        Self {
+            format,
+            ppq,
+            tracks,
+        }
+    }
+
+    /// Parse SMF from raw file contents.
+    ///
+    /// Unknown chunks are skipped. SMPTE-based time division is not
+    /// supported.
+    pub fn from_bytes(buf: &[u8]) -> ReaperResult<Self> {
+        let mut reader = SmfReader::new(buf);
+        if reader.take(4)? != b"MThd" {
+            return Err(ReaRsError::InvalidObject("Not a MIDI file"));
+        }
+        let header_length = reader.u32()? as usize;
+        if header_length < 6 {
+            return Err(ReaRsError::InvalidObject("SMF header is too short"));
+        }
+        let format = SmfFormat::from_raw(reader.u16()?)?;
+        let n_tracks = reader.u16()?;
+        let division = reader.u16()?;
+        if division & 0x8000 != 0 {
+            return Err(ReaRsError::InvalidObject(
+                "SMPTE time division is not supported",
+            ));
+        }
+        reader.take(header_length - 6)?;
+        let mut tracks = Vec::with_capacity(n_tracks as usize);
+        while tracks.len() < n_tracks as usize && !reader.is_empty() {
+            let id = reader.take(4)?;
+            let length = reader.u32()? as usize;
+            let data = reader.take(length)?;
+            if id != b"MTrk" {
+                continue;
+            }
+            tracks.push(SmfTrack::from_chunk(data)?);
+        }
+        Ok(Self {
+            format,
+            ppq: division,
+            tracks,
+        })
+    }
+
+    /// Build SMF file contents.
+    ///
+    /// Returns error if format 0 file has more than one track.
+    pub fn to_bytes(&self) -> ReaperResult<Vec<u8>> {
+        if self.format == SmfFormat::SingleTrack && self.tracks.len() != 1 {
+            return Err(ReaRsError::InvalidObject(
+                "SMF format 0 should have exactly one track",
+            ));
+        }
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"MThd");
+        buf.extend_from_slice(&6_u32.to_be_bytes());
+        buf.extend_from_slice(&self.format.to_raw().to_be_bytes());
+        buf.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&self.ppq.to_be_bytes());
+        for track in self.tracks.iter() {
+            let chunk = track.to_chunk();
+            buf.extend_from_slice(b"MTrk");
+            buf.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            buf.extend(chunk);
+        }
+        Ok(buf)
+    }
+
+    pub fn format(&self) -> SmfFormat {
+        self.format
+    }
+    pub fn set_format(&mut self, format: SmfFormat) {
+        self.format = format;
+    }
+    /// Ticks per quarter note.
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+    pub fn tracks(&self) -> &Vec<SmfTrack> {
+        &self.tracks
+    }
+    pub fn tracks_mut(&mut self) -> &mut Vec<SmfTrack> {
+        &mut self.tracks
+    }
+    pub fn into_tracks(self) -> Vec<SmfTrack> {
+        self.tracks
+    }
+
+    /// Convert all event positions to the new ticks per quarter note.
+    ///
+    /// Take PPQ in REAPER is usually 960.
+    pub fn rescaled(mut self, ppq: u16) -> Self {
+        let from = self.ppq;
+        for track in self.tracks.iter_mut() {
+            for event in track.events.iter_mut() {
+                event.set_ppq_position(rescale_ppq(
+                    event.ppq_position(),
+                    from,
+                    ppq,
+                ));
+            }
+        }
+        self.ppq = ppq;
+        self
+    }
+
+    /// All tempo events of the file, sorted by position.
+    ///
+    /// For format 1 they are expected to be at the first track, but all
+    /// tracks are scanned.
+    pub fn tempo_map(&self) -> Vec<SmfTempo> {
+        let mut tempo: Vec<SmfTempo> =
+            self.tracks.iter().flat_map(|t| t.tempo_events()).collect();
+        tempo.sort_by_key(|t| t.position_in_ppq);
+        tempo
+    }
+
+    /// All time signature events of the file, sorted by position.
+    pub fn time_signatures(&self) -> Vec<SmfTimeSignature> {
+        let mut sigs: Vec<SmfTimeSignature> = self
+            .tracks
+            .iter()
+            .flat_map(|t| t.time_signatures())
+            .collect();
+        sigs.sort_by_key(|t| t.position_in_ppq);
+        sigs
+    }
+
+    /// Merge all tracks into one (format 0) track.
+    pub fn into_single_track(self) -> Self {
+        let mut events: Vec<MidiEvent<RawMidiMessage>> = self
+            .tracks
+            .into_iter()
+            .flat_map(|t| t.events.into_iter())
+            .collect();
+        events.sort_by_key(|e| e.ppq_position());
+        Self {
+            format: SmfFormat::SingleTrack,
+            ppq: self.ppq,
+            tracks: vec![SmfTrack { events }],
+        }
+    }
+}
+
+/// Convert ticks position between two PPQ resolutions, with rounding.
+pub fn rescale_ppq(position: u32, from_ppq: u16, to_ppq: u16) -> u32 {
+    if from_ppq == to_ppq || from_ppq == 0 {
+        return position;
+    }
+    let (from, to) = (from_ppq as u64, to_ppq as u64);
+    ((position as u64 * to + from / 2) / from) as u32
+}
+
+/// One `MTrk` chunk with events at absolute ticks positions.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SmfTrack {
+    events: Vec<MidiEvent<RawMidiMessage>>,
+}
+impl SmfTrack {
+    /// Events are expected to be sorted by position.
+    pub fn new(events: Vec<MidiEvent<RawMidiMessage>>) -> Self {
+        Self { events }
+    }
+
+    /// Build track from raw take MIDI, as got from [crate::Take::get_midi].
+    ///
+    /// CC Bezier data and notation are kept as REAPER meta events.
+    pub fn from_take_midi(buf: Vec<u8>) -> Self {
+        Self {
+            events: MidiEventBuilder::new(buf.into_iter()).collect(),
+        }
+    }
+
+    /// Build track from notes only.
+    pub fn from_notes(notes: impl Iterator<Item = MidiNoteEvent>) -> Self {
+        let mut events: Vec<_> = flatten_midi_notes(notes).collect();
+        events.sort_by_key(|e| e.ppq_position());
+        Self { events }
+    }
+
+    /// Raw take MIDI, that can be passed to [crate::Take::set_midi].
+    pub fn to_take_midi(&self) -> Vec<u8> {
+        MidiEventConsumer::new(self.events.clone().into_iter()).collect()
+    }
+
+    pub fn events(&self) -> &Vec<MidiEvent<RawMidiMessage>> {
+        &self.events
+    }
+    pub fn events_mut(&mut self) -> &mut Vec<MidiEvent<RawMidiMessage>> {
+        &mut self.events
+    }
+    pub fn into_events(self) -> Vec<MidiEvent<RawMidiMessage>> {
+        self.events
+    }
+
+    /// Insert event, keeping events sorted.
+    ///
+    /// Event is placed after all events with the same position.
+    pub fn push(&mut self, event: MidiEvent<RawMidiMessage>) {
+        let idx = self
+            .events
+            .partition_point(|e| e.ppq_position() <= event.ppq_position());
+        self.events.insert(idx, event);
+    }
+    pub fn push_tempo(&mut self, tempo: SmfTempo) {
+        self.push(tempo.to_event())
+    }
+    pub fn push_time_signature(&mut self, signature: SmfTimeSignature) {
+        self.push(signature.to_event())
+    }
+
+    /// Paired note-on and note-off events.
+    pub fn notes(&self) -> Vec<MidiNoteEvent> {
+        FilterNotes::new(self.events.clone().into_iter()).collect()
+    }
+
+    /// Track name meta event (`0xff 0x03`), if any.
+    pub fn name(&self) -> Option<String> {
+        self.events.iter().find_map(|e| {
+            let raw = e.message().borrow_raw();
+            match raw.len() >= 2 && raw[0] == 0xff && raw[1] == 0x03 {
+                true => Some(String::from_utf8_lossy(&raw[2..]).to_string()),
+                false => None,
+            }
+        })
+    }
+    /// Replace or add track name meta event.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.events.retain(|e| {
+            let raw = e.message().borrow_raw();
+            !(raw.len() >= 2 && raw[0] == 0xff && raw[1] == 0x03)
+        });
+        let mut buf = vec![0xff, 0x03];
+        buf.extend(name.into().into_bytes());
+        self.events.insert(0, meta_event(0, buf));
+    }
+
+    pub fn tempo_events(&self) -> Vec<SmfTempo> {
+        self.events
+            .iter()
+            .filter_map(SmfTempo::from_event)
+            .collect()
+    }
+    pub fn time_signatures(&self) -> Vec<SmfTimeSignature> {
+        self.events
+            .iter()
+            .filter_map(SmfTimeSignature::from_event)
+            .collect()
+    }
+
+    fn from_chunk(data: &[u8]) -> ReaperResult<Self> {
+        let mut reader = SmfReader::new(data);
+        let mut events = Vec::new();
+        let mut position: u32 = 0;
+        let mut running_status: Option<u8> = None;
+        while !reader.is_empty() {
+            position = position.checked_add(reader.var_len()?).ok_or(
+                ReaRsError::InvalidObject("SMF event position overflow"),
+            )?;
+            let mut status = reader.u8()?;
+            let buf = match status {
+                0xff => {
+                    let kind = reader.u8()?;
+                    let length = reader.var_len()? as usize;
+                    let data = reader.take(length)?;
+                    if kind == 0x2f {
+                        break;
+                    }
+                    let mut buf = vec![0xff, kind];
+                    buf.extend_from_slice(data);
+                    buf
+                }
+                0xf0 | 0xf7 => {
+                    let length = reader.var_len()? as usize;
+                    let mut buf = vec![status];
+                    buf.extend_from_slice(reader.take(length)?);
+                    buf
+                }
+                _ => {
+                    let mut buf = Vec::with_capacity(3);
+                    if status < 0x80 {
+                        reader.pos -= 1;
+                        status = running_status.ok_or(
+                            ReaRsError::InvalidObject(
+                                "SMF data byte without running status",
+                            ),
+                        )?;
+                    } else {
+                        running_status = Some(status);
+                    }
+                    buf.push(status);
+                    let length = match status & 0xf0 {
+                        0xc0 | 0xd0 => 1,
+                        0xf0 => 0,
+                        _ => 2,
+                    };
+                    buf.extend_from_slice(reader.take(length)?);
+                    buf
+                }
+            };
+            if buf.is_empty() {
+                continue;
+            }
+            events.push(MidiEvent::new(
+                position,
+                false,
+                false,
+                CcShapeKind::Square,
+                RawMidiMessage::from_raw(buf).expect("always Some"),
+            ));
+        }
+        Ok(Self { events })
+    }
+
+    fn to_chunk(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut last_position = 0;
+        let mut events: Vec<&MidiEvent<RawMidiMessage>> =
+            self.events.iter().collect();
+        events.sort_by_key(|e| e.ppq_position());
+        for event in events {
+            let raw = event.message().borrow_raw();
+            if raw.is_empty() {
+                continue;
+            }
+            // End of track is always written once at the end, and a single
+            // 0xff (reset) has no SMF representation.
+            if raw[0] == 0xff && (raw.len() < 2 || raw[1] == 0x2f) {
+                continue;
+            }
+            write_var_len(&mut buf, event.ppq_position() - last_position);
+            last_position = event.ppq_position();
+            match raw[0] {
+                0xff => {
+                    buf.extend_from_slice(&raw[..2]);
+                    write_var_len(&mut buf, raw.len() as u32 - 2);
+                    buf.extend_from_slice(&raw[2..]);
+                }
+                0xf0 | 0xf7 => {
+                    buf.push(raw[0]);
+                    write_var_len(&mut buf, raw.len() as u32 - 1);
+                    buf.extend_from_slice(&raw[1..]);
+                }
+                _ => buf.extend_from_slice(raw),
+            }
+        }
+        buf.extend_from_slice(&[0, 0xff, 0x2f, 0]);
+        buf
+    }
+}
+
+fn meta_event(position: u32, buf: Vec<u8>) -> MidiEvent<RawMidiMessage> {
+    MidiEvent::new(
+        position,
+        false,
+        false,
+        CcShapeKind::Square,
+        RawMidiMessage::from_raw(buf).expect("always Some"),
+    )
+}
+
+/// Set Tempo meta event (`0xff 0x51`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SmfTempo {
+    pub position_in_ppq: u32,
+    pub bpm: f64,
+}
+impl SmfTempo {
+    pub fn new(position_in_ppq: u32, bpm: f64) -> Self {
+        Self {
+            position_in_ppq,
+            bpm,
+        }
+    }
+    /// Microseconds per quarter note, as stored in file.
+    pub fn micros_per_quarter(&self) -> u32 {
+        (60_000_000.0 / self.bpm).round() as u32
+    }
+    pub fn from_event(event: &MidiEvent<RawMidiMessage>) -> Option<Self> {
+        let raw = event.message().borrow_raw();
+        if raw.len() != 5 || raw[0] != 0xff || raw[1] != 0x51 {
+            return None;
+        }
+        let micros = u32::from_be_bytes([0, raw[2], raw[3], raw[4]]);
+        if micros == 0 {
+            return None;
+        }
+        Some(Self::new(
+            event.ppq_position(),
+            60_000_000.0 / micros as f64,
+        ))
+    }
+    pub fn to_event(&self) -> MidiEvent<RawMidiMessage> {
+        let micros = self.micros_per_quarter().to_be_bytes();
+        meta_event(
+            self.position_in_ppq,
+            vec![0xff, 0x51, micros[1], micros[2], micros[3]],
+        )
+    }
+}
+
+/// Time Signature meta event (`0xff 0x58`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmfTimeSignature {
+    pub position_in_ppq: u32,
+    pub time_signature: TimeSignature,
+    /// MIDI clocks per metronome click. Usually, 24.
+    pub clocks_per_click: u8,
+    /// Notated 32nd notes per quarter. Usually, 8.
+    pub thirty_seconds_per_quarter: u8,
+}
+impl SmfTimeSignature {
+    pub fn new(position_in_ppq: u32, time_signature: TimeSignature) -> Self {
+        Self {
+            position_in_ppq,
+            time_signature,
+            clocks_per_click: 24,
+            thirty_seconds_per_quarter: 8,
+        }
+    }
+    pub fn from_event(event: &MidiEvent<RawMidiMessage>) -> Option<Self> {
+        let raw = event.message().borrow_raw();
+        if raw.len() != 6 || raw[0] != 0xff || raw[1] != 0x58 {
+            return None;
+        }
+        Some(Self {
+            position_in_ppq: event.ppq_position(),
+            time_signature: TimeSignature::new(
+                raw[2] as u32,
+                1_u32.checked_shl(raw[3] as u32)?,
+            ),
+            clocks_per_click: raw[4],
+            thirty_seconds_per_quarter: raw[5],
+        })
+    }
+    pub fn to_event(&self) -> MidiEvent<RawMidiMessage> {
+        let denominator =
+            self.time_signature.denominator.max(1).trailing_zeros();
+        meta_event(
+            self.position_in_ppq,
+            vec![
+                0xff,
+                0x58,
+                self.time_signature.numerator as u8,
+                denominator as u8,
+                self.clocks_per_click,
+                self.thirty_seconds_per_quarter,
+            ],
+        )
+    }
+}
+
+fn write_var_len(buf: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.extend(bytes.into_iter().rev());
+}
+
+struct SmfReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+impl<'a> SmfReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+    fn take(&mut self, length: usize) -> ReaperResult<&'a [u8]> {
+        if self.pos + length > self.buf.len() {
+            return Err(ReaRsError::InvalidObject(
+                "MIDI file unexpectedly ended",
+            ));
+        }
+        let slice = &self.buf[self.pos..self.pos + length];
+        self.pos += length;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> ReaperResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> ReaperResult<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+    fn u32(&mut self) -> ReaperResult<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn var_len(&mut self) -> ReaperResult<u32> {
+        let mut value: u32 = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReaRsError::InvalidObject(
+            "Variable-length quantity is too long",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_len() {
+        for value in [0, 0x40, 0x7f, 0x80, 0x2000, 0x3fff, 0x4000, 0x0fffffff]
+        {
+            let mut buf = Vec::new();
+            write_var_len(&mut buf, value);
+            assert_eq!(SmfReader::new(&buf).var_len().unwrap(), value);
+        }
+        let mut buf = Vec::new();
+        write_var_len(&mut buf, 0x4000);
+        assert_eq!(buf, vec![0x81, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn test_read_running_status() {
+        let track = [
+            0x00, 0xff, 0x03, 0x03, b'a', b'b', b'c', //
+            0x00, 0x90, 60, 100, //
+            0x00, 64, 100, // running status
+            0x60, 0x80, 60, 0, //
+            0x00, 64, 0, //
+            0x00, 0xf0, 0x03, 0x7e, 0x09, 0xf7, //
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let mut buf = b"MThd".to_vec();
+        buf.extend([0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        buf.extend(b"MTrk");
+        buf.extend((track.len() as u32).to_be_bytes());
+        buf.extend(track);
+        let smf = Smf::from_bytes(&buf).unwrap();
+        assert_eq!(smf.format(), SmfFormat::SingleTrack);
+        assert_eq!(smf.ppq(), 96);
+        let track = &smf.tracks()[0];
+        assert_eq!(track.name(), Some("abc".to_string()));
+        let notes = track.notes();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[1].note, 64);
+        assert_eq!(notes[1].end_in_ppq, 96);
+        let sysex = track.events().last().unwrap();
+        assert_eq!(
+            sysex.message().borrow_raw(),
+            &vec![0xf0, 0x7e, 0x09, 0xf7]
+        );
+        // Written back without running status, but with the same contents.
+        let smf = Smf::from_bytes(&smf.to_bytes().unwrap()).unwrap();
+        assert_eq!(&smf.tracks()[0].notes(), &notes);
+    }
+
+    #[test]
+    fn test_sysex_packets() {
+        let track = [
+            0x00, 0xf0, 0x02, 0x43, 0x10, // first packet
+            0x10, 0xf7, 0x02, 0x20, 0xf7, // continuation
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let mut buf = b"MThd".to_vec();
+        buf.extend([0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        buf.extend(b"MTrk");
+        buf.extend((track.len() as u32).to_be_bytes());
+        buf.extend(track);
+        let smf = Smf::from_bytes(&buf).unwrap();
+        let events = smf.tracks()[0].events();
+        assert_eq!(events[1].message().borrow_raw(), &vec![0xf7, 0x20, 0xf7]);
+        assert_eq!(smf.to_bytes().unwrap(), buf);
+
+        // single 0xff is skipped on writing.
+        let mut track = smf.tracks()[0].clone();
+        track.push(MidiEvent::new(
+            20,
+            false,
+            false,
+            CcShapeKind::Square,
+            RawMidiMessage::from_raw(vec![0xff]).unwrap(),
+        ));
+        let smf = Smf::new(SmfFormat::SingleTrack, 96, vec![track]);
+        assert_eq!(smf.to_bytes().unwrap(), buf);
+    }
+
+    #[test]
+    fn test_round_trip_and_rescale() {
+        let mut conductor = SmfTrack::default();
+        conductor.set_name("tempo");
+        conductor.push_tempo(SmfTempo::new(0, 120.0));
+        conductor.push_tempo(SmfTempo::new(1920, 60.0));
+        conductor.push_time_signature(SmfTimeSignature::new(
+            0,
+            TimeSignature::new(7, 8),
+        ));
+        let notes = vec![
+            MidiNoteEvent::new(0, 240, false, false, 1, 60, 90, 0),
+            MidiNoteEvent::new(240, 480, false, false, 10, 36, 127, 64),
+        ];
+        let smf = Smf::new(
+            SmfFormat::MultiTrack,
+            480,
+            vec![conductor, SmfTrack::from_notes(notes.into_iter())],
+        );
+        let bytes = smf.to_bytes().unwrap();
+        let read = Smf::from_bytes(&bytes).unwrap();
+        assert_eq!(read, smf);
+        assert_eq!(read.tracks()[0].name(), Some("tempo".to_string()));
+
+        let read = read.rescaled(960);
+        let tempo = read.tempo_map();
+        assert_eq!(
+            tempo,
+            vec![SmfTempo::new(0, 120.0), SmfTempo::new(3840, 60.0)]
+        );
+        assert_eq!(
+            read.time_signatures()[0].time_signature,
+            TimeSignature::new(7, 8)
+        );
+        let notes = read.tracks()[1].notes();
+        assert_eq!(notes[1].start_in_ppq, 480);
+        assert_eq!(notes[1].end_in_ppq, 960);
+        assert_eq!(notes[1].channel, 10);
+        assert_eq!(notes[1].off_velocity, 64);
+
+        let single = read.into_single_track();
+        assert_eq!(single.tracks().len(), 1);
+        assert!(single.to_bytes().is_ok());
+    }
+
+    #[test]
+    fn test_take_midi_round_trip() {
+        let take_buf = vec![
+            30, 0, 0, 0, 0, 3, 0, 0, 0, 144, 61, 96, //
+            80, 0, 0, 0, 0, 3, 0, 0, 0, 128, 61, 0, //
+            10, 0, 0, 0, 0, 8, 0, 0, 0, 255, 1, 109, 121, 116, 101, 120, 116,
+        ];
+        let track = SmfTrack::from_take_midi(take_buf.clone());
+        let smf = Smf::new(SmfFormat::SingleTrack, 960, vec![track]);
+        let smf = Smf::from_bytes(&smf.to_bytes().unwrap()).unwrap();
+        assert_eq!(smf.tracks()[0].to_take_midi(), take_buf);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Smf::from_bytes(b"RIFF").is_err());
+        assert!(Smf::from_bytes(b"MThd\x00\x00\x00\x06\x00\x02").is_err());
+        let smf = Smf::new(SmfFormat::SingleTrack, 960, vec![]);
+        assert!(smf.to_bytes().is_err());
+        // Position does not fit u32.
+        let mut track = Vec::new();
+        for _ in 0..17 {
+            track.extend([0xff, 0xff, 0xff, 0x7f, 0x90, 60, 100]);
+        }
+        let mut buf = b"MThd".to_vec();
+        buf.extend([0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        buf.extend(b"MTrk");
+        buf.extend((track.len() as u32).to_be_bytes());
+        buf.extend(track);
+        assert!(matches!(
+            Smf::from_bytes(&buf),
+            Err(ReaRsError::InvalidObject("SMF event position overflow"))
+        ));
+    }
+}
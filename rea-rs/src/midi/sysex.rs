@@ -0,0 +1,729 @@
+//! Typed decoding of System Exclusive messages.
+//!
+//! [SysExMessage] recognizes universal real-time and non-real-time messages
+//! (device inquiry, General MIDI modes, sample dump header, MIDI Machine
+//! Control, MTC full frame, master volume) and manufacturer-specific
+//! messages with one- or three-byte [ManufacturerId]. Everything else is
+//! kept in raw form, so [SysExMessage::to_bytes] always gives back the
+//! original bytes.
+//!
+//! [pack_7bit] and [unpack_7bit] convert 8-bit payloads to the 7-bit form,
+//! used by most of vendor dumps.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let msg = AllSysMessage::from_raw(vec![
+//!     0xf0, 0x7f, 0x7f, 0x04, 0x01, 0x00, 0x40, 0xf7,
+//! ])
+//! .unwrap();
+//! assert_eq!(
+//!     msg.sysex(),
+//!     Some(SysExMessage::MasterVolume {
+//!         device_id: ALL_CALL_DEVICE_ID,
+//!         volume: 0x2000
+//!     })
+//! );
+//!
+//! let inquiry: AllSysMessage =
+//!     SysExMessage::DeviceInquiryRequest { device_id: 0x10 }.into();
+//! assert_eq!(
+//!     inquiry.get_raw(),
+//!     vec![0xf0, 0x7e, 0x10, 0x06, 0x01, 0xf7]
+//! );
+//!
+//! let payload = vec![0xff, 0x00, 0x80, 0x7f];
+//! let packed = pack_7bit(&payload);
+//! assert!(packed.iter().all(|b| *b < 0x80));
+//! assert_eq!(unpack_7bit(&packed), payload);
+//! ```
+
+use int_enum::IntEnum;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::AllSysMessage;
+
+/// Device ID, addressing all devices.
+pub const ALL_CALL_DEVICE_ID: u8 = 0x7f;
+
+const SYSEX_START: u8 = 0xf0;
+const SYSEX_END: u8 = 0xf7;
+const NON_REAL_TIME: u8 = 0x7e;
+const REAL_TIME: u8 = 0x7f;
+
+/// Manufacturer ID of SysEx message.
+///
+/// Three-byte IDs start with `0x00` in the message, which is not stored in
+/// [ManufacturerId::Extended].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ManufacturerId {
+    Single(u8),
+    Extended(u8, u8),
+}
+impl ManufacturerId {
+    pub const SEQUENTIAL: Self = Self::Single(0x01);
+    pub const MOOG: Self = Self::Single(0x04);
+    pub const ROLAND: Self = Self::Single(0x41);
+    pub const KORG: Self = Self::Single(0x42);
+    pub const YAMAHA: Self = Self::Single(0x43);
+    pub const NON_COMMERCIAL: Self = Self::Single(0x7d);
+    pub const NATIVE_INSTRUMENTS: Self = Self::Extended(0x21, 0x09);
+    pub const ABLETON: Self = Self::Extended(0x21, 0x1d);
+
+    /// Read ID from the start of buffer.
+    ///
+    /// Returns ID and number of bytes it took.
+    pub fn from_bytes(buf: &[u8]) -> Option<(Self, usize)> {
+        match buf {
+            [0x00, b1, b2, ..] if *b1 < 0x80 && *b2 < 0x80 => {
+                Some((Self::Extended(*b1, *b2), 3))
+            }
+            [0x00, ..] => None,
+            [b, ..] if *b < 0x80 => Some((Self::Single(*b), 1)),
+            _ => None,
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Single(b) => vec![*b],
+            Self::Extended(b1, b2) => vec![0x00, *b1, *b2],
+        }
+    }
+    /// Universal real-time or non-real-time ID.
+    pub fn is_universal(&self) -> bool {
+        matches!(self, Self::Single(NON_REAL_TIME | REAL_TIME))
+    }
+}
+
+/// Modes of General MIDI, set by universal non-real-time message.
+#[repr(u8)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, IntEnum, Serialize, Deserialize,
+)]
+pub enum GeneralMidiMode {
+    Gm1On = 0x01,
+    Off = 0x02,
+    Gm2On = 0x03,
+}
+
+/// Frame rate, encoded in hours byte of MTC full frame.
+#[repr(u8)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, IntEnum, Serialize, Deserialize,
+)]
+pub enum MtcFrameRate {
+    Fps24 = 0,
+    Fps25 = 1,
+    Fps2997Drop = 2,
+    Fps30 = 3,
+}
+
+/// Time of MTC full frame or MMC locate.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
+pub struct SysExTime {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub subframes: u8,
+}
+impl SysExTime {
+    pub fn new(
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+        subframes: u8,
+    ) -> Self {
+        Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            subframes,
+        }
+    }
+}
+
+/// MIDI Machine Control command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    RecordStrobe,
+    RecordExit,
+    RecordPause,
+    Pause,
+    Eject,
+    Chase,
+    Reset,
+    /// Locate to time. Hours byte keeps frame rate bits, as in MTC.
+    Locate(MtcFrameRate, SysExTime),
+    /// Any other command with its data bytes.
+    Other(u8, Vec<u8>),
+}
+impl MmcCommand {
+    fn from_bytes(buf: &[u8]) -> Option<Self> {
+        let cmd = match buf {
+            [0x01] => Self::Stop,
+            [0x02] => Self::Play,
+            [0x03] => Self::DeferredPlay,
+            [0x04] => Self::FastForward,
+            [0x05] => Self::Rewind,
+            [0x06] => Self::RecordStrobe,
+            [0x07] => Self::RecordExit,
+            [0x08] => Self::RecordPause,
+            [0x09] => Self::Pause,
+            [0x0a] => Self::Eject,
+            [0x0b] => Self::Chase,
+            [0x0d] => Self::Reset,
+            [0x44, 0x06, 0x01, hr, mn, sc, fr, ff] => {
+                let (rate, time) = decode_time(*hr, *mn, *sc, *fr, *ff)?;
+                Self::Locate(rate, time)
+            }
+            [cmd, data @ ..] => Self::Other(*cmd, data.to_vec()),
+            [] => return None,
+        };
+        Some(cmd)
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Stop => vec![0x01],
+            Self::Play => vec![0x02],
+            Self::DeferredPlay => vec![0x03],
+            Self::FastForward => vec![0x04],
+            Self::Rewind => vec![0x05],
+            Self::RecordStrobe => vec![0x06],
+            Self::RecordExit => vec![0x07],
+            Self::RecordPause => vec![0x08],
+            Self::Pause => vec![0x09],
+            Self::Eject => vec![0x0a],
+            Self::Chase => vec![0x0b],
+            Self::Reset => vec![0x0d],
+            Self::Locate(rate, time) => {
+                let mut buf = vec![0x44, 0x06, 0x01];
+                buf.extend(encode_time(*rate, time));
+                buf.push(time.subframes);
+                buf
+            }
+            Self::Other(cmd, data) => {
+                let mut buf = vec![*cmd];
+                buf.extend(data);
+                buf
+            }
+        }
+    }
+}
+
+/// Header of MIDI Sample Dump Standard.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
+pub struct SampleDumpHeader {
+    /// 14-bit sample number.
+    pub sample_number: u16,
+    /// Significant bits per sample, 8–28.
+    pub sample_format: u8,
+    /// Sample period in nanoseconds, 21-bit.
+    pub sample_period: u32,
+    /// Sample length in words, 21-bit.
+    pub length: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    /// 0 — forward, 1 — alternating, 0x7f — loop off.
+    pub loop_type: u8,
+}
+
+/// Decoded System Exclusive message.
+///
+/// Built by [SysExMessage::from_bytes] from the full message, including
+/// `0xf0` and `0xf7`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SysExMessage {
+    DeviceInquiryRequest {
+        device_id: u8,
+    },
+    DeviceInquiryReply {
+        device_id: u8,
+        manufacturer: ManufacturerId,
+        family: u16,
+        member: u16,
+        version: [u8; 4],
+    },
+    GeneralMidi {
+        device_id: u8,
+        mode: GeneralMidiMode,
+    },
+    SampleDumpHeader {
+        device_id: u8,
+        header: SampleDumpHeader,
+    },
+    Mmc {
+        device_id: u8,
+        command: MmcCommand,
+    },
+    MtcFullFrame {
+        device_id: u8,
+        rate: MtcFrameRate,
+        time: SysExTime,
+    },
+    /// 14-bit master volume.
+    MasterVolume {
+        device_id: u8,
+        volume: u16,
+    },
+    /// Universal message, which is not decoded further.
+    Universal {
+        real_time: bool,
+        device_id: u8,
+        sub_id1: u8,
+        data: Vec<u8>,
+    },
+    /// Manufacturer-specific message with its payload.
+    Manufacturer {
+        id: ManufacturerId,
+        data: Vec<u8>,
+    },
+    /// Anything, that is not a well-formed SysEx message.
+    Unknown(Vec<u8>),
+}
+impl SysExMessage {
+    /// Decode full message, starting from `0xf0` and ending by `0xf7`.
+    ///
+    /// Never fails: malformed messages become [SysExMessage::Unknown].
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        Self::decode(buf).unwrap_or_else(|| Self::Unknown(buf.to_vec()))
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let body = match buf {
+            [SYSEX_START, body @ .., SYSEX_END] => body,
+            _ => return None,
+        };
+        if body.iter().any(|b| *b >= 0x80) {
+            return None;
+        }
+        let (id, id_len) = ManufacturerId::from_bytes(body)?;
+        let data = &body[id_len..];
+        if !id.is_universal() {
+            return Some(Self::Manufacturer {
+                id,
+                data: data.to_vec(),
+            });
+        }
+        let real_time = id == ManufacturerId::Single(REAL_TIME);
+        let (device_id, sub_id1, rest) = match data {
+            [device_id, sub_id1, rest @ ..] => (*device_id, *sub_id1, rest),
+            _ => return None,
+        };
+        let typed = match real_time {
+            false => decode_non_real_time(device_id, sub_id1, rest),
+            true => decode_real_time(device_id, sub_id1, rest),
+        };
+        Some(typed.unwrap_or_else(|| Self::Universal {
+            real_time,
+            device_id,
+            sub_id1,
+            data: rest.to_vec(),
+        }))
+    }
+
+    /// Encode message, including `0xf0` and `0xf7`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let non_rt = ManufacturerId::Single(NON_REAL_TIME);
+        let rt = ManufacturerId::Single(REAL_TIME);
+        let (id, body) = match self {
+            Self::Unknown(buf) => return buf.clone(),
+            Self::Manufacturer { id, data } => (*id, data.clone()),
+            Self::Universal {
+                real_time,
+                device_id,
+                sub_id1,
+                data,
+            } => {
+                let id = if *real_time { REAL_TIME } else { NON_REAL_TIME };
+                let mut body = vec![*device_id, *sub_id1];
+                body.extend(data);
+                (ManufacturerId::Single(id), body)
+            }
+            Self::DeviceInquiryRequest { device_id } => {
+                (non_rt, vec![*device_id, 0x06, 0x01])
+            }
+            Self::DeviceInquiryReply {
+                device_id,
+                manufacturer,
+                family,
+                member,
+                version,
+            } => {
+                let mut body = vec![*device_id, 0x06, 0x02];
+                body.extend(manufacturer.to_bytes());
+                body.extend(split_7bit(*family as u32, 2));
+                body.extend(split_7bit(*member as u32, 2));
+                body.extend(version);
+                (non_rt, body)
+            }
+            Self::GeneralMidi { device_id, mode } => {
+                (non_rt, vec![*device_id, 0x09, mode.int_value()])
+            }
+            Self::SampleDumpHeader { device_id, header } => {
+                let mut body = vec![*device_id, 0x01];
+                body.extend(split_7bit(header.sample_number as u32, 2));
+                body.push(header.sample_format);
+                body.extend(split_7bit(header.sample_period, 3));
+                body.extend(split_7bit(header.length, 3));
+                body.extend(split_7bit(header.loop_start, 3));
+                body.extend(split_7bit(header.loop_end, 3));
+                body.push(header.loop_type);
+                (non_rt, body)
+            }
+            Self::Mmc { device_id, command } => {
+                let mut body = vec![*device_id, 0x06];
+                body.extend(command.to_bytes());
+                (rt, body)
+            }
+            Self::MtcFullFrame {
+                device_id,
+                rate,
+                time,
+            } => {
+                let mut body = vec![*device_id, 0x01, 0x01];
+                body.extend(encode_time(*rate, time));
+                (rt, body)
+            }
+            Self::MasterVolume { device_id, volume } => {
+                let mut body = vec![*device_id, 0x04, 0x01];
+                body.extend(split_7bit(*volume as u32, 2));
+                (rt, body)
+            }
+        };
+        let mut buf = vec![SYSEX_START];
+        buf.extend(id.to_bytes());
+        buf.extend(body);
+        buf.push(SYSEX_END);
+        buf
+    }
+
+    /// Device ID of universal message.
+    pub fn device_id(&self) -> Option<u8> {
+        match self {
+            Self::DeviceInquiryRequest { device_id }
+            | Self::DeviceInquiryReply { device_id, .. }
+            | Self::GeneralMidi { device_id, .. }
+            | Self::SampleDumpHeader { device_id, .. }
+            | Self::Mmc { device_id, .. }
+            | Self::MtcFullFrame { device_id, .. }
+            | Self::MasterVolume { device_id, .. }
+            | Self::Universal { device_id, .. } => Some(*device_id),
+            Self::Manufacturer { .. } | Self::Unknown(_) => None,
+        }
+    }
+
+    /// Manufacturer ID, including universal IDs.
+    pub fn manufacturer_id(&self) -> Option<ManufacturerId> {
+        match self {
+            Self::Manufacturer { id, .. } => Some(*id),
+            Self::Unknown(buf) => {
+                ManufacturerId::from_bytes(buf.get(1..)?).map(|(id, _)| id)
+            }
+            Self::Universal {
+                real_time: false, ..
+            }
+            | Self::DeviceInquiryRequest { .. }
+            | Self::DeviceInquiryReply { .. }
+            | Self::GeneralMidi { .. }
+            | Self::SampleDumpHeader { .. } => {
+                Some(ManufacturerId::Single(NON_REAL_TIME))
+            }
+            Self::Universal { .. }
+            | Self::Mmc { .. }
+            | Self::MtcFullFrame { .. }
+            | Self::MasterVolume { .. } => {
+                Some(ManufacturerId::Single(REAL_TIME))
+            }
+        }
+    }
+}
+
+fn decode_non_real_time(
+    device_id: u8,
+    sub_id1: u8,
+    rest: &[u8],
+) -> Option<SysExMessage> {
+    let msg = match (sub_id1, rest) {
+        (0x06, [0x01]) => SysExMessage::DeviceInquiryRequest { device_id },
+        (0x06, [0x02, reply @ ..]) => {
+            let (manufacturer, id_len) = ManufacturerId::from_bytes(reply)?;
+            let ids = match &reply[id_len..] {
+                ids @ [_, _, _, _, _, _, _, _] => ids,
+                _ => return None,
+            };
+            SysExMessage::DeviceInquiryReply {
+                device_id,
+                manufacturer,
+                family: join_7bit(&ids[0..2]) as u16,
+                member: join_7bit(&ids[2..4]) as u16,
+                version: [ids[4], ids[5], ids[6], ids[7]],
+            }
+        }
+        (0x09, [mode]) => SysExMessage::GeneralMidi {
+            device_id,
+            mode: GeneralMidiMode::from_int(*mode).ok()?,
+        },
+        (0x01, header) if header.len() == 16 => {
+            SysExMessage::SampleDumpHeader {
+                device_id,
+                header: SampleDumpHeader {
+                    sample_number: join_7bit(&header[0..2]) as u16,
+                    sample_format: header[2],
+                    sample_period: join_7bit(&header[3..6]),
+                    length: join_7bit(&header[6..9]),
+                    loop_start: join_7bit(&header[9..12]),
+                    loop_end: join_7bit(&header[12..15]),
+                    loop_type: header[15],
+                },
+            }
+        }
+        _ => return None,
+    };
+    Some(msg)
+}
+
+fn decode_real_time(
+    device_id: u8,
+    sub_id1: u8,
+    rest: &[u8],
+) -> Option<SysExMessage> {
+    let msg = match (sub_id1, rest) {
+        (0x01, [0x01, hr, mn, sc, fr]) => {
+            let (rate, time) = decode_time(*hr, *mn, *sc, *fr, 0)?;
+            SysExMessage::MtcFullFrame {
+                device_id,
+                rate,
+                time,
+            }
+        }
+        (0x04, [0x01, lsb, msb]) => SysExMessage::MasterVolume {
+            device_id,
+            volume: join_7bit(&[*lsb, *msb]) as u16,
+        },
+        (0x06, command) => SysExMessage::Mmc {
+            device_id,
+            command: MmcCommand::from_bytes(command)?,
+        },
+        _ => return None,
+    };
+    Some(msg)
+}
+
+fn decode_time(
+    hr: u8,
+    mn: u8,
+    sc: u8,
+    fr: u8,
+    ff: u8,
+) -> Option<(MtcFrameRate, SysExTime)> {
+    let rate = MtcFrameRate::from_int((hr >> 5) & 0b11).ok()?;
+    Some((rate, SysExTime::new(hr & 0x1f, mn, sc, fr, ff)))
+}
+
+fn encode_time(rate: MtcFrameRate, time: &SysExTime) -> [u8; 4] {
+    [
+        (rate.int_value() << 5) | (time.hours & 0x1f),
+        time.minutes & 0x7f,
+        time.seconds & 0x7f,
+        time.frames & 0x7f,
+    ]
+}
+
+/// Split value into `n_bytes` 7-bit bytes, LSB first.
+fn split_7bit(value: u32, n_bytes: usize) -> Vec<u8> {
+    (0..n_bytes)
+        .map(|idx| ((value >> (7 * idx)) & 0x7f) as u8)
+        .collect()
+}
+
+/// Join 7-bit bytes, LSB first.
+fn join_7bit(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (idx, b)| acc | ((*b as u32 & 0x7f) << (7 * idx)))
+}
+
+/// Pack 8-bit data into 7-bit SysEx payload.
+///
+/// Every 7 bytes become 8: the first one keeps the high bits of the
+/// following bytes (bit 0 for the first byte, bit 1 for the second etc.).
+/// The last group may be shorter.
+pub fn pack_7bit(data: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::with_capacity(data.len() + data.len() / 7 + 1);
+    for chunk in data.chunks(7) {
+        let high_bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (idx, b)| acc | ((b >> 7) << idx));
+        packed.push(high_bits);
+        packed.extend(chunk.iter().map(|b| b & 0x7f));
+    }
+    packed
+}
+
+/// Unpack 7-bit SysEx payload, produced by [pack_7bit].
+pub fn unpack_7bit(packed: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(packed.len() / 8 * 7 + 7);
+    for chunk in packed.chunks(8) {
+        let high_bits = chunk[0];
+        data.extend(
+            chunk[1..]
+                .iter()
+                .enumerate()
+                .map(|(idx, b)| (b & 0x7f) | (((high_bits >> idx) & 1) << 7)),
+        );
+    }
+    data
+}
+
+impl AllSysMessage {
+    /// Decode as SysEx message, if it starts from `0xf0`.
+    ///
+    /// Meta-events (`0xff`) give `None`.
+    pub fn sysex(&self) -> Option<SysExMessage> {
+        match self.buf.first() {
+            Some(&SYSEX_START) => Some(SysExMessage::from_bytes(&self.buf)),
+            _ => None,
+        }
+    }
+}
+impl From<SysExMessage> for AllSysMessage {
+    fn from(value: SysExMessage) -> Self {
+        Self {
+            buf: value.to_bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MidiMessage;
+
+    #[test]
+    fn test_universal_round_trip() {
+        let messages = vec![
+            SysExMessage::DeviceInquiryRequest { device_id: 0x7f },
+            SysExMessage::DeviceInquiryReply {
+                device_id: 0x10,
+                manufacturer: ManufacturerId::NATIVE_INSTRUMENTS,
+                family: 0x1234,
+                member: 0x0102,
+                version: [1, 2, 3, 4],
+            },
+            SysExMessage::GeneralMidi {
+                device_id: 0x7f,
+                mode: GeneralMidiMode::Gm2On,
+            },
+            SysExMessage::SampleDumpHeader {
+                device_id: 0,
+                header: SampleDumpHeader {
+                    sample_number: 300,
+                    sample_format: 16,
+                    sample_period: 22675,
+                    length: 1_000_000,
+                    loop_start: 10,
+                    loop_end: 999_999,
+                    loop_type: 0x7f,
+                },
+            },
+            SysExMessage::Mmc {
+                device_id: 0x7f,
+                command: MmcCommand::Locate(
+                    MtcFrameRate::Fps25,
+                    SysExTime::new(1, 2, 3, 4, 5),
+                ),
+            },
+            SysExMessage::Mmc {
+                device_id: 0x7f,
+                command: MmcCommand::Other(0x40, vec![0x01, 0x02]),
+            },
+            SysExMessage::MasterVolume {
+                device_id: 0x7f,
+                volume: 0x3fff,
+            },
+            SysExMessage::Universal {
+                real_time: false,
+                device_id: 0x7f,
+                sub_id1: 0x7c,
+                data: vec![1, 2, 3],
+            },
+        ];
+        for msg in messages {
+            let bytes = msg.to_bytes();
+            assert_eq!(bytes[0], 0xf0);
+            assert_eq!(*bytes.last().unwrap(), 0xf7);
+            assert_eq!(SysExMessage::from_bytes(&bytes), msg);
+        }
+    }
+
+    #[test]
+    fn test_mtc_full_frame() {
+        let bytes =
+            [0xf0, 0x7f, 0x7f, 0x01, 0x01, 0x61, 0x3b, 0x3b, 0x1d, 0xf7];
+        let msg = SysExMessage::from_bytes(&bytes);
+        assert_eq!(
+            msg,
+            SysExMessage::MtcFullFrame {
+                device_id: 0x7f,
+                rate: MtcFrameRate::Fps30,
+                time: SysExTime::new(1, 59, 59, 29, 0),
+            }
+        );
+        assert_eq!(msg.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_manufacturer_and_unknown() {
+        let roland = [0xf0, 0x41, 0x10, 0x42, 0x12, 0x40, 0xf7];
+        let msg = SysExMessage::from_bytes(&roland);
+        assert_eq!(msg.manufacturer_id(), Some(ManufacturerId::ROLAND));
+        assert_eq!(msg.device_id(), None);
+        assert_eq!(msg.to_bytes(), roland);
+
+        let ableton = [0xf0, 0x00, 0x21, 0x1d, 0x01, 0xf7];
+        assert_eq!(
+            SysExMessage::from_bytes(&ableton),
+            SysExMessage::Manufacturer {
+                id: ManufacturerId::ABLETON,
+                data: vec![0x01]
+            }
+        );
+
+        // Truncated and with 8-bit data byte.
+        for bytes in [vec![0xf0, 0x00, 0x21], vec![0xf0, 0x43, 0x80, 0xf7]] {
+            let msg = SysExMessage::from_bytes(&bytes);
+            assert_eq!(msg, SysExMessage::Unknown(bytes.clone()));
+            assert_eq!(msg.to_bytes(), bytes);
+        }
+
+        let meta = AllSysMessage::from_raw(vec![0xff, 0x01, 0x41]).unwrap();
+        assert_eq!(meta.sysex(), None);
+    }
+
+    #[test]
+    fn test_seven_bit_packing() {
+        let data: Vec<u8> = (0..=255).collect();
+        let packed = pack_7bit(&data);
+        assert_eq!(packed.len(), 256 / 7 * 8 + 1 + 256 % 7);
+        assert!(packed.iter().all(|b| *b < 0x80));
+        assert_eq!(unpack_7bit(&packed), data);
+        assert_eq!(pack_7bit(&[0x80, 0x01]), vec![0b01, 0x00, 0x01]);
+        assert!(pack_7bit(&[]).is_empty());
+    }
+}
@@ -0,0 +1,635 @@
+//! Transactional editing of the whole take MIDI.
+//!
+//! [MidiTakeEvents] splits raw take MIDI buffer into typed collections and
+//! builds sorted buffer back. [MidiTakeEditor] binds it to the
+//! [Take] and writes changes back on [MidiTakeEditor::commit] or on drop.
+//!
+//! Events, that can not be classified (orphan note-on\off, meta-events
+//! without typed representation, stray Bezier data, invalid UTF-8 text etc.)
+//! are kept in [MidiTakeEvents::unknown] and written back byte-for-byte.
+//!
+//! # Note
+//!
+//! Note-on with zero velocity is treated as note-off and is written back as
+//! `0x80` message.
+//!
+//! Events, placed at the same position are written in the fixed order:
+//! note-offs, program changes, CC, pitch bend, channel pressure,
+//! after-touch, SysEx, text, unknown, note-ons and notation. Within one kind
+//! the order of collection is preserved.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rea_rs::{Reaper, Position, MidiNoteEvent};
+//! let rpr = Reaper::get_mut();
+//! let mut pr = rpr.current_project();
+//! let mut tr = pr.get_track_mut(0).unwrap();
+//! let mut item = tr.add_midi_item(Position::from(0.0), Position::from(2.0));
+//! let mut take = item.active_take_mut();
+//! let mut editor = take.edit_midi().unwrap();
+//! editor
+//!     .notes_mut()
+//!     .add(MidiNoteEvent::new(0, 960, false, false, 1, 60, 100, 0));
+//! editor.notes_mut().modify_where(|n| n.note == 60, |n| n.on_velocity = 80);
+//! editor.ccs_mut().remove_where(|cc| cc.message().cc_num() == 1);
+//! // Written to take here. Or on drop.
+//! editor.commit().unwrap();
+//! ```
+
+use std::ops::{Deref, DerefMut};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::BEIZER_HEADER;
+use crate::{
+    flatten_events_with_beizer_curve, flatten_midi_notes, to_raw_midi_events,
+    AfterTouchMessage, AllSysMessage, CCMessage, CcShapeKind,
+    ChannelPressureMessage, HasBeizer, MidiEvent, MidiEventBuilder,
+    MidiEventConsumer, MidiMessage, MidiNoteEvent, Mutable, NotationMessage,
+    NoteOffMessage, NoteOnMessage, PitchBendMessage, ProgramChangeMessage,
+    RawMidiMessage, ReaperResult, Take, TextMessage,
+};
+
+/// Collection of one kind of events inside [MidiTakeEvents].
+///
+/// Order of events is not significant: they are sorted on write.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiEventList<T> {
+    events: Vec<T>,
+}
+impl<T> Default for MidiEventList<T> {
+    fn default() -> Self {
+        Self { events: Vec::new() }
+    }
+}
+impl<T> MidiEventList<T> {
+    pub fn new(events: Vec<T>) -> Self {
+        Self { events }
+    }
+    /// Add event and return its index.
+    pub fn add(&mut self, event: T) -> usize {
+        self.events.push(event);
+        self.events.len() - 1
+    }
+    /// Remove event by index. Later events are shifted.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        match index < self.events.len() {
+            true => Some(self.events.remove(index)),
+            false => None,
+        }
+    }
+    /// Remove all events, matching predicate, and return them.
+    pub fn remove_where(
+        &mut self,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> Vec<T> {
+        let (removed, kept) =
+            self.events.drain(..).partition(|e| predicate(e));
+        self.events = kept;
+        removed
+    }
+    /// Modify event by index. Returns false if there is no such event.
+    pub fn modify(&mut self, index: usize, f: impl FnOnce(&mut T)) -> bool {
+        match self.events.get_mut(index) {
+            Some(event) => {
+                f(event);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Modify all events, matching predicate. Returns number of modified.
+    pub fn modify_where(
+        &mut self,
+        mut predicate: impl FnMut(&T) -> bool,
+        mut f: impl FnMut(&mut T),
+    ) -> usize {
+        let mut count = 0;
+        for event in self.events.iter_mut().filter(|e| predicate(e)) {
+            f(event);
+            count += 1;
+        }
+        count
+    }
+    /// Index of the first event, matching predicate.
+    pub fn position(
+        &self,
+        predicate: impl FnMut(&T) -> bool,
+    ) -> Option<usize> {
+        self.events.iter().position(predicate)
+    }
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.events.iter_mut()
+    }
+    pub fn clear(&mut self) {
+        self.events.clear()
+    }
+    pub fn into_vec(self) -> Vec<T> {
+        self.events
+    }
+}
+impl<T> Deref for MidiEventList<T> {
+    type Target = [T];
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+/// All take MIDI, split by the event kinds.
+///
+/// Can be used without REAPER, on the buffers got by
+/// [Take::get_midi] and passed to [Take::set_midi].
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MidiTakeEvents {
+    notes: MidiEventList<MidiNoteEvent>,
+    ccs: MidiEventList<MidiEvent<CCMessage>>,
+    pitch_bends: MidiEventList<MidiEvent<PitchBendMessage>>,
+    channel_pressure: MidiEventList<MidiEvent<ChannelPressureMessage>>,
+    after_touch: MidiEventList<MidiEvent<AfterTouchMessage>>,
+    program_changes: MidiEventList<MidiEvent<ProgramChangeMessage>>,
+    sysex: MidiEventList<MidiEvent<AllSysMessage>>,
+    text: MidiEventList<MidiEvent<TextMessage>>,
+    notation: MidiEventList<MidiEvent<NotationMessage>>,
+    unknown: MidiEventList<MidiEvent<RawMidiMessage>>,
+    end_of_take: Option<MidiEvent<RawMidiMessage>>,
+}
+impl MidiTakeEvents {
+    /// Parse raw take MIDI, as got from [Take::get_midi].
+    pub fn from_raw(buf: Vec<u8>) -> Self {
+        let mut events: Vec<MidiEvent<RawMidiMessage>> =
+            MidiEventBuilder::new(buf.into_iter()).collect();
+        let mut result = Self::default();
+        // REAPER puts "all notes off" at the end of take buffer. It keeps
+        // the source length, so it is kept separately.
+        if let Some(last) = events.last() {
+            let raw = last.message().borrow_raw();
+            if raw.len() == 3 && raw[0] & 0xf0 == 0xb0 && raw[1] == 123 {
+                result.end_of_take = events.pop();
+            }
+        }
+        let mut note_ons: Vec<MidiEvent<NoteOnMessage>> = Vec::new();
+        let mut iter = events.into_iter().peekable();
+        while let Some(event) = iter.next() {
+            let raw = event.message().get_raw();
+            if raw.is_empty() {
+                result.unknown.add(event);
+                continue;
+            }
+            let mut raw_with_beizer = raw.clone();
+            if event.cc_shape_kind() == CcShapeKind::Beizer {
+                if let Some(next) = iter.peek() {
+                    if is_beizer_data(next.message().borrow_raw()) {
+                        raw_with_beizer.append(&mut next.message().get_raw());
+                        iter.next();
+                    }
+                }
+            }
+            let status = raw[0];
+            match status & 0xf0 {
+                0x90 if raw.len() == 3 && raw[2] > 0 => {
+                    note_ons.push(MidiEvent::with_new_message(
+                        event,
+                        NoteOnMessage::from_raw(raw).expect("checked"),
+                    ))
+                }
+                0x80 | 0x90 if raw.len() == 3 => {
+                    let (channel, note) = (status & 0x0f, raw[1]);
+                    let off_velocity = match status & 0xf0 {
+                        0x80 => raw[2],
+                        _ => 0,
+                    };
+                    match note_ons.iter().position(|on| {
+                        on.message().borrow_raw()[0] & 0x0f == channel
+                            && on.message().note() == note
+                    }) {
+                        Some(idx) => {
+                            let off = MidiEvent::with_new_message(
+                                event,
+                                NoteOffMessage::new(
+                                    channel + 1,
+                                    note,
+                                    off_velocity,
+                                ),
+                            );
+                            let on = note_ons.remove(idx);
+                            result
+                                .notes
+                                .add(MidiNoteEvent::from_raw_parts(on, off));
+                        }
+                        None => {
+                            result.unknown.add(event);
+                        }
+                    }
+                }
+                0xb0 => {
+                    result.ccs.add(with_message(event, raw_with_beizer));
+                }
+                0xe0 => {
+                    result
+                        .pitch_bends
+                        .add(with_message(event, raw_with_beizer));
+                }
+                0xd0 => {
+                    result
+                        .channel_pressure
+                        .add(with_message(event, raw_with_beizer));
+                }
+                0xa0 if raw.len() == 3 => {
+                    result.after_touch.add(with_message(event, raw));
+                }
+                0xc0 if raw.len() == 2 => {
+                    result.program_changes.add(with_message(event, raw));
+                }
+                0xf0 => result.add_sys(event, raw_with_beizer),
+                _ => {
+                    result.unknown.add(event);
+                }
+            }
+        }
+        for on in note_ons {
+            result.unknown.add(MidiEvent::with_new_message(
+                on.clone(),
+                on.message().as_raw_message(),
+            ));
+        }
+        result
+    }
+
+    fn add_sys(&mut self, event: MidiEvent<RawMidiMessage>, raw: Vec<u8>) {
+        if raw.len() >= 2 && raw[0] == 0xff && raw[1] == 0x0f {
+            if is_beizer_data(&raw) {
+                self.unknown.add(event);
+            } else {
+                self.notation.add(with_message(event, raw));
+            }
+        } else if raw.len() >= 2
+            && raw[0] == 0xff
+            && std::str::from_utf8(&raw[2..]).is_ok()
+            && TextMessage::from_raw(raw.clone()).is_some()
+        {
+            self.text.add(with_message(event, raw));
+        } else if raw[0] == 0xf0 {
+            self.sysex.add(with_message(event, raw));
+        } else {
+            self.unknown.add(event);
+        }
+    }
+
+    /// Build sorted raw MIDI, to be passed to [Take::set_midi].
+    pub fn to_raw(&self) -> Vec<u8> {
+        let mut entries: Vec<(u32, u8, Vec<MidiEvent<RawMidiMessage>>)> =
+            Vec::new();
+        let mut push =
+            |priority: u8, group: Vec<MidiEvent<RawMidiMessage>>| {
+                if let Some(first) = group.first() {
+                    entries.push((first.ppq_position(), priority, group));
+                }
+            };
+        for note in self.notes.iter() {
+            let mut on_off: Vec<_> =
+                flatten_midi_notes(std::iter::once(note.clone())).collect();
+            // note-off of zero-length note goes after its own note-on.
+            let off_priority = match note.start_in_ppq == note.end_in_ppq {
+                true => 11,
+                false => 0,
+            };
+            push(off_priority, vec![on_off.pop().expect("note-off")]);
+            push(9, vec![on_off.pop().expect("note-on")]);
+        }
+        for event in self.program_changes.iter() {
+            push(1, raw_group(event));
+        }
+        for event in self.ccs.iter() {
+            push(2, beizer_group(event));
+        }
+        for event in self.pitch_bends.iter() {
+            push(3, beizer_group(event));
+        }
+        for event in self.channel_pressure.iter() {
+            push(4, beizer_group(event));
+        }
+        for event in self.after_touch.iter() {
+            push(5, raw_group(event));
+        }
+        for event in self.sysex.iter() {
+            push(6, raw_group(event));
+        }
+        for event in self.text.iter() {
+            push(7, raw_group(event));
+        }
+        for event in self.unknown.iter() {
+            push(8, raw_group(event));
+        }
+        for event in self.notation.iter() {
+            push(10, raw_group(event));
+        }
+        // sort is stable, so the order inside one kind is kept.
+        entries.sort_by_key(|(ppq, priority, _)| (*ppq, *priority));
+        let mut events: Vec<MidiEvent<RawMidiMessage>> =
+            entries.into_iter().flat_map(|(_, _, g)| g).collect();
+        if let Some(end) = &self.end_of_take {
+            let mut end = end.clone();
+            if let Some(last) = events.last() {
+                end.set_ppq_position(
+                    end.ppq_position().max(last.ppq_position()),
+                );
+            }
+            events.push(end);
+        }
+        MidiEventConsumer::new(events.into_iter()).collect()
+    }
+
+    pub fn notes(&self) -> &MidiEventList<MidiNoteEvent> {
+        &self.notes
+    }
+    pub fn notes_mut(&mut self) -> &mut MidiEventList<MidiNoteEvent> {
+        &mut self.notes
+    }
+    pub fn ccs(&self) -> &MidiEventList<MidiEvent<CCMessage>> {
+        &self.ccs
+    }
+    pub fn ccs_mut(&mut self) -> &mut MidiEventList<MidiEvent<CCMessage>> {
+        &mut self.ccs
+    }
+    pub fn pitch_bends(&self) -> &MidiEventList<MidiEvent<PitchBendMessage>> {
+        &self.pitch_bends
+    }
+    pub fn pitch_bends_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<PitchBendMessage>> {
+        &mut self.pitch_bends
+    }
+    pub fn channel_pressure(
+        &self,
+    ) -> &MidiEventList<MidiEvent<ChannelPressureMessage>> {
+        &self.channel_pressure
+    }
+    pub fn channel_pressure_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<ChannelPressureMessage>> {
+        &mut self.channel_pressure
+    }
+    pub fn after_touch(&self) -> &MidiEventList<MidiEvent<AfterTouchMessage>> {
+        &self.after_touch
+    }
+    pub fn after_touch_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<AfterTouchMessage>> {
+        &mut self.after_touch
+    }
+    pub fn program_changes(
+        &self,
+    ) -> &MidiEventList<MidiEvent<ProgramChangeMessage>> {
+        &self.program_changes
+    }
+    pub fn program_changes_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<ProgramChangeMessage>> {
+        &mut self.program_changes
+    }
+    /// SysEx messages (`0xf0 ... 0xf7`).
+    pub fn sysex(&self) -> &MidiEventList<MidiEvent<AllSysMessage>> {
+        &self.sysex
+    }
+    pub fn sysex_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<AllSysMessage>> {
+        &mut self.sysex
+    }
+    /// Text meta-events (text, lyrics, markers etc.).
+    pub fn text(&self) -> &MidiEventList<MidiEvent<TextMessage>> {
+        &self.text
+    }
+    pub fn text_mut(&mut self) -> &mut MidiEventList<MidiEvent<TextMessage>> {
+        &mut self.text
+    }
+    pub fn notation(&self) -> &MidiEventList<MidiEvent<NotationMessage>> {
+        &self.notation
+    }
+    pub fn notation_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<NotationMessage>> {
+        &mut self.notation
+    }
+    /// Events, that are not classified. They are written back as is.
+    pub fn unknown(&self) -> &MidiEventList<MidiEvent<RawMidiMessage>> {
+        &self.unknown
+    }
+    pub fn unknown_mut(
+        &mut self,
+    ) -> &mut MidiEventList<MidiEvent<RawMidiMessage>> {
+        &mut self.unknown
+    }
+}
+
+fn is_beizer_data(raw: &[u8]) -> bool {
+    raw.starts_with(&BEIZER_HEADER)
+}
+
+fn with_message<T: MidiMessage>(
+    event: MidiEvent<RawMidiMessage>,
+    raw: Vec<u8>,
+) -> MidiEvent<T> {
+    let message = T::from_raw(raw).expect("message kind is checked");
+    MidiEvent::with_new_message(event, message)
+}
+
+fn raw_group<T: MidiMessage>(
+    event: &MidiEvent<T>,
+) -> Vec<MidiEvent<RawMidiMessage>> {
+    to_raw_midi_events(std::iter::once(event.clone())).collect()
+}
+
+fn beizer_group<T: HasBeizer>(
+    event: &MidiEvent<T>,
+) -> Vec<MidiEvent<RawMidiMessage>> {
+    flatten_events_with_beizer_curve(std::iter::once(event.clone())).collect()
+}
+
+/// Edits take MIDI as [MidiTakeEvents] and writes it back on commit or drop.
+///
+/// All mutable access (through `DerefMut`) marks editor as modified. If
+/// nothing was modified — take is not touched.
+///
+/// Errors on drop can not be returned, so they are logged. Use
+/// [MidiTakeEditor::commit] to handle them.
+#[derive(Debug)]
+pub struct MidiTakeEditor<'a, 'b> {
+    take: &'b mut Take<'a, Mutable>,
+    events: MidiTakeEvents,
+    is_modified: bool,
+}
+impl<'a, 'b> MidiTakeEditor<'a, 'b> {
+    pub fn new(take: &'b mut Take<'a, Mutable>) -> ReaperResult<Self> {
+        let events = MidiTakeEvents::from_raw(take.get_midi(None)?);
+        Ok(Self {
+            take,
+            events,
+            is_modified: false,
+        })
+    }
+    pub fn is_modified(&self) -> bool {
+        self.is_modified
+    }
+    /// Write changes to take and finish editing.
+    pub fn commit(mut self) -> ReaperResult<()> {
+        self.write()
+    }
+    /// Finish editing without writing changes.
+    pub fn discard(mut self) {
+        self.is_modified = false;
+    }
+    /// Write changes to take and continue editing.
+    pub fn write(&mut self) -> ReaperResult<()> {
+        if !self.is_modified {
+            return Ok(());
+        }
+        self.take.set_midi(self.events.to_raw())?;
+        self.is_modified = false;
+        Ok(())
+    }
+}
+impl<'a, 'b> Deref for MidiTakeEditor<'a, 'b> {
+    type Target = MidiTakeEvents;
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+impl<'a, 'b> DerefMut for MidiTakeEditor<'a, 'b> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.is_modified = true;
+        &mut self.events
+    }
+}
+impl<'a, 'b> Drop for MidiTakeEditor<'a, 'b> {
+    fn drop(&mut self) {
+        if let Err(error) = self.write() {
+            log::error!("Can not write MIDI to take: {:?}", error);
+        }
+    }
+}
+
+impl<'a> Take<'a, Mutable> {
+    /// Load all take MIDI to [MidiTakeEditor].
+    ///
+    /// Changes are written back on [MidiTakeEditor::commit] or on drop.
+    pub fn edit_midi(&mut self) -> ReaperResult<MidiTakeEditor<'a, '_>> {
+        MidiTakeEditor::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_event(
+        offset: u32,
+        flag: u8,
+        msg: &[u8],
+    ) -> impl Iterator<Item = u8> {
+        let mut buf = offset.to_le_bytes().to_vec();
+        buf.push(flag);
+        buf.extend((msg.len() as u32).to_le_bytes());
+        buf.extend(msg);
+        buf.into_iter()
+    }
+
+    fn test_buf() -> Vec<u8> {
+        let mut tension = vec![0xff, 0x0f, b'C', b'C', b'B', b'Z', b' ', 0];
+        tension.extend(0.5_f32.to_le_bytes());
+        let mut notation = vec![0xff, 0x0f];
+        notation.extend(b"NOTE 0 60 articulation staccato");
+        [
+            raw_event(0, 0, &[0x90, 60, 100]).collect::<Vec<_>>(),
+            raw_event(0, 0, &notation).collect(),
+            raw_event(0, 80, &[0xe0, 0, 64]).collect(),
+            raw_event(0, 0, &tension).collect(),
+            raw_event(10, 0, &[0xb0, 1, 10]).collect(),
+            raw_event(10, 0, &[0x80, 61, 0]).collect(), // orphan
+            raw_event(0, 0, &[0xff, 0x05, b'l', b'a']).collect(),
+            raw_event(0, 0, &[0xff, 0x21, 0]).collect(), // port meta
+            raw_event(0, 1, &[0xf0, 0x7e, 0x7f, 0x09, 0x01, 0xf7]).collect(),
+            raw_event(100, 0, &[0x90, 60, 0]).collect(),
+            raw_event(50, 0, &[0xb0, 123, 0]).collect(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_classification() {
+        let events = MidiTakeEvents::from_raw(test_buf());
+        assert_eq!(
+            events.notes().to_vec(),
+            vec![MidiNoteEvent::new(0, 120, false, false, 1, 60, 100, 0)]
+        );
+        assert_eq!(events.ccs().len(), 1);
+        assert_eq!(
+            events.pitch_bends()[0].message().beizer_tension(),
+            Some(0.5)
+        );
+        assert_eq!(events.text()[0].message().text(), "la");
+        assert_eq!(events.text()[0].message().kind(), 5);
+        assert_eq!(events.notation().len(), 1);
+        assert_eq!(events.sysex().len(), 1);
+        assert!(events.sysex()[0].selected());
+        assert_eq!(events.unknown().len(), 2);
+    }
+
+    #[test]
+    fn test_unmodified_round_trip() {
+        let events = MidiTakeEvents::from_raw(test_buf());
+        let raw = events.to_raw();
+        let back = MidiTakeEvents::from_raw(raw.clone());
+        assert_eq!(back, events);
+        // note-on with zero velocity is the only normalized event.
+        assert_eq!(raw.len(), test_buf().len());
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut events = MidiTakeEvents::from_raw(test_buf());
+        let idx = events
+            .notes_mut()
+            .add(MidiNoteEvent::new(300, 400, false, false, 2, 64, 90, 0));
+        assert!(events.notes_mut().modify(idx, |n| n.note = 65));
+        assert_eq!(
+            events
+                .notes_mut()
+                .modify_where(|n| n.channel == 1, |n| n.start_in_ppq = 5),
+            1
+        );
+        let removed = events.ccs_mut().remove_where(|_| true);
+        assert_eq!(removed.len(), 1);
+        assert!(events.sysex_mut().remove(5).is_none());
+
+        let raw = events.to_raw();
+        // Orphan note-off is kept, so it is seen by FilterNotes as well.
+        let notes: Vec<MidiNoteEvent> =
+            MidiEventBuilder::new(raw.clone().into_iter())
+                .filter_notes()
+                .filter(|n| n.note != 61)
+                .collect();
+        assert_eq!(notes[0].start_in_ppq, 5);
+        assert_eq!(notes[1].note, 65);
+        let all: Vec<_> = MidiEventBuilder::new(raw.into_iter()).collect();
+        // end of take moved after the last note.
+        let last = all.last().unwrap();
+        assert_eq!(last.ppq_position(), 400);
+        assert_eq!(last.message().borrow_raw(), &vec![0xb0, 123, 0]);
+        let positions: Vec<_> = all.iter().map(|e| e.ppq_position()).collect();
+        let mut sorted = positions.clone();
+        sorted.sort();
+        assert_eq!(positions, sorted);
+    }
+
+    #[test]
+    fn test_zero_length_note() {
+        let mut events = MidiTakeEvents::from_raw(test_buf());
+        let note = MidiNoteEvent::new(120, 120, false, false, 1, 62, 90, 0);
+        events.notes_mut().add(note.clone());
+        let back = MidiTakeEvents::from_raw(events.to_raw());
+        assert!(back.notes().contains(&note));
+        assert_eq!(back.unknown().len(), events.unknown().len());
+    }
+}
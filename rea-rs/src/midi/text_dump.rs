@@ -0,0 +1,565 @@
+//! Line-based text format of take MIDI.
+//!
+//! Every event is one line:
+//!
+//! ```text
+//! <ppq> <flags> <shape> <kind> <arguments...>
+//! ```
+//!
+//! - `ppq` is absolute position.
+//! - `flags` is two chars: `s` if selected, `m` if muted, or `-`. If the
+//!   raw flag byte has other bits set, the whole byte follows in hex after
+//!   `:`, e.g. `s-:0d`.
+//! - `shape` is one of `square`, `linear`, `slow`, `fast-start`, `fast-end`,
+//!   `bezier`.
+//! - `kind` and arguments are:
+//!   - `note_on <channel> <note> <velocity>`
+//!   - `note_off <channel> <note> <velocity>`
+//!   - `aftertouch <channel> <note> <pressure>`
+//!   - `cc <channel> <number> <value>`
+//!   - `program <channel> <program>`
+//!   - `pressure <channel> <value>`
+//!   - `pitch_bend <channel> <value 0..16383>`
+//!   - `sysex <hex bytes, including f0 and f7>`
+//!   - `meta <type> "<text>"`, with `\"`, `\\` and `\xHH` escapes
+//!   - `bezier <type> <tension>` for the `CCBZ` data of the previous event
+//!   - `raw <hex bytes>` for everything else
+//!
+//! Channels are 1-based. Empty lines and lines, starting with `#`, are
+//! ignored. Every event of raw MIDI buffer round-trips byte-exactly,
+//! including its flag byte. [MidiEvent] keeps only selection, mute and
+//! CC shape, so [parse_midi_dump] drops other flag bits.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let text = "\
+//! 0 -- square note_on 1 60 100
+//! 0 s- linear cc 2 74 0
+//! 480 -- square cc 2 74 127
+//! 480 -m square note_off 1 60 0
+//! 960 -- square meta 15 \"NOTE 0 60 text \\\"hi\\\"\"
+//! ";
+//! let events = parse_midi_dump(text).unwrap();
+//! assert_eq!(events.len(), 5);
+//! assert!(events[1].selected());
+//! assert!(events[3].muted());
+//! assert_eq!(dump_midi_events(events.into_iter()), text);
+//! ```
+
+use super::{borrowed::event_flag, BEIZER_HEADER};
+use crate::{
+    CcShapeKind, MidiBufWriter, MidiEvent, MidiEventRefBuilder, Mutable,
+    ProbablyMutable, RawMidiMessage, ReaRsError, ReaperResult, Take,
+};
+
+fn shape_name(shape: CcShapeKind) -> &'static str {
+    match shape {
+        CcShapeKind::Square => "square",
+        CcShapeKind::Linear => "linear",
+        CcShapeKind::SlowStartEnd => "slow",
+        CcShapeKind::FastStart => "fast-start",
+        CcShapeKind::FastEnd => "fast-end",
+        CcShapeKind::Beizer => "bezier",
+    }
+}
+
+fn shape_from_name(name: &str) -> Option<CcShapeKind> {
+    Some(match name {
+        "square" => CcShapeKind::Square,
+        "linear" => CcShapeKind::Linear,
+        "slow" => CcShapeKind::SlowStartEnd,
+        "fast-start" => CcShapeKind::FastStart,
+        "fast-end" => CcShapeKind::FastEnd,
+        "bezier" => CcShapeKind::Beizer,
+        _ => return None,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('"');
+    for byte in bytes {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(*byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or("text should be quoted")?;
+    let mut out = Vec::with_capacity(inner.len());
+    let mut bytes = inner.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'"') => out.push(b'"'),
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'x') => {
+                let digits: Vec<u8> = bytes.by_ref().take(2).collect();
+                let digits = String::from_utf8(digits).unwrap_or_default();
+                out.push(
+                    u8::from_str_radix(&digits, 16)
+                        .map_err(|_| format!("bad escape: \\x{}", digits))?,
+                );
+            }
+            _ => return Err("unknown escape".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+fn format_message(msg: &[u8]) -> String {
+    let is_data = |bytes: &[u8]| bytes.iter().all(|b| *b < 0x80);
+    let status = msg.first().copied().unwrap_or_default();
+    let channel = (status & 0x0f) + 1;
+    match (status & 0xf0, msg.len()) {
+        (0x80 | 0x90 | 0xa0 | 0xb0, 3) if is_data(&msg[1..]) => {
+            let kind = match status & 0xf0 {
+                0x80 => "note_off",
+                0x90 => "note_on",
+                0xa0 => "aftertouch",
+                _ => "cc",
+            };
+            format!("{} {} {} {}", kind, channel, msg[1], msg[2])
+        }
+        (0xe0, 3) if is_data(&msg[1..]) => {
+            let value = msg[1] as u16 | (msg[2] as u16) << 7;
+            format!("pitch_bend {} {}", channel, value)
+        }
+        (0xc0 | 0xd0, 2) if is_data(&msg[1..]) => {
+            let kind = match status & 0xf0 {
+                0xc0 => "program",
+                _ => "pressure",
+            };
+            format!("{} {} {}", kind, channel, msg[1])
+        }
+        _ if status == 0xf0 => format!("sysex {}", hex(msg)),
+        _ if msg.len() == 12
+            && msg.starts_with(&BEIZER_HEADER)
+            && msg[6] == b' ' =>
+        {
+            let tension =
+                f32::from_le_bytes([msg[8], msg[9], msg[10], msg[11]]);
+            match format!("{}", tension).parse::<f32>().map(f32::to_bits) {
+                Ok(bits) if bits == tension.to_bits() => {
+                    format!("bezier {} {}", msg[7], tension)
+                }
+                _ => format!("raw {}", hex(msg)),
+            }
+        }
+        _ if status == 0xff && msg.len() >= 2 => {
+            format!("meta {} {}", msg[1], escape(&msg[2..]))
+        }
+        _ => format!("raw {}", hex(msg)),
+    }
+}
+
+/// Selection, mute and CC shape of raw flag byte.
+///
+/// Unknown shape bits are treated as [CcShapeKind::Square].
+fn flag_parts(flag: u8) -> (bool, bool, CcShapeKind) {
+    (
+        flag & 1 != 0,
+        flag & 2 != 0,
+        CcShapeKind::from_raw(flag & 0b11110000).unwrap_or_default(),
+    )
+}
+
+fn format_line(position_in_ppq: u32, flag: u8, message: &[u8]) -> String {
+    let (is_selected, is_muted, cc_shape_kind) = flag_parts(flag);
+    let raw_flag = match event_flag(is_selected, is_muted, cc_shape_kind) {
+        f if f == flag => String::new(),
+        _ => format!(":{:02x}", flag),
+    };
+    format!(
+        "{} {}{}{} {} {}",
+        position_in_ppq,
+        if is_selected { 's' } else { '-' },
+        if is_muted { 'm' } else { '-' },
+        raw_flag,
+        shape_name(cc_shape_kind),
+        format_message(message)
+    )
+}
+
+/// One line of text dump, without line break.
+pub fn format_midi_event_line(
+    position_in_ppq: u32,
+    is_selected: bool,
+    is_muted: bool,
+    cc_shape_kind: CcShapeKind,
+    message: &[u8],
+) -> String {
+    format_line(
+        position_in_ppq,
+        event_flag(is_selected, is_muted, cc_shape_kind),
+        message,
+    )
+}
+
+/// Text dump of events, one line per event.
+pub fn dump_midi_events(
+    events: impl Iterator<Item = MidiEvent<RawMidiMessage>>,
+) -> String {
+    let mut out = String::new();
+    for event in events {
+        out += &format_midi_event_line(
+            event.ppq_position(),
+            event.selected(),
+            event.muted(),
+            event.cc_shape_kind(),
+            &event.message().buf,
+        );
+        out.push('\n');
+    }
+    out
+}
+
+/// Text dump of take raw MIDI buffer, as got from [Take::get_midi].
+pub fn dump_midi_buf(buf: &[u8]) -> String {
+    let mut out = String::new();
+    for event in MidiEventRefBuilder::new(buf) {
+        out +=
+            &format_line(event.ppq_position(), event.flag(), event.message());
+        out.push('\n');
+    }
+    out
+}
+
+fn parse_u8(token: Option<&str>, max: u8) -> Result<u8, String> {
+    let token = token.ok_or("not enough arguments")?;
+    match token.parse::<u8>() {
+        Ok(value) if value <= max => Ok(value),
+        _ => Err(format!("expected number 0..={}, got '{}'", max, token)),
+    }
+}
+
+fn parse_hex(tokens: &[&str]) -> Result<Vec<u8>, String> {
+    tokens
+        .iter()
+        .map(|t| {
+            u8::from_str_radix(t, 16)
+                .map_err(|_| format!("expected hex byte, got '{}'", t))
+        })
+        .collect()
+}
+
+type ParsedLine = (u32, u8, Vec<u8>);
+
+fn parse_line(line: &str) -> Result<ParsedLine, String> {
+    let mut parts = line.splitn(5, ' ');
+    let mut next = || parts.next().ok_or("line is too short");
+    let position = next()?;
+    let position = position
+        .parse::<u32>()
+        .map_err(|_| format!("bad position: '{}'", position))?;
+    let flags = next()?;
+    let (chars, raw_flag) = match flags.split_once(':') {
+        Some((chars, raw)) if raw.len() == 2 => (
+            chars,
+            Some(
+                u8::from_str_radix(raw, 16)
+                    .map_err(|_| format!("bad flag byte: '{}'", raw))?,
+            ),
+        ),
+        Some(_) => return Err(format!("bad flags: '{}'", flags)),
+        None => (flags, None),
+    };
+    let (is_selected, is_muted) = match chars {
+        "--" => (false, false),
+        "s-" => (true, false),
+        "-m" => (false, true),
+        "sm" => (true, true),
+        _ => return Err(format!("bad flags: '{}'", flags)),
+    };
+    let shape = next()?;
+    let shape = shape_from_name(shape)
+        .ok_or_else(|| format!("bad shape: '{}'", shape))?;
+    let flag = match raw_flag {
+        None => event_flag(is_selected, is_muted, shape),
+        Some(flag) if flag_parts(flag) == (is_selected, is_muted, shape) => {
+            flag
+        }
+        Some(flag) => {
+            return Err(format!(
+                "flag byte {:02x} does not match flags and shape",
+                flag
+            ))
+        }
+    };
+    let kind = next()?;
+    let rest = parts.next().unwrap_or("");
+    let message = match kind {
+        "meta" => {
+            let (kind, text) = rest.split_once(' ').ok_or("no meta text")?;
+            let mut msg = vec![0xff, parse_u8(Some(kind), 0xff)?];
+            msg.extend(unescape(text)?);
+            msg
+        }
+        "bezier" => {
+            let (kind, tension) =
+                rest.split_once(' ').ok_or("no bezier tension")?;
+            let tension = tension
+                .parse::<f32>()
+                .map_err(|_| format!("bad tension: '{}'", tension))?;
+            let mut msg = BEIZER_HEADER.to_vec();
+            msg.push(b' ');
+            msg.push(parse_u8(Some(kind), 0xff)?);
+            msg.extend(tension.to_le_bytes());
+            msg
+        }
+        "sysex" | "raw" => {
+            let tokens: Vec<&str> = rest.split_whitespace().collect();
+            let msg = parse_hex(&tokens)?;
+            if msg.is_empty() {
+                return Err("empty message".to_string());
+            }
+            msg
+        }
+        _ => {
+            let mut args = rest.split(' ');
+            let channel = parse_u8(args.next(), 16)?;
+            if channel == 0 {
+                return Err("channel should be 1..=16".to_string());
+            }
+            let status = |s: u8| s | (channel - 1);
+            let msg = match kind {
+                "note_off" | "note_on" | "aftertouch" | "cc" => {
+                    let s = match kind {
+                        "note_off" => 0x80,
+                        "note_on" => 0x90,
+                        "aftertouch" => 0xa0,
+                        _ => 0xb0,
+                    };
+                    vec![
+                        status(s),
+                        parse_u8(args.next(), 127)?,
+                        parse_u8(args.next(), 127)?,
+                    ]
+                }
+                "program" => vec![status(0xc0), parse_u8(args.next(), 127)?],
+                "pressure" => {
+                    vec![status(0xd0), parse_u8(args.next(), 127)?]
+                }
+                "pitch_bend" => {
+                    let token = args.next().ok_or("not enough arguments")?;
+                    let value = match token.parse::<u16>() {
+                        Ok(v) if v < 16384 => v,
+                        _ => {
+                            return Err(format!(
+                                "bad pitch bend value: '{}'",
+                                token
+                            ))
+                        }
+                    };
+                    vec![
+                        status(0xe0),
+                        (value & 0x7f) as u8,
+                        (value >> 7) as u8,
+                    ]
+                }
+                _ => return Err(format!("unknown event kind: '{}'", kind)),
+            };
+            if args.next().is_some() {
+                return Err("too many arguments".to_string());
+            }
+            msg
+        }
+    };
+    Ok((position, flag, message))
+}
+
+fn parse_lines(
+    text: &str,
+) -> impl Iterator<Item = ReaperResult<ParsedLine>> + '_ {
+    text.lines().enumerate().filter_map(|(idx, line)| {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            return None;
+        }
+        Some(
+            parse_line(line.trim_start())
+                .map_err(|e| ReaRsError::Parse(idx + 1, e)),
+        )
+    })
+}
+
+/// Parse text dump into events.
+///
+/// Returns [ReaRsError::Parse] with 1-based line number on error.
+pub fn parse_midi_dump(
+    text: &str,
+) -> ReaperResult<Vec<MidiEvent<RawMidiMessage>>> {
+    parse_lines(text)
+        .map(|line| {
+            let (position, flag, buf) = line?;
+            let (selected, muted, shape) = flag_parts(flag);
+            Ok(MidiEvent::new(
+                position,
+                selected,
+                muted,
+                shape,
+                RawMidiMessage { buf },
+            ))
+        })
+        .collect()
+}
+
+/// Parse text dump into take raw MIDI buffer, to be passed to
+/// [Take::set_midi].
+pub fn parse_midi_dump_to_buf(text: &str) -> ReaperResult<Vec<u8>> {
+    let mut writer = MidiBufWriter::new();
+    for line in parse_lines(text) {
+        let (position, flag, buf) = line?;
+        writer.push_with_flag(position, flag, &buf);
+    }
+    Ok(writer.finish())
+}
+
+impl<'a, T: ProbablyMutable> Take<'a, T> {
+    /// All take MIDI in the text format of [crate::midi::text_dump].
+    pub fn dump_midi(&self) -> ReaperResult<String> {
+        Ok(dump_midi_buf(&self.get_midi(None)?))
+    }
+}
+impl<'a> Take<'a, Mutable> {
+    /// Replace all take MIDI by the text dump.
+    ///
+    /// See [crate::midi::text_dump].
+    pub fn load_midi_dump(&mut self, text: &str) -> ReaperResult<()> {
+        self.set_midi(parse_midi_dump_to_buf(text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_buf() -> Vec<u8> {
+        let mut writer = MidiBufWriter::new();
+        let mut push = |pos, sel, mute, shape, msg: &[u8]| {
+            writer.push_raw(pos, sel, mute, shape, msg)
+        };
+        push(0, false, false, CcShapeKind::Square, &[0x90, 60, 100]);
+        push(0, true, false, CcShapeKind::Beizer, &[0xb3, 1, 64]);
+        let mut beizer = BEIZER_HEADER.to_vec();
+        beizer.extend([b' ', 0]);
+        beizer.extend(0.3_f32.to_le_bytes());
+        push(0, true, false, CcShapeKind::Square, &beizer);
+        push(10, false, true, CcShapeKind::Linear, &[0xef, 0x7f, 0x7f]);
+        push(20, false, false, CcShapeKind::FastEnd, &[0xd0, 5]);
+        push(20, false, false, CcShapeKind::Square, &[0xc9, 127]);
+        push(20, false, false, CcShapeKind::Square, &[0xa1, 61, 2]);
+        push(30, true, true, CcShapeKind::Square, &[0xf0, 0x7e, 0xf7]);
+        push(40, false, false, CcShapeKind::Square, &[0xff, 0x05, 0xd0]);
+        let mut notation = vec![0xff, 0x0f];
+        notation.extend(b"NOTE 0 60 text \"a\\b\"");
+        push(40, false, false, CcShapeKind::Square, &notation);
+        push(50, false, false, CcShapeKind::Square, &[0x80, 60, 0]);
+        push(50, false, false, CcShapeKind::Square, &[0x90, 0x80, 0]);
+        push(60, false, false, CcShapeKind::SlowStartEnd, &[0xb0, 123, 0]);
+        writer.finish()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buf = test_buf();
+        let text = dump_midi_buf(&buf);
+        assert_eq!(parse_midi_dump_to_buf(&text).unwrap(), buf);
+        let events = parse_midi_dump(&text).unwrap();
+        assert_eq!(dump_midi_events(events.into_iter()), text);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "0 s- bezier cc 4 1 64");
+        assert_eq!(lines[2], "0 s- square bezier 0 0.3");
+        assert_eq!(lines[3], "10 -m linear pitch_bend 16 16383");
+        assert_eq!(lines[7], "30 sm square sysex f0 7e f7");
+        assert_eq!(lines[8], "40 -- square meta 5 \"\\xd0\"");
+        assert_eq!(
+            lines[9],
+            "40 -- square meta 15 \"NOTE 0 60 text \\\"a\\\\b\\\"\""
+        );
+        assert_eq!(lines[11], "50 -- square raw 90 80 00");
+    }
+
+    #[test]
+    fn test_unknown_flag_bits() {
+        let mut writer = MidiBufWriter::new();
+        writer.push_with_flag(0, 0b00011101, &[0xb0, 1, 2]);
+        writer.push_with_flag(10, 0b10000000, &[0x90, 60, 1]);
+        writer.push_with_flag(20, 0b00001000, &[0x80, 60, 0]);
+        let buf = writer.finish();
+        let text = dump_midi_buf(&buf);
+        assert_eq!(
+            text,
+            "0 s-:1d linear cc 1 1 2\n\
+             10 --:80 square note_on 1 60 1\n\
+             20 --:08 square note_off 1 60 0\n"
+        );
+        assert_eq!(parse_midi_dump_to_buf(&text).unwrap(), buf);
+        let events = parse_midi_dump(&text).unwrap();
+        assert!(events[0].selected());
+        assert_eq!(events[0].cc_shape_kind(), CcShapeKind::Linear);
+        assert_eq!(
+            dump_midi_events(events.into_iter()),
+            "0 s- linear cc 1 1 2\n\
+             10 -- square note_on 1 60 1\n\
+             20 -- square note_off 1 60 0\n"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let text = "# header\n\n  # indented\r\n0 -- square cc 1 1 1\r\n";
+        let events = parse_midi_dump(text).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message().buf, vec![0xb0, 1, 1]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let check = |text: &str, line: usize| match parse_midi_dump(text) {
+            Err(ReaRsError::Parse(l, _)) => assert_eq!(l, line, "{}", text),
+            r => panic!("{}: {:?}", text, r),
+        };
+        check("0 -- square cc 1 1 1\n0 -- square cc 17 1 1", 2);
+        check("0 -- square cc 0 1 1", 1);
+        check("0 -- square cc 1 1 128", 1);
+        check("0 -- square cc 1 1", 1);
+        check("0 -- square cc 1 1 1 1", 1);
+        check("x -- square cc 1 1 1", 1);
+        check("0 s square cc 1 1 1", 1);
+        check("0 s-:1 square cc 1 1 1", 1);
+        check("0 s-:zz square cc 1 1 1", 1);
+        check("0 --:09 square cc 1 1 1", 1);
+        check("0 s-:09 linear cc 1 1 1", 1);
+        check("0 -- round cc 1 1 1", 1);
+        check("0 -- square nrpn 1 1 1", 1);
+        check("0 -- square pitch_bend 1 16384", 1);
+        check("0 -- square meta 1 text", 1);
+        check("0 -- square meta 1 \"\\q\"", 1);
+        check("0 -- square raw", 1);
+        check("0 -- square sysex f0 zz", 1);
+    }
+}
@@ -0,0 +1,417 @@
+//! Musical transforms of [MidiNoteEvent] streams: quantize, swing and
+//! humanize.
+//!
+//! All transforms are iterator adapters, parameterized by the grid in ppq,
+//! so they can be used on any notes source: [crate::Take::iter_midi],
+//! [crate::midi::MidiTakeEvents] or plain `Vec`.
+//!
+//! Notes are yielded in the input order, so, if positions may change the
+//! order of events, use [crate::midi::sorted_by_ppq] before writing buffer
+//! back.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! // 1/16 grid for 960 ppq take.
+//! let grid = 240;
+//! let notes = vec![
+//!     MidiNoteEvent::new(10, 200, false, false, 1, 60, 100, 0),
+//!     MidiNoteEvent::new(250, 470, false, false, 1, 62, 100, 0),
+//! ];
+//! let quantized: Vec<MidiNoteEvent> = notes
+//!     .into_iter()
+//!     .quantize(QuantizeSettings::new(grid))
+//!     .swing(grid, 50.0)
+//!     .humanize(HumanizeSettings::new(42).timing(5).velocity(10))
+//!     .collect();
+//! let raw = MidiEventConsumer::new(sorted_by_ppq(flatten_midi_notes(
+//!     quantized.into_iter(),
+//! )));
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::MidiNoteEvent;
+
+/// Adds transforms to every iterator over [MidiNoteEvent].
+pub trait NoteTransforms: Iterator<Item = MidiNoteEvent> + Sized {
+    /// Quantize notes to the (possibly, swung) grid.
+    fn quantize(self, settings: QuantizeSettings) -> Quantize<Self> {
+        Quantize {
+            notes: self,
+            settings,
+        }
+    }
+    /// Delay (or advance, if negative) every second grid line.
+    ///
+    /// `percent` is in range `-100..100`. 100% moves off-beat grid line by
+    /// half of the grid step. Time between grid lines is stretched
+    /// linearly, so notes, that are not on the grid, move proportionally.
+    fn swing(self, grid: u32, percent: f64) -> Swing<Self> {
+        Swing {
+            notes: self,
+            grid,
+            percent,
+        }
+    }
+    /// Randomize timing and velocity. The same seed gives the same result.
+    fn humanize(self, settings: HumanizeSettings) -> Humanize<Self> {
+        Humanize {
+            notes: self,
+            random: SplitMix64::new(settings.seed),
+            settings,
+        }
+    }
+}
+impl<I: Iterator<Item = MidiNoteEvent>> NoteTransforms for I {}
+
+/// Which note positions are quantized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuantizeTarget {
+    /// Only start is moved, end is kept.
+    Starts,
+    /// Start is moved, length is kept.
+    StartsKeepLength,
+    /// Only end is moved.
+    Ends,
+    /// Start and end are quantized separately.
+    StartsAndEnds,
+}
+
+/// Settings of [NoteTransforms::quantize].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuantizeSettings {
+    /// Grid step in ppq.
+    pub grid: u32,
+    /// Swing of grid in percents `-100..100`.
+    pub swing: f64,
+    /// How close to grid line note is moved: 0.0 — not moved, 1.0 —
+    /// exactly on the grid line.
+    pub strength: f64,
+    pub target: QuantizeTarget,
+    /// If Some — only positions, that are closer to the grid line than
+    /// window (in ppq), are quantized.
+    pub window: Option<u32>,
+}
+impl QuantizeSettings {
+    /// Full-strength quantize of note starts, keeping note length.
+    pub fn new(grid: u32) -> Self {
+        Self {
+            grid,
+            swing: 0.0,
+            strength: 1.0,
+            target: QuantizeTarget::StartsKeepLength,
+            window: None,
+        }
+    }
+    pub fn swing(mut self, percent: f64) -> Self {
+        self.swing = percent;
+        self
+    }
+    pub fn strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+    pub fn target(mut self, target: QuantizeTarget) -> Self {
+        self.target = target;
+        self
+    }
+    pub fn window(mut self, window: impl Into<Option<u32>>) -> Self {
+        self.window = window.into();
+        self
+    }
+
+    /// The closest grid line to the position, taking swing into account.
+    pub fn nearest_grid_line(&self, position: u32) -> u32 {
+        if self.grid == 0 {
+            return position;
+        }
+        let period = self.grid as i64 * 2;
+        let base = position as i64 / period * period;
+        let swung = base + self.grid as i64 + self.swing_offset();
+        [base, swung, base + period]
+            .into_iter()
+            .min_by_key(|line| (line - position as i64).abs())
+            .expect("not empty") as u32
+    }
+
+    fn swing_offset(&self) -> i64 {
+        (self.grid as f64 / 2.0 * self.swing.clamp(-100.0, 100.0) / 100.0)
+            .round() as i64
+    }
+
+    fn quantized(&self, position: u32) -> u32 {
+        let line = self.nearest_grid_line(position);
+        let distance = (line as i64 - position as i64).unsigned_abs();
+        if let Some(window) = self.window {
+            if distance > window as u64 {
+                return position;
+            }
+        }
+        let strength = self.strength.clamp(0.0, 1.0);
+        (position as f64 + (line as f64 - position as f64) * strength).round()
+            as u32
+    }
+
+    fn apply(&self, mut note: MidiNoteEvent) -> MidiNoteEvent {
+        let length = note.end_in_ppq.saturating_sub(note.start_in_ppq);
+        match self.target {
+            QuantizeTarget::Starts => {
+                note.start_in_ppq = self.quantized(note.start_in_ppq);
+            }
+            QuantizeTarget::StartsKeepLength => {
+                note.start_in_ppq = self.quantized(note.start_in_ppq);
+                note.end_in_ppq = note.start_in_ppq + length;
+            }
+            QuantizeTarget::Ends => {
+                note.end_in_ppq = self.quantized(note.end_in_ppq);
+            }
+            QuantizeTarget::StartsAndEnds => {
+                note.start_in_ppq = self.quantized(note.start_in_ppq);
+                note.end_in_ppq = self.quantized(note.end_in_ppq);
+            }
+        }
+        // Do not let note collapse or turn inside-out.
+        if note.end_in_ppq <= note.start_in_ppq {
+            note.end_in_ppq = note.start_in_ppq + self.grid.max(1);
+        }
+        note
+    }
+}
+
+/// Iterator adapter, made by [NoteTransforms::quantize].
+#[derive(Debug, Clone)]
+pub struct Quantize<I: Iterator<Item = MidiNoteEvent>> {
+    notes: I,
+    settings: QuantizeSettings,
+}
+impl<I: Iterator<Item = MidiNoteEvent>> Iterator for Quantize<I> {
+    type Item = MidiNoteEvent;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.settings.apply(self.notes.next()?))
+    }
+}
+
+/// Iterator adapter, made by [NoteTransforms::swing].
+#[derive(Debug, Clone)]
+pub struct Swing<I: Iterator<Item = MidiNoteEvent>> {
+    notes: I,
+    grid: u32,
+    percent: f64,
+}
+impl<I: Iterator<Item = MidiNoteEvent>> Swing<I> {
+    fn warp(&self, position: u32) -> u32 {
+        if self.grid == 0 {
+            return position;
+        }
+        let grid = self.grid as f64;
+        let period = self.grid as u64 * 2;
+        let base = position as u64 / period * period;
+        let offset = (position as u64 - base) as f64;
+        let swung =
+            grid + grid / 2.0 * self.percent.clamp(-100.0, 100.0) / 100.0;
+        let warped = match offset <= grid {
+            true => offset * swung / grid,
+            false => swung + (offset - grid) * (grid * 2.0 - swung) / grid,
+        };
+        (base as f64 + warped).round() as u32
+    }
+}
+impl<I: Iterator<Item = MidiNoteEvent>> Iterator for Swing<I> {
+    type Item = MidiNoteEvent;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut note = self.notes.next()?;
+        note.start_in_ppq = self.warp(note.start_in_ppq);
+        note.end_in_ppq =
+            self.warp(note.end_in_ppq).max(note.start_in_ppq + 1);
+        Some(note)
+    }
+}
+
+/// Settings of [NoteTransforms::humanize].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HumanizeSettings {
+    pub seed: u64,
+    /// Maximum shift of note in ppq in both directions.
+    pub timing: u32,
+    /// Maximum change of note length in ppq in both directions.
+    pub length: u32,
+    /// Maximum change of velocity in both directions.
+    pub velocity: u8,
+}
+impl HumanizeSettings {
+    /// Settings, that do nothing, until ranges are set.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            timing: 0,
+            length: 0,
+            velocity: 0,
+        }
+    }
+    pub fn timing(mut self, ppq: u32) -> Self {
+        self.timing = ppq;
+        self
+    }
+    pub fn length(mut self, ppq: u32) -> Self {
+        self.length = ppq;
+        self
+    }
+    pub fn velocity(mut self, velocity: u8) -> Self {
+        self.velocity = velocity;
+        self
+    }
+}
+
+/// Iterator adapter, made by [NoteTransforms::humanize].
+#[derive(Debug, Clone)]
+pub struct Humanize<I: Iterator<Item = MidiNoteEvent>> {
+    notes: I,
+    settings: HumanizeSettings,
+    random: SplitMix64,
+}
+impl<I: Iterator<Item = MidiNoteEvent>> Iterator for Humanize<I> {
+    type Item = MidiNoteEvent;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut note = self.notes.next()?;
+        let shift = self.random.in_range(self.settings.timing as i64);
+        let length_change = self.random.in_range(self.settings.length as i64);
+        let velocity_change =
+            self.random.in_range(self.settings.velocity as i64);
+        let length = note.end_in_ppq as i64 - note.start_in_ppq as i64;
+        let start = (note.start_in_ppq as i64 + shift).max(0);
+        let length = (length + length_change).max(1);
+        note.start_in_ppq = start as u32;
+        note.end_in_ppq = (start + length) as u32;
+        note.on_velocity =
+            (note.on_velocity as i64 + velocity_change).clamp(1, 127) as u8;
+        Some(note)
+    }
+}
+
+/// Small deterministic PRNG, used for humanize.
+#[derive(Debug, Clone)]
+struct SplitMix64 {
+    state: u64,
+}
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+    /// Uniform value in `-range..=range`.
+    fn in_range(&mut self, range: i64) -> i64 {
+        let value = self.next_u64();
+        if range == 0 {
+            return 0;
+        }
+        (value % (range as u64 * 2 + 1)) as i64 - range
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(start: u32, end: u32) -> MidiNoteEvent {
+        MidiNoteEvent::new(start, end, false, false, 1, 60, 100, 0)
+    }
+
+    fn starts_ends(notes: Vec<MidiNoteEvent>) -> Vec<(u32, u32)> {
+        notes
+            .into_iter()
+            .map(|n| (n.start_in_ppq, n.end_in_ppq))
+            .collect()
+    }
+
+    #[test]
+    fn test_quantize() {
+        let notes = vec![note(10, 200), note(230, 470), note(600, 610)];
+        let result: Vec<_> = notes
+            .clone()
+            .into_iter()
+            .quantize(QuantizeSettings::new(240))
+            .collect();
+        assert_eq!(
+            starts_ends(result),
+            vec![(0, 190), (240, 480), (480, 490)]
+        );
+        let result: Vec<_> = notes
+            .clone()
+            .into_iter()
+            .quantize(
+                QuantizeSettings::new(240)
+                    .strength(0.5)
+                    .target(QuantizeTarget::StartsAndEnds),
+            )
+            .collect();
+        assert_eq!(
+            starts_ends(result),
+            vec![(5, 220), (235, 475), (540, 665)]
+        );
+        let result: Vec<_> = notes
+            .into_iter()
+            .quantize(
+                QuantizeSettings::new(240)
+                    .window(20)
+                    .target(QuantizeTarget::Starts),
+            )
+            .collect();
+        assert_eq!(
+            starts_ends(result),
+            vec![(0, 200), (240, 470), (600, 610)]
+        );
+    }
+
+    #[test]
+    fn test_swing() {
+        let settings = QuantizeSettings::new(240).swing(50.0);
+        assert_eq!(settings.nearest_grid_line(250), 300);
+        assert_eq!(settings.nearest_grid_line(400), 480);
+        let result: Vec<_> =
+            vec![note(0, 240), note(240, 480), note(120, 360)]
+                .into_iter()
+                .swing(240, 50.0)
+                .collect();
+        assert_eq!(
+            starts_ends(result),
+            vec![(0, 300), (300, 480), (150, 390)]
+        );
+    }
+
+    #[test]
+    fn test_humanize() {
+        let notes: Vec<_> = (0..50)
+            .map(|i| note(i * 480 + 100, i * 480 + 300))
+            .collect();
+        let settings = HumanizeSettings::new(7).timing(10).velocity(20);
+        let first: Vec<_> =
+            notes.clone().into_iter().humanize(settings).collect();
+        let second: Vec<_> =
+            notes.clone().into_iter().humanize(settings).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, notes);
+        for (new, old) in first.iter().zip(notes.iter()) {
+            let shift = new.start_in_ppq as i64 - old.start_in_ppq as i64;
+            assert!(shift.abs() <= 10);
+            assert_eq!(new.end_in_ppq - new.start_in_ppq, 200);
+            assert!((80..=120).contains(&new.on_velocity));
+        }
+        let other: Vec<_> = notes
+            .into_iter()
+            .humanize(HumanizeSettings {
+                seed: 8,
+                ..settings
+            })
+            .collect();
+        assert_ne!(first, other);
+    }
+}
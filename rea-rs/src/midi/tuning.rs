@@ -0,0 +1,785 @@
+//! Microtonal tuning by Scala `.scl` scales and `.kbm` keyboard mappings.
+//!
+//! [Tuning] maps MIDI notes to frequencies. [retune_notes] converts notes
+//! into the nearest 12-TET notes with per-channel [PitchBendMessage]
+//! offsets. Notes with different offsets, that sound simultaneously, are
+//! rotated over the given channels.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::midi::*;
+//!
+//! let scale = ScalaScale::parse(
+//!     "! 24edo.scl
+//! quarter tones
+//! 24
+//! 50.0
+//! 100.0",
+//! );
+//! // Declared 24 pitches, but only 2 are given.
+//! assert!(scale.is_err());
+//!
+//! // Every key is a quarter tone.
+//! let tuning = Tuning::new(
+//!     ScalaScale::equal_temperament(24),
+//!     KeyboardMapping::default(),
+//! );
+//! assert_eq!(tuning.frequency(69), Some(440.0));
+//! let notes = vec![
+//!     MidiNoteEvent::new(0, 480, false, false, 1, 69, 90, 0),
+//!     MidiNoteEvent::new(0, 480, false, false, 1, 70, 90, 0),
+//! ];
+//! let retuned = retune_notes(&notes, &tuning, &RetuneSettings::default());
+//! assert_eq!(retuned.notes[0].channel, 1);
+//! // A quarter tone above A is B flat, lowered by 50 cents.
+//! assert_eq!(retuned.notes[1].note, 70);
+//! assert_eq!(retuned.notes[1].channel, 2);
+//! // -50 cents with bend range of 2 semitones.
+//! assert_eq!(retuned.pitch_bends[1].message().raw_value(), 6144);
+//! ```
+
+use std::{fs, path::Path};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    flatten_midi_notes, to_raw_midi_events, CcShapeKind, MidiEvent,
+    MidiMessage, MidiNoteEvent, NotationMessage, NotationProperty,
+    PitchBendMessage, RawMidiMessage, ReaRsError, ReaperResult, TypedNotation,
+};
+
+/// One degree of Scala scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScalaPitch {
+    Cents(f64),
+    Ratio(u64, u64),
+}
+impl ScalaPitch {
+    pub fn cents(&self) -> f64 {
+        match self {
+            Self::Cents(cents) => *cents,
+            Self::Ratio(num, den) => {
+                1200.0 * (*num as f64 / *den as f64).log2()
+            }
+        }
+    }
+}
+
+/// Iterate over not-comment lines with 1-based line numbers.
+fn scala_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+fn first_token(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Scala scale (`.scl`).
+///
+/// Pitches do not include the implicit `1/1`. The last pitch is the
+/// interval of repetition (usually `2/1`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalaScale {
+    pub description: String,
+    pub pitches: Vec<ScalaPitch>,
+}
+impl ScalaScale {
+    /// Equal temperament of `steps` per octave.
+    pub fn equal_temperament(steps: usize) -> Self {
+        let steps = steps.max(1);
+        Self {
+            description: format!("{} equal divisions of octave", steps),
+            pitches: (1..=steps)
+                .map(|step| match step == steps {
+                    true => ScalaPitch::Ratio(2, 1),
+                    false => {
+                        ScalaPitch::Cents(1200.0 * step as f64 / steps as f64)
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        let mut lines = scala_lines(text);
+        let error = |line, msg: &str| ReaRsError::Parse(line, msg.to_string());
+        let (_, description) =
+            lines.next().ok_or_else(|| error(1, "no description"))?;
+        let (line, count) =
+            lines.next().ok_or_else(|| error(2, "no number of notes"))?;
+        let count = first_token(count)
+            .parse::<usize>()
+            .map_err(|_| error(line, "bad number of notes"))?;
+        // count is not trusted: pitches are checked against it after.
+        let mut pitches = Vec::new();
+        for (line, text) in lines.by_ref().take(count) {
+            let token = first_token(text);
+            let pitch = match token.contains('.') {
+                true => token.parse::<f64>().map(ScalaPitch::Cents).ok(),
+                false => {
+                    let (num, den) =
+                        token.split_once('/').unwrap_or((token, "1"));
+                    match (num.parse::<u64>(), den.parse::<u64>()) {
+                        (Ok(num), Ok(den)) if num > 0 && den > 0 => {
+                            Some(ScalaPitch::Ratio(num, den))
+                        }
+                        _ => None,
+                    }
+                }
+            };
+            pitches.push(pitch.ok_or_else(|| {
+                ReaRsError::Parse(line, format!("bad pitch: '{}'", token))
+            })?);
+        }
+        if pitches.len() != count {
+            return Err(error(
+                text.lines().count(),
+                "less pitches, than declared",
+            ));
+        }
+        Ok(Self {
+            description: description.trim().to_string(),
+            pitches,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> ReaperResult<Self> {
+        let bytes =
+            fs::read(path).map_err(|e| ReaRsError::Io(e.to_string()))?;
+        Self::parse(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Number of degrees in one period.
+    pub fn len(&self) -> usize {
+        self.pitches.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pitches.is_empty()
+    }
+    /// Interval of repetition in cents.
+    pub fn period_cents(&self) -> f64 {
+        self.pitches.last().map(|p| p.cents()).unwrap_or(1200.0)
+    }
+    /// Cents of any (also negative or out of period) scale degree.
+    pub fn degree_cents(&self, degree: i32) -> f64 {
+        if self.is_empty() {
+            return 100.0 * degree as f64;
+        }
+        let len = self.len() as i32;
+        let (period, idx) = (degree.div_euclid(len), degree.rem_euclid(len));
+        let cents = match idx {
+            0 => 0.0,
+            idx => self.pitches[idx as usize - 1].cents(),
+        };
+        period as f64 * self.period_cents() + cents
+    }
+}
+
+/// Scala keyboard mapping (`.kbm`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyboardMapping {
+    /// Notes outside `first_note..=last_note` are not mapped.
+    pub first_note: u8,
+    pub last_note: u8,
+    /// Note, where scale degree 0 is mapped.
+    pub middle_note: u8,
+    /// Note, which has `reference_frequency`.
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    /// Scale degree, that is formal octave of the mapping. If 0 — the
+    /// scale length is used.
+    pub octave_degree: usize,
+    /// Degrees for keys, starting from `middle_note`. None is not mapped
+    /// key. Empty mapping means linear mapping: one key per scale degree.
+    pub mapping: Vec<Option<usize>>,
+}
+impl Default for KeyboardMapping {
+    /// Linear mapping of all keys, with 1/1 at 60 and 440 Hz at 69.
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+impl KeyboardMapping {
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        let lines: Vec<(usize, &str)> = scala_lines(text).collect();
+        let last_line = text.lines().count();
+        let token = |idx: usize, what: &str| -> ReaperResult<(usize, &str)> {
+            let (line, text) = lines.get(idx).ok_or_else(|| {
+                ReaRsError::Parse(last_line, format!("no {}", what))
+            })?;
+            Ok((*line, first_token(text)))
+        };
+        let number = |idx: usize, what: &str| -> ReaperResult<usize> {
+            let (line, token) = token(idx, what)?;
+            token.parse::<usize>().map_err(|_| {
+                ReaRsError::Parse(line, format!("bad {}: '{}'", what, token))
+            })
+        };
+        let note = |value: usize| value.min(127) as u8;
+        let size = number(0, "map size")?;
+        if size > 128 {
+            let (line, _) = token(0, "map size")?;
+            return Err(ReaRsError::Parse(
+                line,
+                format!("map size is more than 128: {}", size),
+            ));
+        }
+        let first_note = note(number(1, "first note")?);
+        let last_note = note(number(2, "last note")?);
+        let middle_note = note(number(3, "middle note")?);
+        let reference_note = note(number(4, "reference note")?);
+        let (line, frequency) = token(5, "reference frequency")?;
+        let reference_frequency = match frequency.parse::<f64>() {
+            Ok(f) if f > 0.0 => f,
+            _ => {
+                return Err(ReaRsError::Parse(
+                    line,
+                    format!("bad reference frequency: '{}'", frequency),
+                ))
+            }
+        };
+        let octave_degree = number(6, "octave degree")?;
+        let mut mapping = Vec::with_capacity(size);
+        // Missing entries at the end are not mapped.
+        for idx in 7..(7 + size).min(lines.len()) {
+            let (line, token) = token(idx, "mapping entry")?;
+            mapping.push(match token {
+                "x" | "X" => None,
+                t => Some(t.parse::<usize>().map_err(|_| {
+                    ReaRsError::Parse(line, format!("bad degree: '{}'", t))
+                })?),
+            });
+        }
+        mapping.resize(size, None);
+        Ok(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> ReaperResult<Self> {
+        let bytes =
+            fs::read(path).map_err(|e| ReaRsError::Io(e.to_string()))?;
+        Self::parse(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Scale degree of note, or None if note is not mapped.
+    pub fn degree(&self, note: u8, scale_len: usize) -> Option<i32> {
+        let offset = note as i32 - self.middle_note as i32;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let size = self.mapping.len() as i32;
+        let octave_degree = match self.octave_degree {
+            0 => scale_len,
+            degree => degree,
+        } as i32;
+        let (octave, idx) = (offset.div_euclid(size), offset.rem_euclid(size));
+        let degree = self.mapping[idx as usize]? as i32;
+        Some(octave * octave_degree + degree)
+    }
+}
+
+/// Scale together with keyboard mapping.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tuning {
+    pub scale: ScalaScale,
+    pub mapping: KeyboardMapping,
+}
+impl Tuning {
+    pub fn new(scale: ScalaScale, mapping: KeyboardMapping) -> Self {
+        Self { scale, mapping }
+    }
+
+    fn note_cents(&self, note: u8) -> Option<f64> {
+        let degree = self.mapping.degree(note, self.scale.len())?;
+        Some(self.scale.degree_cents(degree))
+    }
+
+    /// Frequency of note in Hz, or None if note is not mapped.
+    ///
+    /// If reference note itself is not mapped, its frequency is still used
+    /// as the frequency of the degree, that would be there by linear
+    /// mapping.
+    pub fn frequency(&self, note: u8) -> Option<f64> {
+        let map = &self.mapping;
+        if note < map.first_note || note > map.last_note {
+            return None;
+        }
+        let cents = self.note_cents(note)?;
+        let reference =
+            self.note_cents(map.reference_note).unwrap_or_else(|| {
+                self.scale.degree_cents(
+                    map.reference_note as i32 - map.middle_note as i32,
+                )
+            });
+        Some(
+            map.reference_frequency * 2_f64.powf((cents - reference) / 1200.0),
+        )
+    }
+
+    /// Pitch in 12-TET semitones (A4 = 69 = 440 Hz), may be fractional.
+    pub fn pitch(&self, note: u8) -> Option<f64> {
+        Some(69.0 + 12.0 * (self.frequency(note)? / 440.0).log2())
+    }
+}
+
+/// Settings of [retune_notes].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetuneSettings {
+    /// Pitch bend range of the instrument in semitones.
+    pub pitch_bend_range: f64,
+    /// 1-based channels for rotation.
+    pub channels: Vec<u8>,
+    /// Write notation event with cents offset for every note.
+    pub write_notation: bool,
+}
+impl Default for RetuneSettings {
+    /// Range of 2 semitones, all 16 channels, no notation.
+    fn default() -> Self {
+        Self {
+            pitch_bend_range: 2.0,
+            channels: (1..=16).collect(),
+            write_notation: false,
+        }
+    }
+}
+impl RetuneSettings {
+    pub fn with_pitch_bend_range(mut self, semitones: f64) -> Self {
+        self.pitch_bend_range = semitones;
+        self
+    }
+    pub fn with_channels(
+        mut self,
+        channels: impl IntoIterator<Item = u8>,
+    ) -> Self {
+        self.channels = channels.into_iter().collect();
+        self
+    }
+    pub fn with_notation(mut self, write_notation: bool) -> Self {
+        self.write_notation = write_notation;
+        self
+    }
+}
+
+/// Result of [retune_notes].
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RetunedNotes {
+    /// Mapped notes with new pitch and channel, in the original order.
+    pub notes: Vec<MidiNoteEvent>,
+    /// Pitch bend at the start of every note. Index is the same as of
+    /// note.
+    pub pitch_bends: Vec<MidiEvent<PitchBendMessage>>,
+    /// Notation with cents offset, if requested.
+    pub notation: Vec<MidiEvent<NotationMessage>>,
+    /// Indices of source notes, that are not mapped by tuning (or out of
+    /// MIDI range after retuning).
+    pub unmapped: Vec<usize>,
+    /// Indices of source notes, that are dropped, because their channel
+    /// was stolen at their start.
+    pub stolen: Vec<usize>,
+}
+impl RetunedNotes {
+    /// All events, sorted by position. At the same position note-offs go
+    /// first, then pitch bends and notation, then note-ons.
+    pub fn to_raw_events(&self) -> Vec<MidiEvent<RawMidiMessage>> {
+        let mut events: Vec<(u8, MidiEvent<RawMidiMessage>)> =
+            flatten_midi_notes(self.notes.iter().cloned())
+                .map(|e| match e.message().buf[0] & 0xf0 {
+                    0x90 => (2, e),
+                    _ => (0, e),
+                })
+                .collect();
+        events.extend(
+            to_raw_midi_events(self.pitch_bends.iter().cloned())
+                .chain(to_raw_midi_events(self.notation.iter().cloned()))
+                .map(|e| (1, e)),
+        );
+        events.sort_by_key(|(priority, e)| (e.ppq_position(), *priority));
+        events.into_iter().map(|(_, e)| e).collect()
+    }
+}
+
+fn at_note_start<T: MidiMessage>(
+    note: &MidiNoteEvent,
+    message: T,
+) -> MidiEvent<T> {
+    MidiEvent::new(
+        note.start_in_ppq,
+        note.is_selected,
+        note.is_muted,
+        CcShapeKind::Square,
+        message,
+    )
+}
+
+/// Format cents offset for notation: `+14c`, `-31c`.
+fn cents_text(cents: f64) -> String {
+    format!("{:+.0}c", cents)
+}
+
+/// Convert notes to the nearest 12-TET pitches with pitch bends.
+///
+/// Every note gets a channel from [RetuneSettings::channels]:
+/// - a channel, where notes with the same bend, but not of the same pitch
+///   are sounding, is shared;
+/// - otherwise, a free channel, released the longest time ago;
+/// - otherwise, the channel of the oldest sounding note, which is cut at
+///   the start of the new note. Notes, that become zero-length, are
+///   dropped and listed in [RetunedNotes::stolen].
+pub fn retune_notes(
+    notes: &[MidiNoteEvent],
+    tuning: &Tuning,
+    settings: &RetuneSettings,
+) -> RetunedNotes {
+    let mut result = RetunedNotes::default();
+    let range = settings.pitch_bend_range.max(f64::EPSILON);
+    let mut order: Vec<usize> = (0..notes.len()).collect();
+    order.sort_by_key(|i| (notes[*i].start_in_ppq, notes[*i].note));
+    // (channel, bend, sounding out indices, release position)
+    let mut channels: Vec<(u8, u16, Vec<usize>, u32)> = settings
+        .channels
+        .iter()
+        .map(|ch| (*ch, 8192, Vec::new(), 0))
+        .collect();
+    let mut retuned: Vec<(usize, MidiNoteEvent, u16, f64)> = Vec::new();
+    for idx in order {
+        let note = &notes[idx];
+        let pitch = match tuning.pitch(note.note) {
+            Some(pitch) if (0.0..127.5).contains(&pitch.round()) => pitch,
+            _ => {
+                result.unmapped.push(idx);
+                continue;
+            }
+        };
+        let nearest = pitch.round();
+        let cents = (pitch - nearest) * 100.0;
+        let bend = (8192.0 + (pitch - nearest) / range * 8192.0)
+            .round()
+            .clamp(0.0, 16383.0) as u16;
+        let start = note.start_in_ppq;
+        for (_, _, sounding, release) in channels.iter_mut() {
+            sounding.retain(|i: &usize| {
+                let end = retuned[*i].1.end_in_ppq;
+                if end <= start {
+                    *release = (*release).max(end);
+                }
+                end > start
+            });
+        }
+        let shared = channels.iter().position(|(_, b, sounding, _)| {
+            *b == bend
+                && !sounding.is_empty()
+                && sounding.iter().all(|i| retuned[*i].1.note != nearest as u8)
+        });
+        let free = channels
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, sounding, _))| sounding.is_empty())
+            .min_by_key(|(_, (_, b, _, release))| (*b != bend, *release))
+            .map(|(slot, _)| slot);
+        let slot = match (shared, free) {
+            (Some(slot), _) | (None, Some(slot)) => slot,
+            (None, None) => {
+                let slot = channels
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (_, _, sounding, _))| {
+                        sounding
+                            .iter()
+                            .map(|i| retuned[*i].1.start_in_ppq)
+                            .min()
+                    })
+                    .map(|(slot, _)| slot);
+                let slot = match slot {
+                    Some(slot) => slot,
+                    // No channels given at all.
+                    None => {
+                        result.unmapped.push(idx);
+                        continue;
+                    }
+                };
+                for i in channels[slot].2.drain(..) {
+                    let stolen = &mut retuned[i].1;
+                    stolen.end_in_ppq = stolen.end_in_ppq.min(start);
+                    if stolen.end_in_ppq <= stolen.start_in_ppq {
+                        result.stolen.push(retuned[i].0);
+                    }
+                }
+                slot
+            }
+        };
+        let (channel, channel_bend, sounding, _) = &mut channels[slot];
+        *channel_bend = bend;
+        sounding.push(retuned.len());
+        let mut new_note = note.clone();
+        new_note.note = nearest as u8;
+        new_note.channel = *channel;
+        retuned.push((idx, new_note, bend, cents));
+    }
+    retuned.sort_by_key(|(idx, ..)| *idx);
+    result.unmapped.sort();
+    result.stolen.sort();
+    for (idx, note, bend, cents) in retuned {
+        if result.stolen.binary_search(&idx).is_ok() {
+            continue;
+        }
+        result.pitch_bends.push(at_note_start(
+            &note,
+            PitchBendMessage::new(note.channel, bend),
+        ));
+        if settings.write_notation {
+            result.notation.push(at_note_start(
+                &note,
+                NotationMessage::from(TypedNotation::note(
+                    note.channel,
+                    note.note,
+                    vec![NotationProperty::Text(cents_text(cents))],
+                )),
+            ));
+        }
+        result.notes.push(note);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEANTONE: &str = "! meanquar.scl
+!
+1/4-comma meantone scale. Pietro Aaron's temperament (1523)
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+    fn note(start: u32, end: u32, pitch: u8) -> MidiNoteEvent {
+        MidiNoteEvent::new(start, end, false, false, 1, pitch, 90, 0)
+    }
+
+    #[test]
+    fn test_scala() {
+        let scale = ScalaScale::parse(MEANTONE).unwrap();
+        assert_eq!(
+            scale.description,
+            "1/4-comma meantone scale. Pietro Aaron's temperament (1523)"
+        );
+        assert_eq!(scale.len(), 12);
+        assert_eq!(scale.pitches[3], ScalaPitch::Ratio(5, 4));
+        assert!((scale.degree_cents(4) - 386.3137).abs() < 1e-3);
+        assert_eq!(scale.degree_cents(12), 1200.0);
+        assert!((scale.degree_cents(-8) - (386.3137 - 1200.0)).abs() < 1e-3);
+
+        let et = ScalaScale::equal_temperament(19);
+        assert_eq!(et.len(), 19);
+        assert_eq!(et.period_cents(), 1200.0);
+
+        let err = |text| match ScalaScale::parse(text) {
+            Err(ReaRsError::Parse(line, _)) => line,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(err("desc\n2\n100.0\nabc\n"), 4);
+        assert_eq!(err("desc\nmany\n"), 2);
+        assert_eq!(err("desc\n3\n100.0\n2/1\n"), 4);
+        assert_eq!(err("desc\n1\n-3/2\n"), 3);
+    }
+
+    #[test]
+    fn test_kbm() {
+        // White keys only map 7-note scale, A = 432 Hz.
+        let kbm = KeyboardMapping::parse(
+            "! white.kbm
+12
+0
+127
+60
+69
+432.0
+7
+! mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+",
+        )
+        .unwrap();
+        assert_eq!(kbm.mapping.len(), 12);
+        assert_eq!(kbm.degree(61, 7), None);
+        assert_eq!(kbm.degree(72, 7), Some(7));
+        assert_eq!(kbm.degree(59, 7), Some(-1));
+        let scale = ScalaScale::parse(
+            "pythagorean\n7\n9/8\n81/64\n4/3\n3/2\n27/16\n243/128\n2/1",
+        )
+        .unwrap();
+        let tuning = Tuning::new(scale, kbm);
+        assert_eq!(tuning.frequency(69), Some(432.0));
+        assert_eq!(tuning.frequency(70), None);
+        let c = tuning.frequency(60).unwrap();
+        assert!((c - 432.0 * 16.0 / 27.0).abs() < 1e-9);
+        assert!((tuning.frequency(81).unwrap() - 864.0).abs() < 1e-9);
+        assert!(
+            KeyboardMapping::parse("12\n0\n127\n60\n69\nabc\n12\n").is_err()
+        );
+        assert!(KeyboardMapping::parse(
+            "4000000000\n0\n127\n60\n69\n440.0\n12\n"
+        )
+        .is_err());
+        assert!(ScalaScale::parse("huge\n4000000000\n3/2\n").is_err());
+    }
+
+    #[test]
+    fn test_retune() {
+        let tuning = Tuning::new(
+            ScalaScale::parse(MEANTONE).unwrap(),
+            KeyboardMapping::default(),
+        );
+        let notes = vec![
+            note(0, 100, 69),
+            note(0, 100, 64),
+            note(0, 100, 76),
+            note(50, 150, 60),
+            note(200, 300, 64),
+            note(200, 300, 127),
+        ];
+        let settings = RetuneSettings::default()
+            .with_channels([1, 2])
+            .with_notation(true);
+        let retuned = retune_notes(&notes, &tuning, &settings);
+        assert_eq!(retuned.unmapped, Vec::<usize>::new());
+        let result: Vec<_> = retuned
+            .notes
+            .iter()
+            .map(|n| (n.note, n.channel, n.end_in_ppq))
+            .collect();
+        // E-s share bend and channel, all channels are busy at 50, so the
+        // channel of the oldest notes is stolen.
+        assert_eq!(
+            result,
+            vec![
+                (69, 2, 100),
+                (64, 1, 50),
+                (76, 1, 50),
+                (60, 1, 150),
+                (64, 2, 300),
+                (127, 1, 300),
+            ]
+        );
+        assert_eq!(retuned.pitch_bends[0].message().raw_value(), 8192);
+        let e_bend = retuned.pitch_bends[1].message().raw_value();
+        assert_eq!(e_bend, retuned.pitch_bends[2].message().raw_value());
+        // E is 386.3 cents from C, C is 10.3 cents above 12-TET.
+        let e_cents = (e_bend as f64 - 8192.0) / 8192.0 * 200.0;
+        assert!((e_cents + 3.4).abs() < 0.1, "{}", e_cents);
+        assert_eq!(
+            retuned.notation[1]
+                .message()
+                .typed_notation()
+                .unwrap()
+                .text(),
+            Some("-3c")
+        );
+        let raw = retuned.to_raw_events();
+        assert_eq!(raw.len(), 6 * 2 + 6 * 2);
+        assert_eq!(raw[0].message().buf[0] & 0xf0, 0xe0);
+        assert_eq!(raw.last().unwrap().ppq_position(), 300);
+    }
+
+    #[test]
+    fn test_retune_stealing() {
+        let tuning = Tuning::new(
+            ScalaScale::equal_temperament(24),
+            KeyboardMapping::default(),
+        );
+        // Quarter-tone above needs another bend, so the only channel is
+        // stolen at the start of the first note.
+        let notes = vec![note(0, 100, 60), note(0, 100, 61), note(50, 80, 62)];
+        let retuned = retune_notes(
+            &notes,
+            &tuning,
+            &RetuneSettings::default().with_channels([1]),
+        );
+        assert_eq!(retuned.stolen, vec![0]);
+        let result: Vec<_> = retuned
+            .notes
+            .iter()
+            .map(|n| (n.start_in_ppq, n.end_in_ppq, n.channel))
+            .collect();
+        assert_eq!(result, vec![(0, 50, 1), (50, 80, 1)]);
+        assert_eq!(retuned.pitch_bends.len(), 2);
+        assert_eq!(retuned.to_raw_events().len(), 2 * 2 + 2);
+    }
+
+    #[test]
+    fn test_retune_same_pitch() {
+        let tuning = Tuning::new(
+            ScalaScale::equal_temperament(12),
+            KeyboardMapping::default(),
+        );
+        let notes = vec![note(0, 100, 60), note(10, 50, 60), note(20, 30, 64)];
+        let retuned = retune_notes(
+            &notes,
+            &tuning,
+            &RetuneSettings::default().with_channels([1, 2]),
+        );
+        let channels: Vec<_> =
+            retuned.notes.iter().map(|n| n.channel).collect();
+        // Overlapping notes of the same pitch never share channel.
+        assert_eq!(channels, vec![1, 2, 1]);
+        assert!(retuned.stolen.is_empty());
+    }
+
+    #[test]
+    fn test_no_channels() {
+        let tuning = Tuning::new(
+            ScalaScale::equal_temperament(24),
+            KeyboardMapping::default(),
+        );
+        let notes = vec![note(0, 10, 60), note(0, 10, 61)];
+        let retuned = retune_notes(
+            &notes,
+            &tuning,
+            &RetuneSettings::default().with_channels([]),
+        );
+        assert_eq!(retuned.unmapped, vec![0, 1]);
+        assert!(retuned.notes.is_empty());
+    }
+}
@@ -101,6 +101,49 @@ pub enum Direction {
     Left,
 }
 
+/// Transport state, as returned by `GetPlayStateEx`.
+#[repr(i32)]
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, IntEnum, Serialize, Deserialize,
+)]
+pub enum PlayState {
+    Stopped = 0,
+    Playing = 1,
+    Paused = 2,
+    Recording = 5,
+    RecordingPaused = 6,
+}
+impl PlayState {
+    /// Decode state bits: &1 playing, &2 paused, &4 recording.
+    ///
+    /// ```
+    /// use rea_rs::PlayState;
+    ///
+    /// assert_eq!(PlayState::from_raw(4), PlayState::Recording);
+    /// assert_eq!(PlayState::from_raw(6), PlayState::RecordingPaused);
+    /// assert_eq!(PlayState::from_raw(3), PlayState::Paused);
+    /// ```
+    pub fn from_raw(raw: i32) -> Self {
+        match (raw & 4 != 0, raw & 2 != 0, raw & 1 != 0) {
+            (true, true, _) => Self::RecordingPaused,
+            (true, false, _) => Self::Recording,
+            (false, true, _) => Self::Paused,
+            (false, false, true) => Self::Playing,
+            (false, false, false) => Self::Stopped,
+        }
+    }
+}
+
+/// Project record mode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RecordMode {
+    Normal,
+    /// Auto-punch within time selection.
+    TimeSelectionPunch,
+    /// Auto-punch within selected items.
+    ItemPunch,
+}
+
 #[repr(i32)]
 #[derive(
     Clone, Copy, Debug, Eq, PartialEq, IntEnum, Serialize, Deserialize,
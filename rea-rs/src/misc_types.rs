@@ -771,6 +771,54 @@ impl GUID {
             }
         }
     }
+    /// Parse `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}` without REAPER.
+    ///
+    /// Used for reading project files offline.
+    pub fn parse_braced(value: &str) -> ReaperResult<Self> {
+        let err = || ReaRsError::InvalidObject("Can not convert to GUID");
+        let inner = value
+            .trim()
+            .strip_prefix('{')
+            .and_then(|v| v.strip_suffix('}'))
+            .ok_or_else(err)?;
+        let groups: Vec<&str> = inner.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+        if lengths != [8, 4, 4, 4, 12]
+            || !inner.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+        {
+            return Err(err());
+        }
+        let hex = |s: &str| u64::from_str_radix(s, 16).unwrap_or_default();
+        let tail = format!("{}{}", groups[3], groups[4]);
+        let mut data4 = [0_u8; 8];
+        for (idx, byte) in data4.iter_mut().enumerate() {
+            *byte = hex(&tail[idx * 2..idx * 2 + 2]) as u8;
+        }
+        Ok(Self {
+            raw: raw::GUID {
+                Data1: hex(groups[0]) as u32,
+                Data2: hex(groups[1]) as u16,
+                Data3: hex(groups[2]) as u16,
+                Data4: data4,
+            },
+        })
+    }
+    /// Format as `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}` without REAPER.
+    pub fn to_braced_string(&self) -> String {
+        let d4 = self.raw.Data4;
+        format!(
+            "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{}}}",
+            self.raw.Data1,
+            self.raw.Data2,
+            self.raw.Data3,
+            d4[0],
+            d4[1],
+            d4[2..]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>()
+        )
+    }
     pub fn new() -> Self {
         unsafe {
             let mut ptr = MaybeUninit::zeroed();
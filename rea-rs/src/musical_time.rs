@@ -0,0 +1,561 @@
+//! Musical time: bars, beats and ticks.
+//!
+//! Beat is a note of time signature denominator (e.g. eighth in 6/8), and
+//! it is divided into [TICKS_PER_BEAT] ticks. Bars and beats are 1-based,
+//! as in REAPER ruler: `1.1.0` is the project start.
+//!
+//! Conversions to and from [Position] go through [TempoMap], which is
+//! implemented by [Project] and by [OfflineTempoMap], that can be built
+//! from [RppProject] or by hand.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{
+//!     MusicalDuration, MusicalPosition, OfflineTempoMap, Position,
+//!     RppTempoMarker, TimeSignature,
+//! };
+//!
+//! let mut waltz = RppTempoMarker::new(4.0, 120.0);
+//! waltz.time_signature = Some(TimeSignature::new(3, 4));
+//! let map = OfflineTempoMap::new(120.0, TimeSignature::new(4, 4), [waltz]);
+//!
+//! let position: MusicalPosition = "3.2.480".parse().unwrap();
+//! assert_eq!(position.to_position(&map), Position::from(4.75));
+//!
+//! let next = position.offset(MusicalDuration::new(1, 2, 480), &map);
+//! assert_eq!(next.to_string(), "5.2.0");
+//! ```
+
+use std::{
+    fmt::Display,
+    mem::MaybeUninit,
+    ops::{Add, Mul, Sub},
+    str::FromStr,
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    Position, Project, ReaRsError, Reaper, RppProject, RppTempoMarker,
+    TimeSignature,
+};
+
+/// Resolution of [MusicalPosition] and [MusicalDuration].
+pub const TICKS_PER_BEAT: u32 = 960;
+
+/// Small tolerance for floating-point rounding on bar boundaries.
+const EPSILON: f64 = 1e-9;
+
+/// Tempo and time signature of the project timeline.
+pub trait TempoMap {
+    fn quarters_at(&self, position: Position) -> f64;
+    fn position_at_quarters(&self, quarters: f64) -> Position;
+    /// 0-based index of the measure, containing given quarter.
+    fn measure_at_quarters(&self, quarters: f64) -> u32;
+    /// Start (in quarters) and time signature of the 0-based measure.
+    fn measure_start(&self, index: u32) -> (f64, TimeSignature);
+}
+
+impl TempoMap for Project {
+    fn quarters_at(&self, position: Position) -> f64 {
+        position.as_quarters(self)
+    }
+    fn position_at_quarters(&self, quarters: f64) -> Position {
+        Position::from_quarters(quarters.max(0.0), self)
+    }
+    fn measure_at_quarters(&self, quarters: f64) -> u32 {
+        let (mut start, mut end) =
+            (MaybeUninit::zeroed(), MaybeUninit::zeroed());
+        let measure = unsafe {
+            Reaper::get().low().TimeMap_QNToMeasures(
+                self.context().to_raw(),
+                quarters + EPSILON,
+                start.as_mut_ptr(),
+                end.as_mut_ptr(),
+            )
+        };
+        (measure - 1).max(0) as u32
+    }
+    fn measure_start(&self, index: u32) -> (f64, TimeSignature) {
+        let (mut start, mut end, mut num, mut denom, mut tempo) = (
+            MaybeUninit::zeroed(),
+            MaybeUninit::zeroed(),
+            MaybeUninit::zeroed(),
+            MaybeUninit::zeroed(),
+            MaybeUninit::zeroed(),
+        );
+        unsafe {
+            Reaper::get().low().TimeMap_GetMeasureInfo(
+                self.context().to_raw(),
+                index as i32,
+                start.as_mut_ptr(),
+                end.as_mut_ptr(),
+                num.as_mut_ptr(),
+                denom.as_mut_ptr(),
+                tempo.as_mut_ptr(),
+            );
+            (
+                start.assume_init(),
+                TimeSignature::new(
+                    num.assume_init() as u32,
+                    denom.assume_init() as u32,
+                ),
+            )
+        }
+    }
+}
+
+/// Position in bars, beats and ticks.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct MusicalPosition {
+    /// 1-based.
+    pub bar: u32,
+    /// 1-based, in notes of time signature denominator.
+    pub beat: u32,
+    pub ticks: u32,
+}
+impl MusicalPosition {
+    pub fn new(bar: u32, beat: u32, ticks: u32) -> Self {
+        Self { bar, beat, ticks }
+    }
+    pub fn from_position(position: Position, map: &impl TempoMap) -> Self {
+        Self::from_quarters(map.quarters_at(position), map)
+    }
+    pub fn to_position(&self, map: &impl TempoMap) -> Position {
+        map.position_at_quarters(self.as_quarters(map))
+    }
+    /// Normalized position at project quarter.
+    pub fn from_quarters(quarters: f64, map: &impl TempoMap) -> Self {
+        let quarters = quarters.max(0.0);
+        let measure = map.measure_at_quarters(quarters);
+        let (start, sig) = map.measure_start(measure);
+        let beats = (quarters - start).max(0.0) * beat_per_quarter(sig);
+        let mut beat = beats.floor() as u32;
+        let mut ticks =
+            ((beats - beat as f64) * TICKS_PER_BEAT as f64).round() as u32;
+        if ticks >= TICKS_PER_BEAT {
+            beat += 1;
+            ticks -= TICKS_PER_BEAT;
+        }
+        if beat >= sig.numerator {
+            return Self::new(measure + 2, 1, ticks);
+        }
+        Self::new(measure + 1, beat + 1, ticks)
+    }
+    /// Project quarter of position.
+    ///
+    /// Beats and ticks may overflow the bar: they are counted in the notes
+    /// of the bar time signature.
+    pub fn as_quarters(&self, map: &impl TempoMap) -> f64 {
+        let (start, sig) = map.measure_start(self.bar.max(1) - 1);
+        let beats = self.beat.max(1) as f64 - 1.0
+            + self.ticks as f64 / TICKS_PER_BEAT as f64;
+        start + beats / beat_per_quarter(sig)
+    }
+    /// Position moved forward by duration and normalized.
+    pub fn offset(
+        self,
+        duration: MusicalDuration,
+        map: &impl TempoMap,
+    ) -> Self {
+        let ticks = MusicalDuration::new(0, 0, self.ticks) + duration;
+        let moved = Self::new(
+            self.bar + duration.bars,
+            self.beat + ticks.beats,
+            ticks.ticks,
+        );
+        Self::from_quarters(moved.as_quarters(map), map)
+    }
+}
+impl Default for MusicalPosition {
+    fn default() -> Self {
+        Self::new(1, 1, 0)
+    }
+}
+impl Display for MusicalPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.bar, self.beat, self.ticks)
+    }
+}
+impl FromStr for MusicalPosition {
+    type Err = ReaRsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (bar, beat, ticks) = parse_triple(s)
+            .ok_or(ReaRsError::InvalidObject("expected \"bar.beat.ticks\""))?;
+        if bar == 0 || beat == 0 {
+            return Err(ReaRsError::InvalidObject("bar and beat are 1-based"));
+        }
+        Ok(Self::new(bar, beat, ticks))
+    }
+}
+
+/// Length in bars, beats and ticks.
+///
+/// Ticks are kept below [TICKS_PER_BEAT], but beats are not folded to
+/// bars, as bar length depends on time signature.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+pub struct MusicalDuration {
+    pub bars: u32,
+    pub beats: u32,
+    pub ticks: u32,
+}
+impl MusicalDuration {
+    pub fn new(bars: u32, beats: u32, ticks: u32) -> Self {
+        Self::from_total(
+            bars,
+            beats as u64 * TICKS_PER_BEAT as u64 + ticks as u64,
+        )
+    }
+    fn from_total(bars: u32, ticks: u64) -> Self {
+        Self {
+            bars,
+            beats: (ticks / TICKS_PER_BEAT as u64) as u32,
+            ticks: (ticks % TICKS_PER_BEAT as u64) as u32,
+        }
+    }
+    /// Beats and ticks, as ticks.
+    pub fn total_ticks(&self) -> u64 {
+        self.beats as u64 * TICKS_PER_BEAT as u64 + self.ticks as u64
+    }
+    /// Duration between positions. None if end is before start.
+    ///
+    /// Counts whole bars first, so the rest is in beats of the last bar.
+    pub fn between(
+        start: MusicalPosition,
+        end: MusicalPosition,
+        map: &impl TempoMap,
+    ) -> Option<Self> {
+        if end < start {
+            return None;
+        }
+        let mut bars = end.bar - start.bar;
+        let mut from =
+            MusicalPosition::new(start.bar + bars, start.beat, start.ticks);
+        let end_quarters = end.as_quarters(map);
+        while bars > 0 && from.as_quarters(map) > end_quarters + EPSILON {
+            bars -= 1;
+            from.bar -= 1;
+        }
+        let (_, sig) = map.measure_start(end.bar.max(1) - 1);
+        let rest = (end_quarters - from.as_quarters(map))
+            * beat_per_quarter(sig)
+            * TICKS_PER_BEAT as f64;
+        Some(Self::from_total(bars, rest.round().max(0.0) as u64))
+    }
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        Some(Self::from_total(
+            self.bars.checked_sub(rhs.bars)?,
+            self.total_ticks().checked_sub(rhs.total_ticks())?,
+        ))
+    }
+}
+impl Add for MusicalDuration {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::from_total(
+            self.bars + rhs.bars,
+            self.total_ticks() + rhs.total_ticks(),
+        )
+    }
+}
+impl Sub for MusicalDuration {
+    type Output = Self;
+    /// # Panics
+    ///
+    /// If rhs is longer. Use [MusicalDuration::checked_sub] to avoid.
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs)
+            .expect("musical duration can not be negative")
+    }
+}
+impl Mul<u32> for MusicalDuration {
+    type Output = Self;
+    fn mul(self, rhs: u32) -> Self::Output {
+        Self::from_total(self.bars * rhs, self.total_ticks() * rhs as u64)
+    }
+}
+impl Display for MusicalDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.bars, self.beats, self.ticks)
+    }
+}
+impl FromStr for MusicalDuration {
+    type Err = ReaRsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (bars, beats, ticks) = parse_triple(s).ok_or(
+            ReaRsError::InvalidObject("expected \"bars.beats.ticks\""),
+        )?;
+        Ok(Self::new(bars, beats, ticks))
+    }
+}
+
+fn parse_triple(text: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = text.trim().split('.').map(|p| p.parse::<u32>());
+    let result = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next().unwrap_or(Ok(0)).ok()?,
+    );
+    match parts.next() {
+        None => Some(result),
+        Some(_) => None,
+    }
+}
+
+fn beat_per_quarter(sig: TimeSignature) -> f64 {
+    sig.denominator as f64 / 4.0
+}
+
+/// Tempo map, that does not need running REAPER.
+///
+/// Linear tempo markers change tempo linearly in time. Time signature
+/// marker starts a new bar; if it is placed inside a bar, the bar is
+/// shortened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfflineTempoMap {
+    /// Tempo segments: start time, start quarter, start bpm, end bpm and
+    /// length in seconds (None for the last one).
+    segments: Vec<(f64, f64, f64, f64, Option<f64>)>,
+    /// Time signature changes: start quarter, first measure index.
+    measures: Vec<(f64, u32, TimeSignature)>,
+}
+impl OfflineTempoMap {
+    /// Map from project tempo and time signature and tempo markers.
+    pub fn new(
+        bpm: f64,
+        time_signature: TimeSignature,
+        markers: impl IntoIterator<Item = RppTempoMarker>,
+    ) -> Self {
+        let mut first = RppTempoMarker::new(0.0, bpm);
+        first.time_signature = Some(time_signature);
+        let mut markers: Vec<RppTempoMarker> = markers.into_iter().collect();
+        markers.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        if markers.first().map(|m| m.position) != Some(first.position) {
+            markers.insert(0, first);
+        } else if markers[0].time_signature.is_none() {
+            markers[0].time_signature = Some(time_signature);
+        }
+
+        let mut segments = Vec::new();
+        let mut quarters = 0.0;
+        for (idx, marker) in markers.iter().enumerate() {
+            let start: f64 = marker.position.into();
+            let (end_bpm, length) = match markers.get(idx + 1) {
+                None => (marker.bpm, None),
+                Some(next) => {
+                    let end: f64 = next.position.into();
+                    let end_bpm = match marker.linear {
+                        true => next.bpm,
+                        false => marker.bpm,
+                    };
+                    (end_bpm, Some(end - start))
+                }
+            };
+            segments.push((start, quarters, marker.bpm, end_bpm, length));
+            if let Some(length) = length {
+                quarters += length * (marker.bpm + end_bpm) / 120.0;
+            }
+        }
+
+        let mut map = Self {
+            segments,
+            measures: Vec::new(),
+        };
+        for marker in markers.iter() {
+            let Some(sig) = marker.time_signature else {
+                continue;
+            };
+            let quarters = map.quarters_at(marker.position);
+            let index = match map.measures.last() {
+                None => 0,
+                Some(&(start, index, prev)) => {
+                    let bars = (quarters - start) / measure_quarters(prev);
+                    index + (bars - EPSILON).ceil().max(0.0) as u32
+                }
+            };
+            map.measures.push((quarters, index, sig));
+        }
+        map
+    }
+}
+impl From<&RppProject> for OfflineTempoMap {
+    fn from(value: &RppProject) -> Self {
+        Self::new(
+            value.tempo,
+            value.time_signature,
+            value.tempo_markers.iter().cloned(),
+        )
+    }
+}
+impl TempoMap for OfflineTempoMap {
+    fn quarters_at(&self, position: Position) -> f64 {
+        let time: f64 = position.into();
+        let (start, quarters, bpm, end_bpm, length) = *self
+            .segments
+            .iter()
+            .rev()
+            .find(|s| s.0 <= time)
+            .unwrap_or(&self.segments[0]);
+        let elapsed = time - start;
+        let slope = match length {
+            Some(length) if length > 0.0 => (end_bpm - bpm) / length,
+            _ => 0.0,
+        };
+        quarters + (bpm * elapsed + slope * elapsed * elapsed / 2.0) / 60.0
+    }
+    fn position_at_quarters(&self, quarters: f64) -> Position {
+        let (start, start_quarters, bpm, end_bpm, length) = *self
+            .segments
+            .iter()
+            .rev()
+            .find(|s| s.1 <= quarters)
+            .unwrap_or(&self.segments[0]);
+        let beats = (quarters - start_quarters) * 60.0;
+        let slope = match length {
+            Some(length) if length > 0.0 => (end_bpm - bpm) / length,
+            _ => 0.0,
+        };
+        let elapsed = match slope.abs() < EPSILON {
+            true => beats / bpm,
+            false => ((bpm * bpm + 2.0 * slope * beats).sqrt() - bpm) / slope,
+        };
+        Position::from((start + elapsed).max(0.0))
+    }
+    fn measure_at_quarters(&self, quarters: f64) -> u32 {
+        let (start, index, sig) = *self
+            .measures
+            .iter()
+            .rev()
+            .find(|m| m.0 <= quarters + EPSILON)
+            .unwrap_or(&self.measures[0]);
+        let bars = (quarters - start + EPSILON) / measure_quarters(sig);
+        index + bars.floor().max(0.0) as u32
+    }
+    fn measure_start(&self, index: u32) -> (f64, TimeSignature) {
+        let (start, first, sig) = *self
+            .measures
+            .iter()
+            .rev()
+            .find(|m| m.1 <= index)
+            .unwrap_or(&self.measures[0]);
+        let start = start + (index - first) as f64 * measure_quarters(sig);
+        (start, sig)
+    }
+}
+
+fn measure_quarters(sig: TimeSignature) -> f64 {
+    sig.numerator as f64 / beat_per_quarter(sig)
+}
+
+impl Project {
+    /// Musical position (bars, beats, ticks) by project tempo map.
+    pub fn musical_position(&self, position: Position) -> MusicalPosition {
+        MusicalPosition::from_position(position, self)
+    }
+    /// Time position of bars, beats and ticks by project tempo map.
+    pub fn position_from_musical(
+        &self,
+        position: MusicalPosition,
+    ) -> Position {
+        position.to_position(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> OfflineTempoMap {
+        let mut ramp = RppTempoMarker::new(4.0, 120.0);
+        ramp.linear = true;
+        let mut waltz = RppTempoMarker::new(6.0, 60.0);
+        waltz.time_signature = Some(TimeSignature::new(6, 8));
+        OfflineTempoMap::new(120.0, TimeSignature::new(4, 4), [ramp, waltz])
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let position: MusicalPosition = "12.3.240".parse().unwrap();
+        assert_eq!(position, MusicalPosition::new(12, 3, 240));
+        assert_eq!(position.to_string(), "12.3.240");
+        assert_eq!("2.1".parse::<MusicalPosition>().unwrap().ticks, 0);
+        assert!("0.1.0".parse::<MusicalPosition>().is_err());
+        assert!("1.1.0.0".parse::<MusicalPosition>().is_err());
+        assert!("a.b.c".parse::<MusicalDuration>().is_err());
+        let duration: MusicalDuration = "1.0.1920".parse().unwrap();
+        assert_eq!(duration, MusicalDuration::new(1, 2, 0));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = MusicalDuration::new(1, 3, 600);
+        let b = MusicalDuration::new(0, 1, 480);
+        assert_eq!(a + b, MusicalDuration::new(1, 5, 120));
+        assert_eq!(a - b, MusicalDuration::new(1, 2, 120));
+        assert_eq!(b * 3, MusicalDuration::new(0, 4, 480));
+        assert_eq!(b.checked_sub(a), None);
+    }
+
+    #[test]
+    fn test_tempo_ramp() {
+        let map = map();
+        // 4 s at 120 bpm: 8 quarters, ramp 120 → 60 over 2 s: 3 quarters.
+        assert_eq!(map.quarters_at(Position::from(4.0)), 8.0);
+        assert_eq!(map.quarters_at(Position::from(6.0)), 11.0);
+        assert_eq!(map.position_at_quarters(11.0), Position::from(6.0));
+        let middle = map.position_at_quarters(9.75);
+        assert!((map.quarters_at(middle) - 9.75).abs() < 1e-5);
+        // after the last marker tempo is constant.
+        assert_eq!(map.quarters_at(Position::from(8.0)), 13.0);
+    }
+
+    #[test]
+    fn test_bars_and_beats() {
+        let map = map();
+        let at = |secs: f64| MusicalPosition::from_position(secs.into(), &map);
+        assert_eq!(at(0.0), MusicalPosition::new(1, 1, 0));
+        assert_eq!(at(1.25), MusicalPosition::new(1, 3, 480));
+        assert_eq!(at(4.0), MusicalPosition::new(3, 1, 0));
+        // 6/8 marker at quarter 11 shortens bar 3, so 6/8 starts at bar 4.
+        assert_eq!(at(6.0), MusicalPosition::new(4, 1, 0));
+        // 60 bpm: eighth is 0.5 s.
+        assert_eq!(at(7.25), MusicalPosition::new(4, 3, 480));
+        assert_eq!(at(9.0), MusicalPosition::new(5, 1, 0));
+
+        let position = MusicalPosition::new(4, 3, 480);
+        assert_eq!(position.to_position(&map), Position::from(7.25));
+        let next = position.offset(MusicalDuration::new(1, 4, 0), &map);
+        assert_eq!(next, MusicalPosition::new(6, 1, 480));
+        let duration = MusicalDuration::between(position, next, &map).unwrap();
+        assert_eq!(duration, MusicalDuration::new(1, 4, 0));
+
+        // bar 0 is read as bar 1, as by as_quarters.
+        let zero = MusicalPosition::new(0, 0, 0);
+        let duration = MusicalDuration::between(zero, zero, &map);
+        assert_eq!(duration, Some(MusicalDuration::new(0, 0, 0)));
+    }
+}
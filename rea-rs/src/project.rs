@@ -6,8 +6,9 @@ use crate::{
         WithNull,
     },
     Color, CommandId, Immutable, Item, MarkerRegionInfo, MarkerRegionIterator,
-    Mutable, PlayRate, Position, ProjectContext, ReaRsError, Reaper,
-    TimeRange, TimeRangeKind, TimeSignature, Track, UndoFlags,
+    Mutable, PlayRate, PlayState, Position, ProjectContext, ReaRsError,
+    Reaper, ReaperResult, RecordMode, TimeRange, TimeRangeKind,
+    TimeSignature, Track, UndoFlags,
 };
 use c_str_macro::c_str;
 use int_enum::IntEnum;
@@ -22,6 +23,19 @@ use self::project_info::{
     BoundsMode, RenderSettings, RenderTail, RenderTailFlags,
 };
 
+/// Action "Record: Set record mode to normal".
+const RECORD_MODE_NORMAL_ACTION: u32 = 40252;
+/// Action "Record: Set record mode to time selection auto-punch".
+const RECORD_MODE_TIME_SELECTION_ACTION: u32 = 40076;
+/// Action "Record: Set record mode to selected item auto-punch".
+const RECORD_MODE_ITEMS_ACTION: u32 = 40253;
+/// Action "Pre-roll: Toggle pre-roll on play".
+const PREROLL_PLAY_ACTION: u32 = 41819;
+/// Action "Pre-roll: Toggle pre-roll on record".
+const PREROLL_RECORD_ACTION: u32 = 41818;
+/// Action "Options: Toggle metronome".
+const METRONOME_ACTION: u32 = 40364;
+
 #[derive(Debug, PartialEq)]
 pub struct Project {
     context: ProjectContext,
@@ -216,6 +230,140 @@ impl<'a> Project {
         }
     }
 
+    /// Current transport state of the project.
+    ///
+    /// Unknown state bits are ignored.
+    pub fn play_state(&self) -> PlayState {
+        let state = unsafe {
+            Reaper::get().low().GetPlayStateEx(self.context().to_raw())
+        };
+        PlayState::from_raw(state)
+    }
+
+    /// Start playback from position, or seek there, if already playing.
+    ///
+    /// If project is paused or recording, transport is stopped first.
+    pub fn play_from(&mut self, position: Position) {
+        match self.play_state() {
+            PlayState::Playing => {
+                self.set_cursor_position(position, false, true)
+            }
+            _ => {
+                self.stop();
+                self.set_cursor_position(position, false, false);
+                self.play();
+            }
+        }
+    }
+
+    /// Master play rate, not taking play rate envelope into account.
+    ///
+    /// See [Project::get_play_rate] for the rate at the given position.
+    pub fn play_rate(&self) -> PlayRate {
+        unsafe {
+            PlayRate::from(
+                Reaper::get()
+                    .low()
+                    .Master_GetPlayRate(self.context().to_raw()),
+            )
+        }
+    }
+    /// Set master play rate.
+    ///
+    /// # Note
+    ///
+    /// Reaper changes play rate only of the current project, so project is
+    /// made current for the time of the call.
+    pub fn set_play_rate(&mut self, rate: PlayRate) -> ReaperResult<()> {
+        self.with_current_project(|| -> anyhow::Result<()> {
+            Reaper::get().low().CSurf_OnPlayRateChange(rate.into());
+            Ok(())
+        })
+        .map_err(|_| {
+            ReaRsError::UnsuccessfulOperation("Can not set play rate.")
+        })
+    }
+
+    /// Record mode, read from the state of record mode actions.
+    ///
+    /// # Note
+    ///
+    /// Reaper reports action state for the current project, see
+    /// [Project::toggle_state].
+    pub fn record_mode(&self) -> RecordMode {
+        if self.toggle_state(RECORD_MODE_TIME_SELECTION_ACTION) {
+            RecordMode::TimeSelectionPunch
+        } else if self.toggle_state(RECORD_MODE_ITEMS_ACTION) {
+            RecordMode::ItemPunch
+        } else {
+            RecordMode::Normal
+        }
+    }
+    /// Set record mode by performing the corresponding action.
+    pub fn set_record_mode(&mut self, mode: RecordMode) {
+        let action = match mode {
+            RecordMode::Normal => RECORD_MODE_NORMAL_ACTION,
+            RecordMode::TimeSelectionPunch => {
+                RECORD_MODE_TIME_SELECTION_ACTION
+            }
+            RecordMode::ItemPunch => RECORD_MODE_ITEMS_ACTION,
+        };
+        Reaper::get().perform_action(CommandId::new(action), 0, Some(self));
+    }
+
+    /// Whether pre-roll is used before playback.
+    ///
+    /// Read from the current project, see [Project::toggle_state].
+    pub fn is_play_preroll_enabled(&self) -> bool {
+        self.toggle_state(PREROLL_PLAY_ACTION)
+    }
+    /// Enable or disable pre-roll before playback by toggle action.
+    pub fn set_play_preroll_enabled(&mut self, enabled: bool) {
+        self.set_toggle_state(PREROLL_PLAY_ACTION, enabled)
+    }
+    /// Whether pre-roll is used before recording.
+    ///
+    /// Read from the current project, see [Project::toggle_state].
+    pub fn is_record_preroll_enabled(&self) -> bool {
+        self.toggle_state(PREROLL_RECORD_ACTION)
+    }
+    /// Enable or disable pre-roll before recording by toggle action.
+    pub fn set_record_preroll_enabled(&mut self, enabled: bool) {
+        self.set_toggle_state(PREROLL_RECORD_ACTION, enabled)
+    }
+
+    /// Whether metronome is enabled.
+    ///
+    /// Read from the current project, see [Project::toggle_state].
+    pub fn is_metronome_enabled(&self) -> bool {
+        self.toggle_state(METRONOME_ACTION)
+    }
+    /// Enable or disable metronome by toggle action.
+    pub fn set_metronome_enabled(&mut self, enabled: bool) {
+        self.set_toggle_state(METRONOME_ACTION, enabled)
+    }
+
+    /// State of toggle action.
+    ///
+    /// # Note
+    ///
+    /// Reaper reports action state only for the current project, so `self`
+    /// is not taken into account: the result is about the current project,
+    /// even if `self` is another one.
+    pub(crate) fn toggle_state(&self, action: u32) -> bool {
+        Reaper::get().low().GetToggleCommandState(action as i32) == 1
+    }
+    /// Perform toggle action, if its state differs.
+    pub(crate) fn set_toggle_state(&mut self, action: u32, state: bool) {
+        if self.toggle_state(action) != state {
+            Reaper::get().perform_action(
+                CommandId::new(action),
+                0,
+                Some(self),
+            );
+        }
+    }
+
     pub fn length(&self) -> Duration {
         unsafe {
             Duration::from_secs_f64(
@@ -237,6 +385,18 @@ impl<'a> Project {
         TimeRange::new(self, TimeRangeKind::TimeSelection)
     }
 
+    /// Start and end of loop points, that are used by repeat.
+    ///
+    /// Shortcut for [Project::get_loop_selection].
+    pub fn loop_points(&self) -> (Position, Position) {
+        self.get_loop_selection().get()
+    }
+    /// Set loop points, that are used by repeat.
+    pub fn set_loop_points(&mut self, start: Position, end: Position) {
+        self.get_loop_selection().set(start, end)
+    }
+
+    /// Whether repeat is enabled.
     pub fn is_loop_enabled(&self) -> bool {
         unsafe {
             Reaper::get()
@@ -0,0 +1,1490 @@
+//! Offline model of `.rpp` project files.
+//!
+//! [RppProject] reads a project file without running REAPER: tracks,
+//! items, takes, sources, FX chains, envelopes, markers and tempo are
+//! parsed into typed structs, which use the same value types as the live
+//! API ([Volume], [Pan], [Color], [GUID], [Position] etc.).
+//!
+//! Every struct keeps the [ChunkBlock] it was read from. On writing, only
+//! values that differ from the parsed ones are updated, so the rest of the
+//! project (and an unchanged project as a whole) is serialized
+//! byte-for-byte.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{RppItem, RppProject, RppTrack, Volume};
+//! use std::time::Duration;
+//!
+//! let text = "<REAPER_PROJECT 0.1 \"7.0\" 0\n  TEMPO 120 4 4\n>\n";
+//! let mut project = RppProject::parse(text).unwrap();
+//! assert_eq!(project.to_string(), text);
+//!
+//! let mut track = RppTrack::new("bass");
+//! track.volume = Volume::from(0.5);
+//! track.items.push(RppItem::new(1.0, Duration::from_secs(2)));
+//! project.tempo = 90.0;
+//! project.tracks.push(track);
+//!
+//! let project = RppProject::parse(&project.to_string()).unwrap();
+//! assert_eq!(project.tempo, 90.0);
+//! assert_eq!(project.tracks[0].name, "bass");
+//! assert_eq!(project.tracks[0].volume.get(), 0.5);
+//! assert_eq!(project.tracks[0].items[0].position, 1.0.into());
+//! ```
+
+use std::{
+    fmt::Display, path::Path, str::FromStr, time::Duration as StdDuration,
+};
+
+use int_enum::IntEnum;
+
+use crate::{
+    CcShapeKind, Chunk, ChunkBlock, ChunkLine, ChunkNode, Color,
+    EnvelopePoint, EnvelopePointShape, MarkerRegionInfo, MidiEvent,
+    MidiMessage, Pan, PlayRate, Position, RawMidiMessage, ReaRsError,
+    ReaperResult, SoloMode, SourceOffset, TimeSignature, TrackFolderState,
+    Volume, GUID,
+};
+
+/// Flag of custom color in `PEAKCOL` and `MARKER` lines.
+const RPP_COLOR_FLAG: u32 = 0x1000000;
+
+/// REAPER project, read from `.rpp` file or created from scratch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppProject {
+    /// Project BPM from `TEMPO` line.
+    pub tempo: f64,
+    pub time_signature: TimeSignature,
+    /// Points of the tempo envelope.
+    pub tempo_markers: Vec<RppTempoMarker>,
+    pub markers: Vec<MarkerRegionInfo>,
+    pub tracks: Vec<RppTrack>,
+    chunk: Chunk,
+}
+impl RppProject {
+    /// Empty project at 120 BPM 4/4.
+    pub fn new() -> Self {
+        let mut root = ChunkBlock::new("REAPER_PROJECT", ["0.1", "7.0", "0"]);
+        root.push(ChunkLine::new("TEMPO", ["120", "4", "4"]));
+        Self::from_chunk(Chunk::new(root)).expect("generated project is valid")
+    }
+    /// Parse the text of `.rpp` file.
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        Self::from_chunk(Chunk::parse(text)?)
+    }
+    pub fn from_file(path: impl AsRef<Path>) -> ReaperResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ReaRsError::Io(e.to_string()))?;
+        Self::parse(&text)
+    }
+    /// Chunk with the `<REAPER_PROJECT` root block.
+    pub fn from_chunk(chunk: Chunk) -> ReaperResult<Self> {
+        let root = chunk
+            .root()
+            .filter(|r| r.name() == "REAPER_PROJECT")
+            .ok_or(ReaRsError::InvalidObject("not a REAPER project"))?;
+        let nodes = root.children();
+        let time_signature = TimeSignature::new(
+            attr(nodes, "TEMPO", 1).unwrap_or(4),
+            attr(nodes, "TEMPO", 2).unwrap_or(4),
+        );
+        let tempo_markers = root
+            .block("TEMPOENVEX")
+            .map(|env| {
+                env.lines_named("PT")
+                    .filter_map(RppTempoMarker::from_line)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let markers = parse_markers(nodes).into_iter().map(|m| m.0).collect();
+        Ok(Self {
+            tempo: attr(nodes, "TEMPO", 0).unwrap_or(120.0),
+            time_signature,
+            tempo_markers,
+            markers,
+            tracks: root
+                .blocks_named("TRACK")
+                .map(RppTrack::from_block)
+                .collect(),
+            chunk,
+        })
+    }
+    /// Chunk as it was read (or created).
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+    /// Chunk with all changes applied.
+    pub fn to_chunk(&self) -> Chunk {
+        let old = Self::from_chunk(self.chunk.clone())
+            .expect("project chunk is checked on creation");
+        let mut chunk = self.chunk.clone();
+        let root = chunk.root_mut().expect("project chunk has root");
+        let nodes = root.children_mut();
+        if self.tempo != old.tempo {
+            set_attr(nodes, "TEMPO", &["120", "4", "4"], 0, self.tempo);
+        }
+        if self.time_signature != old.time_signature {
+            let (num, denom) = self.time_signature.get();
+            set_attr(nodes, "TEMPO", &["120", "4", "4"], 1, num);
+            set_attr(nodes, "TEMPO", &["120", "4", "4"], 2, denom);
+        }
+        if self.tempo_markers != old.tempo_markers {
+            self.write_tempo_markers(nodes);
+        }
+        if self.markers != old.markers {
+            write_markers(nodes, &self.markers);
+        }
+        if self.tracks != old.tracks {
+            let blocks = self.tracks.iter().map(|t| t.to_block()).collect();
+            replace_blocks(nodes, |b| b.name() == "TRACK", blocks, None);
+        }
+        chunk
+    }
+    pub fn write_file(&self, path: impl AsRef<Path>) -> ReaperResult<()> {
+        std::fs::write(path, self.to_string())
+            .map_err(|e| ReaRsError::Io(e.to_string()))
+    }
+
+    fn write_tempo_markers(&self, nodes: &mut Vec<ChunkNode>) {
+        let index =
+            match nodes.iter().position(|n| n.name() == Some("TEMPOENVEX")) {
+                Some(index) => index,
+                None => {
+                    let mut env = ChunkBlock::new("TEMPOENVEX", [""; 0]);
+                    for (name, params) in [
+                        ("ACT", &["1", "-1"][..]),
+                        ("VIS", &["1", "0", "1"]),
+                        ("LANEHEIGHT", &["0", "0"]),
+                        ("ARM", &["0"]),
+                        ("DEFSHAPE", &["1", "-1", "-1"]),
+                    ] {
+                        env.push(ChunkLine::new(name, params.iter().copied()));
+                    }
+                    let index = nodes
+                        .iter()
+                        .position(|n| {
+                            matches!(n.name(), Some("MARKER") | Some("TRACK"))
+                        })
+                        .unwrap_or(nodes.len());
+                    nodes.insert(index, env.into());
+                    index
+                }
+            };
+        let env = nodes[index].as_block_mut().expect("is block");
+        let old: Vec<(RppTempoMarker, &ChunkLine)> = env
+            .lines_named("PT")
+            .filter_map(|l| Some((RppTempoMarker::from_line(l)?, l)))
+            .collect();
+        let lines = self
+            .tempo_markers
+            .iter()
+            .enumerate()
+            .map(|(idx, marker)| match old.get(idx) {
+                Some((old, line)) if old == marker => (*line).clone(),
+                _ => marker.to_line(),
+            })
+            .collect();
+        replace_lines(env.children_mut(), "PT", lines, None);
+    }
+}
+impl Default for RppProject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl FromStr for RppProject {
+    type Err = ReaRsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+impl Display for RppProject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_chunk().fmt(f)
+    }
+}
+
+/// Point of the project tempo envelope (`TEMPOENVEX`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RppTempoMarker {
+    pub position: Position,
+    pub bpm: f64,
+    /// Gradual tempo change to the next marker.
+    pub linear: bool,
+    pub time_signature: Option<TimeSignature>,
+}
+impl RppTempoMarker {
+    pub fn new(position: impl Into<Position>, bpm: f64) -> Self {
+        Self {
+            position: position.into(),
+            bpm,
+            linear: false,
+            time_signature: None,
+        }
+    }
+    fn from_line(line: &ChunkLine) -> Option<Self> {
+        let param = |idx| line.param(idx)?.parse::<f64>().ok();
+        let raw_signature = param(3).unwrap_or_default() as u32;
+        let time_signature = match raw_signature {
+            0 => None,
+            raw => Some(TimeSignature::new(raw & 0xFFFF, raw >> 16)),
+        };
+        Some(Self {
+            position: Position::from(param(0)?.max(0.0)),
+            bpm: param(1)?,
+            linear: param(2).unwrap_or(1.0) == 0.0,
+            time_signature,
+        })
+    }
+    fn to_line(self) -> ChunkLine {
+        let position: f64 = self.position.into();
+        let mut params = vec![
+            position.to_string(),
+            self.bpm.to_string(),
+            (!self.linear as u8).to_string(),
+        ];
+        if let Some(signature) = self.time_signature {
+            let raw = signature.numerator + (signature.denominator << 16);
+            params.push(raw.to_string());
+        }
+        ChunkLine::new("PT", params)
+    }
+}
+
+/// `<TRACK` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppTrack {
+    pub name: String,
+    /// `TRACKID`. New tracks have no GUID: REAPER generates it on load.
+    pub guid: Option<GUID>,
+    pub volume: Volume,
+    /// Balance pan. Pan mode and width are not modeled.
+    pub pan: Pan,
+    pub muted: bool,
+    pub solo: SoloMode,
+    /// Custom color, `None` for the theme default.
+    pub color: Option<Color>,
+    pub folder_state: TrackFolderState,
+    pub selected: bool,
+    /// Child blocks with `ENV` in name: `VOLENV2`, `PANENV2` etc.
+    pub envelopes: Vec<RppEnvelope>,
+    pub fx_chain: Option<RppFxChain>,
+    /// `FXCHAIN_REC` block.
+    pub input_fx: Option<RppFxChain>,
+    pub items: Vec<RppItem>,
+    block: ChunkBlock,
+}
+impl RppTrack {
+    pub fn new(name: impl Into<String>) -> Self {
+        let mut block = ChunkBlock::new("TRACK", [""; 0]);
+        block.push(ChunkLine::new("NAME", [name.into()]));
+        for (name, params) in [
+            ("PEAKCOL", &["16576"][..]),
+            ("VOLPAN", &["1", "0", "-1", "-1", "1"]),
+            ("MUTESOLO", &["0", "0", "0"]),
+            ("ISBUS", &["0", "0"]),
+            ("SEL", &["0"]),
+            ("NCHAN", &["2"]),
+            ("MAINSEND", &["1", "0"]),
+        ] {
+            block.push(ChunkLine::new(name, params.iter().copied()));
+        }
+        Self::from_block(&block)
+    }
+    pub fn from_block(block: &ChunkBlock) -> Self {
+        let nodes = block.children();
+        let guid = guid_attr(nodes, "TRACKID")
+            .or_else(|| GUID::parse_braced(block.param(0)?).ok());
+        let solo = SoloMode::from_int(attr(nodes, "MUTESOLO", 1).unwrap_or(0))
+            .unwrap_or(SoloMode::NotSoloed);
+        let depth: i32 = attr(nodes, "ISBUS", 1).unwrap_or(0);
+        let folder_state = TrackFolderState::from_raw(
+            depth.min(1),
+            attr(nodes, "BUSCOMP", 0).unwrap_or(0),
+        );
+        Self {
+            name: attr(nodes, "NAME", 0).unwrap_or_default(),
+            guid,
+            volume: Volume::from(
+                attr(nodes, "VOLPAN", 0).unwrap_or(1.0_f64).max(0.0),
+            ),
+            pan: Pan::from(attr(nodes, "VOLPAN", 1).unwrap_or(0.0)),
+            muted: flag(nodes, "MUTESOLO", 0),
+            solo,
+            color: attr(nodes, "PEAKCOL", 0).and_then(color_from_rpp),
+            folder_state,
+            selected: flag(nodes, "SEL", 0),
+            envelopes: block
+                .blocks()
+                .filter(|b| is_envelope(b))
+                .map(RppEnvelope::from_block)
+                .collect(),
+            fx_chain: block.block("FXCHAIN").map(RppFxChain::from_block),
+            input_fx: block.block("FXCHAIN_REC").map(RppFxChain::from_block),
+            items: block
+                .blocks_named("ITEM")
+                .map(RppItem::from_block)
+                .collect(),
+            block: block.clone(),
+        }
+    }
+    /// Block as it was read (or created).
+    pub fn block(&self) -> &ChunkBlock {
+        &self.block
+    }
+    /// Block with all changes applied.
+    pub fn to_block(&self) -> ChunkBlock {
+        let old = Self::from_block(&self.block);
+        let mut block = self.block.clone();
+        if self.guid != old.guid {
+            let guid = self.guid.map(|g| g.to_braced_string());
+            if block.param(0).is_some() {
+                block
+                    .header_mut()
+                    .set_param(0, guid.clone().unwrap_or_default());
+            }
+            set_string(block.children_mut(), "TRACKID", guid);
+        }
+        let nodes = block.children_mut();
+        if self.name != old.name {
+            set_attr(nodes, "NAME", &[""], 0, &self.name);
+        }
+        let volpan = &["1", "0", "-1", "-1", "1"];
+        if self.volume != old.volume {
+            set_attr(nodes, "VOLPAN", volpan, 0, self.volume.get());
+        }
+        if self.pan != old.pan {
+            set_attr(nodes, "VOLPAN", volpan, 1, self.pan.get());
+        }
+        if self.muted != old.muted {
+            set_attr(nodes, "MUTESOLO", &["0", "0", "0"], 0, self.muted as u8);
+        }
+        if self.solo != old.solo {
+            let solo = self.solo.int_value();
+            set_attr(nodes, "MUTESOLO", &["0", "0", "0"], 1, solo);
+        }
+        if self.color != old.color {
+            set_attr(nodes, "PEAKCOL", &["0"], 0, color_to_rpp(self.color));
+        }
+        if self.folder_state != old.folder_state {
+            let (depth, compact) = self.folder_state.to_raw();
+            let bus = match depth {
+                1 => 1,
+                d if d < 0 => 2,
+                _ => 0,
+            };
+            set_attr(nodes, "ISBUS", &["0", "0"], 0, bus);
+            set_attr(nodes, "ISBUS", &["0", "0"], 1, depth);
+            if let Some(compact) = compact {
+                let defaults = &["0", "0", "0", "0", "0"];
+                set_attr(nodes, "BUSCOMP", defaults, 0, compact);
+            }
+        }
+        if self.selected != old.selected {
+            set_attr(nodes, "SEL", &["0"], 0, self.selected as u8);
+        }
+        let first_item = nodes.iter().position(|n| n.name() == Some("ITEM"));
+        if self.envelopes != old.envelopes {
+            let blocks = self.envelopes.iter().map(|e| e.to_block()).collect();
+            replace_blocks(nodes, is_envelope, blocks, first_item);
+        }
+        if self.fx_chain != old.fx_chain {
+            let blocks = self.fx_chain.iter().map(|c| c.to_block()).collect();
+            replace_blocks(
+                nodes,
+                |b| b.name() == "FXCHAIN",
+                blocks,
+                first_item,
+            );
+        }
+        if self.input_fx != old.input_fx {
+            let blocks = self.input_fx.iter().map(|c| c.to_block()).collect();
+            replace_blocks(
+                nodes,
+                |b| b.name() == "FXCHAIN_REC",
+                blocks,
+                first_item,
+            );
+        }
+        if self.items != old.items {
+            let blocks = self.items.iter().map(|i| i.to_block()).collect();
+            replace_blocks(nodes, |b| b.name() == "ITEM", blocks, None);
+        }
+        block
+    }
+}
+
+/// `<FXCHAIN` block (as well as `FXCHAIN_REC` and `TAKEFX`).
+///
+/// FX are the groups of nodes, starting with `BYPASS` line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppFxChain {
+    pub fx: Vec<RppFx>,
+    /// Chain block without FX nodes.
+    block: ChunkBlock,
+}
+impl RppFxChain {
+    /// Empty chain block, e.g. `FXCHAIN`.
+    pub fn new(name: impl Into<String>) -> Self {
+        let mut block = ChunkBlock::new(name, [""; 0]);
+        block.push(ChunkLine::new("SHOW", ["0"]));
+        block.push(ChunkLine::new("LASTSEL", ["0"]));
+        block.push(ChunkLine::new("DOCKED", ["0"]));
+        Self::from_block(&block)
+    }
+    pub fn from_block(block: &ChunkBlock) -> Self {
+        let mut head = block.clone();
+        let nodes = head.children_mut();
+        let start = nodes
+            .iter()
+            .position(|n| n.name() == Some("BYPASS"))
+            .unwrap_or(nodes.len());
+        let mut fx: Vec<RppFx> = Vec::new();
+        let mut group = Vec::new();
+        for node in nodes.drain(start..) {
+            if node.name() == Some("BYPASS") && !group.is_empty() {
+                fx.push(RppFx::from_nodes(std::mem::take(&mut group)));
+            }
+            group.push(node);
+        }
+        if !group.is_empty() {
+            fx.push(RppFx::from_nodes(group));
+        }
+        Self { fx, block: head }
+    }
+    /// Block with all changes applied.
+    pub fn to_block(&self) -> ChunkBlock {
+        let mut block = self.block.clone();
+        for fx in self.fx.iter() {
+            block.children_mut().extend(fx.to_nodes());
+        }
+        block
+    }
+}
+
+/// One FX of [RppFxChain]: nodes from `BYPASS` to the next FX.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppFx {
+    pub bypassed: bool,
+    pub offline: bool,
+    /// `FXID`.
+    pub guid: Option<GUID>,
+    pub preset_name: Option<String>,
+    /// `PARMENV` blocks.
+    pub envelopes: Vec<RppEnvelope>,
+    nodes: Vec<ChunkNode>,
+}
+impl RppFx {
+    /// FX from plugin block (e.g. `<JS utility/volume ""`) with its state.
+    pub fn new(plugin: ChunkBlock) -> Self {
+        Self::from_nodes(vec![
+            ChunkLine::new("BYPASS", ["0", "0", "0"]).into(),
+            plugin.into(),
+            ChunkLine::new("FLOATPOS", ["0", "0", "0", "0"]).into(),
+            ChunkLine::new("WAK", ["0", "0"]).into(),
+        ])
+    }
+    fn from_nodes(nodes: Vec<ChunkNode>) -> Self {
+        Self {
+            bypassed: flag(&nodes, "BYPASS", 0),
+            offline: flag(&nodes, "BYPASS", 1),
+            guid: guid_attr(&nodes, "FXID"),
+            preset_name: attr(&nodes, "PRESETNAME", 0),
+            envelopes: nodes
+                .iter()
+                .filter_map(|n| n.as_block())
+                .filter(|b| b.name() == "PARMENV")
+                .map(RppEnvelope::from_block)
+                .collect(),
+            nodes,
+        }
+    }
+    /// Plugin block: `VST`, `JS`, `AU` etc.
+    pub fn plugin(&self) -> Option<&ChunkBlock> {
+        self.nodes
+            .iter()
+            .filter_map(|n| n.as_block())
+            .find(|b| b.name() != "PARMENV")
+    }
+    /// Plugin kind: `VST`, `JS`, `AU` etc.
+    pub fn kind(&self) -> Option<&str> {
+        self.plugin().map(|b| b.name())
+    }
+    /// Plugin name as written in the plugin block.
+    pub fn name(&self) -> Option<&str> {
+        self.plugin()?.param(0)
+    }
+    /// Nodes as they were read (or created).
+    pub fn nodes(&self) -> &[ChunkNode] {
+        &self.nodes
+    }
+    /// Nodes with all changes applied.
+    pub fn to_nodes(&self) -> Vec<ChunkNode> {
+        let old = Self::from_nodes(self.nodes.clone());
+        let mut nodes = self.nodes.clone();
+        if self.bypassed != old.bypassed {
+            set_attr(
+                &mut nodes,
+                "BYPASS",
+                &["0", "0", "0"],
+                0,
+                self.bypassed as u8,
+            );
+        }
+        if self.offline != old.offline {
+            set_attr(
+                &mut nodes,
+                "BYPASS",
+                &["0", "0", "0"],
+                1,
+                self.offline as u8,
+            );
+        }
+        if self.guid != old.guid {
+            let guid = self.guid.map(|g| g.to_braced_string());
+            set_string(&mut nodes, "FXID", guid);
+        }
+        if self.preset_name != old.preset_name {
+            set_string(&mut nodes, "PRESETNAME", self.preset_name.clone());
+        }
+        if self.envelopes != old.envelopes {
+            let wak = nodes.iter().position(|n| n.name() == Some("WAK"));
+            let blocks = self.envelopes.iter().map(|e| e.to_block()).collect();
+            replace_blocks(&mut nodes, |b| b.name() == "PARMENV", blocks, wak);
+        }
+        nodes
+    }
+}
+
+/// Envelope block: `VOLENV2`, `PARMENV`, `TEMPOENVEX` etc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppEnvelope {
+    /// `EGUID`.
+    pub guid: Option<GUID>,
+    pub active: bool,
+    pub visible: bool,
+    pub armed: bool,
+    pub points: Vec<(Position, EnvelopePoint)>,
+    block: ChunkBlock,
+}
+impl RppEnvelope {
+    /// Empty envelope block, e.g. `VOLENV2` or `PARMENV 1:1 0 1 0.5`.
+    pub fn new(
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let mut block = ChunkBlock::new(name, params);
+        for (name, params) in [
+            ("ACT", &["1", "-1"][..]),
+            ("VIS", &["1", "1", "1"]),
+            ("LANEHEIGHT", &["0", "0"]),
+            ("ARM", &["0"]),
+            ("DEFSHAPE", &["0", "-1", "-1"]),
+        ] {
+            block.push(ChunkLine::new(name, params.iter().copied()));
+        }
+        Self::from_block(&block)
+    }
+    pub fn from_block(block: &ChunkBlock) -> Self {
+        let nodes = block.children();
+        Self {
+            guid: guid_attr(nodes, "EGUID"),
+            active: flag(nodes, "ACT", 0),
+            visible: flag(nodes, "VIS", 0),
+            armed: flag(nodes, "ARM", 0),
+            points: block.lines_named("PT").filter_map(parse_point).collect(),
+            block: block.clone(),
+        }
+    }
+    pub fn name(&self) -> &str {
+        self.block.name()
+    }
+    /// Block as it was read (or created).
+    pub fn block(&self) -> &ChunkBlock {
+        &self.block
+    }
+    /// Block with all changes applied.
+    pub fn to_block(&self) -> ChunkBlock {
+        let old = Self::from_block(&self.block);
+        let mut block = self.block.clone();
+        let nodes = block.children_mut();
+        if self.guid != old.guid {
+            let guid = self.guid.map(|g| g.to_braced_string());
+            set_string(nodes, "EGUID", guid);
+        }
+        if self.active != old.active {
+            set_attr(nodes, "ACT", &["0", "-1"], 0, self.active as u8);
+        }
+        if self.visible != old.visible {
+            set_attr(nodes, "VIS", &["0", "1", "1"], 0, self.visible as u8);
+        }
+        if self.armed != old.armed {
+            set_attr(nodes, "ARM", &["0"], 0, self.armed as u8);
+        }
+        if self.points != old.points {
+            let old: Vec<(_, &ChunkLine)> = self
+                .block
+                .lines_named("PT")
+                .filter_map(|l| Some((parse_point(l)?, l)))
+                .collect();
+            let lines = self
+                .points
+                .iter()
+                .enumerate()
+                .map(|(idx, point)| match old.get(idx) {
+                    Some((old, line)) if old == point => (*line).clone(),
+                    old => point_line(old.map(|o| o.1), point),
+                })
+                .collect();
+            replace_lines(nodes, "PT", lines, None);
+        }
+        block
+    }
+}
+
+/// `<ITEM` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppItem {
+    pub position: Position,
+    pub length: StdDuration,
+    pub muted: bool,
+    pub selected: bool,
+    pub looped: bool,
+    /// `IGUID`.
+    pub guid: Option<GUID>,
+    pub takes: Vec<RppTake>,
+    /// Item block without take nodes.
+    block: ChunkBlock,
+}
+impl RppItem {
+    pub fn new(position: impl Into<Position>, length: StdDuration) -> Self {
+        let position: f64 = position.into().into();
+        let mut block = ChunkBlock::new("ITEM", [""; 0]);
+        for (name, params) in [
+            ("POSITION", vec![position.to_string()]),
+            ("LENGTH", vec![length.as_secs_f64().to_string()]),
+            ("LOOP", vec!["1".to_string()]),
+            ("MUTE", vec!["0".to_string(), "0".to_string()]),
+            ("SEL", vec!["0".to_string()]),
+        ] {
+            block.push(ChunkLine::new(name, params));
+        }
+        Self::from_block(&block)
+    }
+    /// Parse item block.
+    ///
+    /// The first take starts from the first `NAME` line, every next take
+    /// starts from `TAKE` line.
+    pub fn from_block(block: &ChunkBlock) -> Self {
+        let mut head = block.clone();
+        let children = head.children_mut();
+        let start = children
+            .iter()
+            .position(|n| matches!(n.name(), Some("NAME") | Some("TAKE")))
+            .unwrap_or(children.len());
+        let mut takes: Vec<RppTake> = Vec::new();
+        let mut segment = Vec::new();
+        for node in children.drain(start..) {
+            if node.name() == Some("TAKE") && !segment.is_empty() {
+                takes.push(RppTake::from_nodes(std::mem::take(&mut segment)));
+            }
+            segment.push(node);
+        }
+        if !segment.is_empty() {
+            takes.push(RppTake::from_nodes(segment));
+        }
+        let other_selected = takes.iter().skip(1).any(|t| t.selected);
+        if let Some(first) = takes.first_mut() {
+            first.selected = !other_selected;
+        }
+        let nodes = head.children();
+        Self {
+            position: Position::from(
+                attr(nodes, "POSITION", 0).unwrap_or(0.0_f64).max(0.0),
+            ),
+            length: StdDuration::from_secs_f64(
+                attr(nodes, "LENGTH", 0).unwrap_or(0.0_f64).max(0.0),
+            ),
+            muted: flag(nodes, "MUTE", 0),
+            selected: flag(nodes, "SEL", 0),
+            looped: flag(nodes, "LOOP", 0),
+            guid: guid_attr(nodes, "IGUID"),
+            takes,
+            block: head,
+        }
+    }
+    /// Active take.
+    pub fn selected_take(&self) -> Option<&RppTake> {
+        self.takes.iter().find(|t| t.selected)
+    }
+    /// Block with all changes applied.
+    ///
+    /// Selection of the first take is implicit: it is active if no other
+    /// take is selected.
+    pub fn to_block(&self) -> ChunkBlock {
+        let old = Self::from_block(&self.block);
+        let mut block = self.block.clone();
+        let nodes = block.children_mut();
+        if self.position != old.position {
+            let position: f64 = self.position.into();
+            set_attr(nodes, "POSITION", &["0"], 0, position);
+        }
+        if self.length != old.length {
+            let length = self.length.as_secs_f64();
+            set_attr(nodes, "LENGTH", &["0"], 0, length);
+        }
+        if self.looped != old.looped {
+            set_attr(nodes, "LOOP", &["0"], 0, self.looped as u8);
+        }
+        if self.muted != old.muted {
+            set_attr(nodes, "MUTE", &["0", "0"], 0, self.muted as u8);
+        }
+        if self.selected != old.selected {
+            set_attr(nodes, "SEL", &["0"], 0, self.selected as u8);
+        }
+        if self.guid != old.guid {
+            let guid = self.guid.map(|g| g.to_braced_string());
+            set_string(nodes, "IGUID", guid);
+        }
+        for (idx, take) in self.takes.iter().enumerate() {
+            let mut take_nodes = take.to_nodes();
+            let take_line = take_nodes
+                .first()
+                .and_then(|n| n.as_line())
+                .filter(|l| l.name() == "TAKE")
+                .cloned();
+            if take_line.is_some() {
+                take_nodes.remove(0);
+            }
+            if idx > 0 {
+                let line = match take_line {
+                    Some(line)
+                        if (line.param(0) == Some("SEL")) == take.selected =>
+                    {
+                        line
+                    }
+                    _ => match take.selected {
+                        true => ChunkLine::new("TAKE", ["SEL"]),
+                        false => ChunkLine::new("TAKE", [""; 0]),
+                    },
+                };
+                take_nodes.insert(0, line.into());
+            }
+            nodes.extend(take_nodes);
+        }
+        block
+    }
+}
+
+/// Take of [RppItem]: group of item nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppTake {
+    pub name: String,
+    /// `GUID`.
+    pub guid: Option<GUID>,
+    pub start_offset: SourceOffset,
+    pub play_rate: PlayRate,
+    /// Is the active take of item.
+    pub selected: bool,
+    pub source: Option<RppSource>,
+    /// `TAKEFX` block.
+    pub fx_chain: Option<RppFxChain>,
+    nodes: Vec<ChunkNode>,
+}
+impl RppTake {
+    pub fn new(name: impl Into<String>, source: RppSource) -> Self {
+        Self::from_nodes(vec![
+            ChunkLine::new("NAME", [name.into()]).into(),
+            ChunkLine::new("VOLPAN", ["1", "0", "1", "-1"]).into(),
+            ChunkLine::new("SOFFS", ["0"]).into(),
+            ChunkLine::new("PLAYRATE", ["1", "1", "0", "-1", "0", "0.0025"])
+                .into(),
+            ChunkLine::new("CHANMODE", ["0"]).into(),
+            source.to_block().into(),
+        ])
+    }
+    fn from_nodes(nodes: Vec<ChunkNode>) -> Self {
+        let selected = nodes
+            .first()
+            .and_then(|n| n.as_line())
+            .is_some_and(|l| l.name() == "TAKE" && l.param(0) == Some("SEL"));
+        Self {
+            name: attr(&nodes, "NAME", 0).unwrap_or_default(),
+            guid: guid_attr(&nodes, "GUID"),
+            start_offset: SourceOffset::from_secs_f64(
+                attr(&nodes, "SOFFS", 0).unwrap_or(0.0),
+            ),
+            play_rate: PlayRate::from(
+                attr(&nodes, "PLAYRATE", 0).unwrap_or(1.0_f64),
+            ),
+            selected,
+            source: nodes
+                .iter()
+                .filter_map(|n| n.as_block())
+                .find(|b| b.name() == "SOURCE")
+                .map(RppSource::from_block),
+            fx_chain: nodes
+                .iter()
+                .filter_map(|n| n.as_block())
+                .find(|b| b.name() == "TAKEFX")
+                .map(RppFxChain::from_block),
+            nodes,
+        }
+    }
+    /// Nodes as they were read (or created).
+    pub fn nodes(&self) -> &[ChunkNode] {
+        &self.nodes
+    }
+    /// Nodes with all changes applied, except take selection, which is
+    /// written by [RppItem::to_block].
+    pub fn to_nodes(&self) -> Vec<ChunkNode> {
+        let old = Self::from_nodes(self.nodes.clone());
+        let mut nodes = self.nodes.clone();
+        if self.name != old.name {
+            set_attr(&mut nodes, "NAME", &[""], 0, &self.name);
+        }
+        if self.guid != old.guid {
+            let guid = self.guid.map(|g| g.to_braced_string());
+            set_string(&mut nodes, "GUID", guid);
+        }
+        if self.start_offset != old.start_offset {
+            let offset = self.start_offset.as_secs_f64();
+            set_attr(&mut nodes, "SOFFS", &["0"], 0, offset);
+        }
+        if self.play_rate != old.play_rate {
+            let rate: f64 = self.play_rate.into();
+            let defaults = &["1", "1", "0", "-1", "0", "0.0025"];
+            set_attr(&mut nodes, "PLAYRATE", defaults, 0, rate);
+        }
+        if self.source != old.source {
+            let blocks = self.source.iter().map(|s| s.to_block()).collect();
+            replace_blocks(&mut nodes, |b| b.name() == "SOURCE", blocks, None);
+        }
+        if self.fx_chain != old.fx_chain {
+            let blocks = self.fx_chain.iter().map(|c| c.to_block()).collect();
+            replace_blocks(&mut nodes, |b| b.name() == "TAKEFX", blocks, None);
+        }
+        nodes
+    }
+}
+
+/// `<SOURCE` block of take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RppSource {
+    /// `WAVE`, `MIDI`, `MP3` etc.
+    pub kind: String,
+    /// Media file path, as written in project.
+    pub file: Option<String>,
+    block: ChunkBlock,
+}
+impl RppSource {
+    /// Media file source, e.g. `RppSource::new_file("WAVE", "a.wav")`.
+    pub fn new_file(kind: impl Into<String>, file: impl Into<String>) -> Self {
+        let mut block = ChunkBlock::new("SOURCE", [kind.into()]);
+        block.push(ChunkLine::new("FILE", [file.into()]));
+        Self::from_block(&block)
+    }
+    /// Empty in-project MIDI source.
+    pub fn new_midi(ppq: u32) -> Self {
+        let mut block = ChunkBlock::new("SOURCE", ["MIDI"]);
+        let ppq = ppq.to_string();
+        block.push(ChunkLine::new("HASDATA", ["1", ppq.as_str(), "QN"]));
+        Self::from_block(&block)
+    }
+    pub fn from_block(block: &ChunkBlock) -> Self {
+        Self {
+            kind: block.param(0).unwrap_or_default().to_string(),
+            file: attr(block.children(), "FILE", 0),
+            block: block.clone(),
+        }
+    }
+    /// Ticks per quarter note of in-project MIDI.
+    pub fn midi_ppq(&self) -> Option<u32> {
+        attr(self.block.children(), "HASDATA", 1)
+    }
+    /// Short MIDI messages of in-project MIDI data.
+    ///
+    /// `E` lines are events, `e` are selected and `m` suffix marks muted
+    /// events. SysEx (`X` blocks) are not decoded, but their position is
+    /// counted.
+    pub fn midi_events(&self) -> Vec<MidiEvent<RawMidiMessage>> {
+        let mut position = 0_u32;
+        let mut events = Vec::new();
+        for node in self.block.children() {
+            let (kind, params) = match node {
+                ChunkNode::Line(line) => (line.name(), line.params()),
+                ChunkNode::Block(block) => (block.name(), block.params()),
+                ChunkNode::Blob(_) => continue,
+            };
+            if !["E", "e", "Em", "em", "X", "x", "Xm", "xm"].contains(&kind) {
+                continue;
+            }
+            let Some(delta) = params.first().and_then(|p| p.parse().ok())
+            else {
+                continue;
+            };
+            position = position.saturating_add(delta);
+            if node.as_line().is_none() {
+                continue;
+            }
+            let buf: Option<Vec<u8>> = params[1..]
+                .iter()
+                .map(|b| u8::from_str_radix(b, 16).ok())
+                .collect();
+            let Some(message) = buf.and_then(RawMidiMessage::from_raw) else {
+                continue;
+            };
+            events.push(MidiEvent::new(
+                position,
+                kind.starts_with('e'),
+                kind.ends_with('m'),
+                CcShapeKind::default(),
+                message,
+            ));
+        }
+        events
+    }
+    /// Replace in-project MIDI data by `events`, sorted by position.
+    ///
+    /// Messages longer than 3 bytes are skipped, existing SysEx blocks are
+    /// removed.
+    pub fn set_midi_events(
+        &mut self,
+        events: impl IntoIterator<Item = MidiEvent<RawMidiMessage>>,
+    ) {
+        let mut position = 0_u32;
+        let lines: Vec<ChunkLine> = events
+            .into_iter()
+            .filter(|e| e.message().borrow_raw().len() <= 3)
+            .map(|event| {
+                let mut kind = match event.selected() {
+                    true => "e",
+                    false => "E",
+                }
+                .to_string();
+                if event.muted() {
+                    kind.push('m');
+                }
+                let delta = event.ppq_position().saturating_sub(position);
+                position = event.ppq_position();
+                let mut params = vec![delta.to_string()];
+                params.extend(
+                    event
+                        .message()
+                        .borrow_raw()
+                        .iter()
+                        .map(|b| format!("{:02x}", b)),
+                );
+                ChunkLine::new(kind, params)
+            })
+            .collect();
+        let nodes = self.block.children_mut();
+        nodes.retain(|n| {
+            n.as_block().is_none()
+                || !matches!(n.name(), Some("X" | "x" | "Xm" | "xm"))
+        });
+        let index = nodes
+            .iter()
+            .position(|n| is_midi_event_line(n) || n.name() == Some("GUID"))
+            .unwrap_or(nodes.len());
+        nodes.retain(|n| !is_midi_event_line(n));
+        let index = index.min(nodes.len());
+        nodes.splice(index..index, lines.into_iter().map(ChunkNode::Line));
+    }
+    /// Block as it was read (or created), with MIDI events set.
+    pub fn block(&self) -> &ChunkBlock {
+        &self.block
+    }
+    /// Block with all changes applied.
+    pub fn to_block(&self) -> ChunkBlock {
+        let old = Self::from_block(&self.block);
+        let mut block = self.block.clone();
+        if self.kind != old.kind {
+            block.header_mut().set_param(0, self.kind.as_str());
+        }
+        if self.file != old.file {
+            set_string(block.children_mut(), "FILE", self.file.clone());
+        }
+        block
+    }
+}
+
+fn is_midi_event_line(node: &ChunkNode) -> bool {
+    node.as_line()
+        .is_some_and(|l| matches!(l.name(), "E" | "e" | "Em" | "em"))
+}
+
+fn is_envelope(block: &ChunkBlock) -> bool {
+    block.name().contains("ENV")
+}
+
+fn find_line<'a>(nodes: &'a [ChunkNode], name: &str) -> Option<&'a ChunkLine> {
+    nodes
+        .iter()
+        .filter_map(|n| n.as_line())
+        .find(|l| l.name() == name)
+}
+
+/// Parameter of the first line with the name.
+fn attr<T: FromStr>(
+    nodes: &[ChunkNode],
+    name: &str,
+    index: usize,
+) -> Option<T> {
+    find_line(nodes, name)?.param(index)?.parse().ok()
+}
+
+fn flag(nodes: &[ChunkNode], name: &str, index: usize) -> bool {
+    attr::<f64>(nodes, name, index).is_some_and(|v| v != 0.0)
+}
+
+fn guid_attr(nodes: &[ChunkNode], name: &str) -> Option<GUID> {
+    GUID::parse_braced(find_line(nodes, name)?.param(0)?).ok()
+}
+
+/// Set parameter of the first line with the name.
+///
+/// If there is no such line, it is created with `defaults` before the
+/// first child block.
+fn set_attr(
+    nodes: &mut Vec<ChunkNode>,
+    name: &str,
+    defaults: &[&str],
+    index: usize,
+    value: impl Display,
+) {
+    let position = match nodes
+        .iter()
+        .position(|n| n.as_line().is_some_and(|l| l.name() == name))
+    {
+        Some(position) => position,
+        None => {
+            let position = nodes
+                .iter()
+                .position(|n| n.as_block().is_some())
+                .unwrap_or(nodes.len());
+            let line = ChunkLine::new(name, defaults.iter().copied());
+            nodes.insert(position, line.into());
+            position
+        }
+    };
+    let line = nodes[position].as_line_mut().expect("is line");
+    line.set_param(index, value.to_string());
+}
+
+/// Set the single parameter of line, or remove the line if value is `None`.
+fn set_string(nodes: &mut Vec<ChunkNode>, name: &str, value: Option<String>) {
+    match value {
+        Some(value) => set_attr(nodes, name, &[""], 0, value),
+        None => nodes.retain(|n| n.as_line().map(|l| l.name()) != Some(name)),
+    }
+}
+
+/// Replace all lines with the name by `lines`, placed where the first old
+/// line was, or at `fallback` (end by default).
+fn replace_lines(
+    nodes: &mut Vec<ChunkNode>,
+    name: &str,
+    lines: Vec<ChunkLine>,
+    fallback: Option<usize>,
+) {
+    let is_old = |n: &ChunkNode| n.as_line().is_some_and(|l| l.name() == name);
+    let position = nodes
+        .iter()
+        .position(is_old)
+        .or(fallback)
+        .unwrap_or(nodes.len());
+    nodes.retain(|n| !is_old(n));
+    nodes.splice(position..position, lines.into_iter().map(ChunkNode::Line));
+}
+
+/// Replace child blocks matching `is_old` by `blocks`, placed where the
+/// first old block was, or at `fallback` (end by default).
+fn replace_blocks(
+    nodes: &mut Vec<ChunkNode>,
+    is_old: impl Fn(&ChunkBlock) -> bool,
+    blocks: Vec<ChunkBlock>,
+    fallback: Option<usize>,
+) {
+    let matches = |n: &ChunkNode| n.as_block().is_some_and(&is_old);
+    let position = nodes
+        .iter()
+        .position(matches)
+        .or(fallback)
+        .unwrap_or(nodes.len());
+    nodes.retain(|n| !matches(n));
+    nodes.splice(position..position, blocks.into_iter().map(ChunkNode::Block));
+}
+
+/// `PT time value shape [time_sig] [selected] [?] [bezier_tension]`
+fn parse_point(line: &ChunkLine) -> Option<(Position, EnvelopePoint)> {
+    let param = |idx| line.param(idx)?.parse::<f64>().ok();
+    let shape = EnvelopePointShape::from_int(param(2).unwrap_or(0.0) as i32)
+        .unwrap_or(EnvelopePointShape::Linear);
+    let point = EnvelopePoint::new(
+        param(1)?,
+        shape,
+        param(6).unwrap_or(0.0),
+        param(4).unwrap_or(0.0) != 0.0,
+    );
+    Some((Position::from(param(0)?.max(0.0)), point))
+}
+
+/// Write point to the old `PT` line, keeping unknown parameters.
+fn point_line(
+    old: Option<&ChunkLine>,
+    (position, point): &(Position, EnvelopePoint),
+) -> ChunkLine {
+    let mut line = old
+        .cloned()
+        .unwrap_or_else(|| ChunkLine::new("PT", [""; 0]));
+    let position: f64 = (*position).into();
+    line.set_param(0, position.to_string());
+    line.set_param(1, point.value.to_string());
+    line.set_param(2, point.shape.int_value().to_string());
+    let with_tension = point.tension != 0.0 || line.param(6).is_some();
+    if point.selected || with_tension || line.param(4).is_some() {
+        pad_params(&mut line, 4);
+        line.set_param(4, (point.selected as u8).to_string());
+    }
+    if with_tension {
+        pad_params(&mut line, 6);
+        line.set_param(6, point.tension.to_string());
+    }
+    line
+}
+
+fn pad_params(line: &mut ChunkLine, len: usize) {
+    while line.params().len() < len {
+        line.set_param(line.params().len(), "0");
+    }
+}
+
+fn color_from_rpp(value: u32) -> Option<Color> {
+    match value & RPP_COLOR_FLAG {
+        0 => None,
+        _ => Some(Color::new(
+            value as u8,
+            (value >> 8) as u8,
+            (value >> 16) as u8,
+        )),
+    }
+}
+
+/// Color as `0x01BBGGRR`, 0 for default.
+fn color_to_rpp(color: Option<Color>) -> u32 {
+    match color {
+        None => 0,
+        Some(c) => {
+            RPP_COLOR_FLAG
+                | c.r as u32
+                | (c.g as u32) << 8
+                | (c.b as u32) << 16
+        }
+    }
+}
+
+/// Markers with their lines: regions have the start and the end line
+/// with the same index.
+fn parse_markers(
+    nodes: &[ChunkNode],
+) -> Vec<(MarkerRegionInfo, Vec<ChunkLine>)> {
+    let mut markers: Vec<(MarkerRegionInfo, Vec<ChunkLine>)> = Vec::new();
+    let mut open_regions = Vec::new();
+    for line in nodes.iter().filter_map(|n| n.as_line()) {
+        if line.name() != "MARKER" {
+            continue;
+        }
+        let param = |idx| line.param(idx)?.parse::<f64>().ok();
+        let (Some(index), Some(position)) = (param(0), param(1)) else {
+            continue;
+        };
+        let index = index as usize;
+        let position = Position::from(position.max(0.0));
+        let is_region = param(3).unwrap_or(0.0) as u32 & 1 != 0;
+        if is_region {
+            if let Some(open) = open_regions.iter().position(|i| *i == index) {
+                let marker_idx = open_regions.remove(open);
+                let (info, lines) = markers
+                    .iter_mut()
+                    .rev()
+                    .find(|(m, _)| m.is_region && m.user_index == marker_idx)
+                    .expect("open region is in list");
+                info.rgn_end = position;
+                lines.push(line.clone());
+                continue;
+            }
+            open_regions.push(index);
+        }
+        let color = color_from_rpp(param(4).unwrap_or(0.0) as u32);
+        let info = MarkerRegionInfo {
+            is_region,
+            user_index: index,
+            enum_index: markers.len(),
+            position,
+            rgn_end: position,
+            name: line.param(2).unwrap_or_default().to_string(),
+            color: color.unwrap_or_default(),
+        };
+        markers.push((info, vec![line.clone()]));
+    }
+    markers
+}
+
+fn write_markers(nodes: &mut Vec<ChunkNode>, markers: &[MarkerRegionInfo]) {
+    let old = parse_markers(nodes);
+    let mut lines = Vec::new();
+    for marker in markers.iter() {
+        if let Some((_, old_lines)) = old.iter().find(|(m, _)| m == marker) {
+            lines.extend(old_lines.iter().cloned());
+            continue;
+        }
+        let position: f64 = marker.position.into();
+        let color = match marker.color == Color::default() {
+            true => 0,
+            false => color_to_rpp(Some(marker.color)),
+        };
+        let index = marker.user_index.to_string();
+        let flags = (marker.is_region as u8).to_string();
+        lines.push(ChunkLine::new(
+            "MARKER",
+            [
+                index.clone(),
+                position.to_string(),
+                marker.name.clone(),
+                flags.clone(),
+                color.to_string(),
+            ],
+        ));
+        if marker.is_region {
+            let end: f64 = marker.rgn_end.into();
+            lines.push(ChunkLine::new(
+                "MARKER",
+                [index, end.to_string(), String::new(), flags],
+            ));
+        }
+    }
+    let first_track = nodes.iter().position(|n| n.name() == Some("TRACK"));
+    replace_lines(nodes, "MARKER", lines, first_track);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoteOnMessage;
+
+    const PROJECT: &str = include_str!("../fixtures/project.rpp");
+
+    #[test]
+    fn test_round_trip() {
+        let project = RppProject::parse(PROJECT).unwrap();
+        assert_eq!(project.to_string(), PROJECT);
+        let crlf = PROJECT.replace('\n', "\r\n");
+        assert_eq!(RppProject::parse(&crlf).unwrap().to_string(), crlf);
+        assert!(RppProject::parse("<TRACK\n>\n").is_err());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let project = RppProject::parse(PROJECT).unwrap();
+        assert_eq!(project.tempo, 120.0);
+        assert_eq!(project.time_signature, TimeSignature::new(4, 4));
+        let tempo = &project.tempo_markers;
+        assert_eq!(tempo.len(), 3);
+        assert!(tempo[1].linear);
+        assert_eq!(tempo[2].time_signature, Some(TimeSignature::new(3, 4)));
+        assert_eq!(project.markers.len(), 2);
+        let region = &project.markers[1];
+        assert!(region.is_region);
+        assert_eq!(region.name, "Verse one");
+        assert_eq!(region.rgn_end, 12.0.into());
+        assert_eq!(region.color, Color::new(255, 0, 0));
+
+        let drums = &project.tracks[0];
+        assert_eq!(drums.name, "Drums");
+        assert_eq!(
+            drums.guid.unwrap().to_braced_string(),
+            "{A1B2C3D4-0000-4000-8000-000000000001}"
+        );
+        assert_eq!(drums.volume.get(), 0.5);
+        assert_eq!(drums.pan.get(), -0.25);
+        assert!(drums.muted);
+        assert_eq!(drums.color, Some(Color::new(255, 0, 255)));
+        assert_eq!(drums.folder_state, TrackFolderState::IsFolder(0));
+        assert_eq!(drums.envelopes[0].name(), "VOLENV2");
+        assert_eq!(drums.envelopes[0].points[1].1.tension, -0.4);
+        let fx = &drums.fx_chain.as_ref().unwrap().fx;
+        assert_eq!(fx.len(), 2);
+        assert_eq!(fx[0].name(), Some("VSTi: ReaSynth (Cockos)"));
+        assert_eq!(fx[0].preset_name.as_deref(), Some("stock - Init"));
+        assert_eq!(fx[0].envelopes[0].points.len(), 2);
+        assert!(fx[1].bypassed);
+        assert_eq!(fx[1].kind(), Some("JS"));
+
+        let item = &drums.items[0];
+        assert_eq!(item.takes.len(), 2);
+        assert_eq!(item.selected_take().unwrap().name, "Beat B");
+        let midi = item.takes[0].source.as_ref().unwrap();
+        assert_eq!(midi.midi_ppq(), Some(960));
+        let events = midi.midi_events();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[1].ppq_position(), 480);
+        assert!(events[1].selected());
+        assert_eq!(events[3].ppq_position(), 1440);
+        let wave = item.takes[1].source.as_ref().unwrap();
+        assert_eq!(wave.file.as_deref(), Some("Media/beat b.wav"));
+        assert_eq!(item.takes[1].play_rate, PlayRate::from(2.0));
+
+        let kick = &project.tracks[1];
+        assert_eq!(kick.name, "Kick 'in'");
+        assert_eq!(kick.solo, SoloMode::SoloedInPlace);
+        assert_eq!(kick.folder_state, TrackFolderState::Last(1));
+        assert!(kick.items[0].muted);
+        assert_eq!(kick.items[0].length, StdDuration::from_secs_f64(1.5));
+    }
+
+    #[test]
+    fn test_edit_and_reparse() {
+        let mut project = RppProject::parse(PROJECT).unwrap();
+        project.time_signature = TimeSignature::new(7, 8);
+        project.tempo_markers[1].bpm = 150.0;
+        project.markers.remove(0);
+        let drums = &mut project.tracks[0];
+        drums.name = "Drum bus".to_string();
+        drums.selected = false;
+        drums.envelopes[0].points[0].1.value = 0.25;
+        let fx = &mut drums.fx_chain.as_mut().unwrap().fx;
+        fx[0].bypassed = true;
+        fx.remove(1);
+        let item = &mut drums.items[0];
+        item.takes.swap(0, 1);
+        item.takes[0].selected = true;
+        item.takes[1].selected = false;
+        item.takes[1].name = "Beat \"A\"".to_string();
+        project.tracks.remove(1);
+
+        let text = project.to_string();
+        assert!(text.contains("  TEMPO 120 7 8\n"));
+        assert!(text.contains("    PT 8 150 0\n"));
+        assert!(text.contains("    NAME \"Drum bus\"\n"));
+        assert!(text.contains("  MARKER 2 12 \"\" 1\n"));
+        assert!(!text.contains("Intro"));
+        assert!(!text.contains("utility/volume"));
+        assert!(text.contains("      TAKE\n"));
+        assert!(text.contains("NAME 'Beat \"A\"'\n"));
+        let project = RppProject::parse(&text).unwrap();
+        assert_eq!(project.to_string(), text);
+        assert_eq!(project.tempo_markers[1].bpm, 150.0);
+        assert_eq!(project.markers.len(), 1);
+        assert_eq!(project.tracks.len(), 1);
+        let drums = &project.tracks[0];
+        assert_eq!(drums.name, "Drum bus");
+        assert!(!drums.selected);
+        assert_eq!(drums.envelopes[0].points[0].1.value, 0.25);
+        let fx = &drums.fx_chain.as_ref().unwrap().fx;
+        assert_eq!(fx.len(), 1);
+        assert!(fx[0].bypassed);
+        let takes = &drums.items[0].takes;
+        assert_eq!(takes[0].name, "Beat B");
+        assert!(takes[0].selected);
+        assert_eq!(takes[1].name, "Beat \"A\"");
+        assert!(!takes[1].selected);
+        assert_eq!(takes[1].source.as_ref().unwrap().midi_events().len(), 6);
+    }
+
+    #[test]
+    fn test_new_project() {
+        let mut project = RppProject::new();
+        project.tempo_markers.push(RppTempoMarker::new(0.0, 100.0));
+        project.markers.push(MarkerRegionInfo {
+            is_region: true,
+            user_index: 1,
+            enum_index: 0,
+            position: 1.0.into(),
+            rgn_end: 3.0.into(),
+            name: "Chorus".to_string(),
+            color: Color::new(0, 255, 0),
+        });
+        let mut track = RppTrack::new("lead");
+        track.folder_state = TrackFolderState::IsFolder(1);
+        track.color = Some(Color::new(1, 2, 3));
+        let mut envelope = RppEnvelope::new("VOLENV2", [""; 0]);
+        envelope.points.push((
+            2.0.into(),
+            EnvelopePoint::new(0.5, EnvelopePointShape::Beizer, 0.2, true),
+        ));
+        track.envelopes.push(envelope);
+        let mut source = RppSource::new_midi(960);
+        source.set_midi_events([
+            MidiEvent::new(
+                0,
+                false,
+                false,
+                CcShapeKind::default(),
+                RawMidiMessage::from_msg(NoteOnMessage::new(1, 60, 100)),
+            ),
+            MidiEvent::new(
+                960,
+                true,
+                true,
+                CcShapeKind::default(),
+                RawMidiMessage::from_msg(NoteOnMessage::new(1, 60, 0)),
+            ),
+        ]);
+        let mut item = RppItem::new(1.0, StdDuration::from_secs(2));
+        item.takes.push(RppTake::new("notes", source));
+        item.takes.push(RppTake::new(
+            "audio",
+            RppSource::new_file("WAVE", "audio file.wav"),
+        ));
+        track.items.push(item);
+        project.tracks.push(track);
+
+        let text = project.to_string();
+        assert!(text.contains("    PT 2 0.5 5 0 1 0 0.2\n"));
+        assert!(text.contains("        E 0 90 3c 64\n"));
+        assert!(text.contains("        em 960 90 3c 00\n"));
+        let parsed = RppProject::parse(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.tempo_markers, project.tempo_markers);
+        assert_eq!(parsed.markers, project.markers);
+        let track = &parsed.tracks[0];
+        assert_eq!(track.folder_state, TrackFolderState::IsFolder(1));
+        assert_eq!(track.color, Some(Color::new(1, 2, 3)));
+        assert_eq!(
+            track.envelopes[0].points,
+            project.tracks[0].envelopes[0].points
+        );
+        let takes = &track.items[0].takes;
+        assert_eq!(takes.len(), 2);
+        assert_eq!(takes[0].source.as_ref().unwrap().midi_events().len(), 2);
+        assert_eq!(
+            takes[1].source.as_ref().unwrap().file.as_deref(),
+            Some("audio file.wav")
+        );
+    }
+
+    #[test]
+    fn test_take_chunks() {
+        let project = RppProject::parse(PROJECT).unwrap();
+        let item = &project.tracks[0].items[0];
+        let chunks: Vec<String> =
+            item.takes.iter().map(|t| t.to_string()).collect();
+        assert!(chunks[0].starts_with("      NAME \"Beat A\"\n"));
+        assert!(chunks[0].ends_with("      >\n"));
+        assert!(chunks[1].starts_with("      TAKE SEL\n      NAME \"Beat B\""));
+        let block = item.to_block().to_string();
+        assert!(block.ends_with(&format!("{}{}    >\n", chunks[0], chunks[1])));
+    }
+
+    #[test]
+    fn test_guid_braced() {
+        let text = "{ABCDEF01-2345-6789-ABCD-EF0123456789}";
+        let guid = GUID::parse_braced(text).unwrap();
+        assert_eq!(guid.to_braced_string(), text);
+        let lower = GUID::parse_braced(&text.to_lowercase()).unwrap();
+        assert_eq!(lower, guid);
+        assert!(GUID::parse_braced("{ABCDEF01-2345}").is_err());
+    }
+}
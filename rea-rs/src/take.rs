@@ -7,9 +7,9 @@ use crate::{
     ptr_wrappers::{self, MediaItemTake, PcmSource, TrackEnvelope},
     utils::{as_c_str, as_c_string, as_string, string_from_buf, WithNull},
     AudioAccessor, Color, Envelope, FXParent, Immutable, Item, KnowsProject,
-    MidiEventBuilder, Mutable, Pan, PanLaw, Pitch, PlayRate, ProbablyMutable,
-    Project, ReaRsError, Reaper, ReaperResult, Source, SourceOffset, TakeFX,
-    Volume, WithReaperPtr, FX, GUID,
+    MidiEventBuilder, Mutable, Pan, PanLaw, Pitch, PlayRate, Position,
+    ProbablyMutable, Project, ReaRsError, Reaper, ReaperResult, Source,
+    SourceOffset, TakeFX, Volume, WithReaperPtr, FX, GUID,
 };
 use int_enum::IntEnum;
 use serde_derive::{Deserialize, Serialize};
@@ -128,6 +128,26 @@ impl<'a, T: ProbablyMutable> Take<'a, T> {
         }
     }
 
+    /// Project position of MIDI ppq position of the take.
+    pub fn ppq_to_position(&self, ppq: f64) -> Position {
+        let time = unsafe {
+            Reaper::get()
+                .low()
+                .MIDI_GetProjTimeFromPPQPos(self.get().as_ptr(), ppq)
+        };
+        Position::from(time)
+    }
+
+    /// MIDI ppq position of the take at project position.
+    pub fn position_to_ppq(&self, position: impl Into<Position>) -> f64 {
+        unsafe {
+            Reaper::get().low().MIDI_GetPPQPosFromProjTime(
+                self.get().as_ptr(),
+                position.into().into(),
+            )
+        }
+    }
+
     /// Get iterator on human-readable MIDI events.
     ///
     /// See [crate::midi]
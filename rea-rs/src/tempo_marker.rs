@@ -0,0 +1,341 @@
+//! Project tempo map: tempo and time signature markers.
+//!
+//! Every tempo marker is also a point of master tempo envelope with the
+//! same index, so its shape and tension are read from (and written to) the
+//! envelope point.
+//!
+//! Every single change updates timeline. To make several changes with one
+//! timeline update and one undo point, use [Project::edit_tempo_map].
+
+use std::ptr::null_mut;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    ptr_wrappers::TrackEnvelope,
+    utils::{as_c_str, WithNull},
+    Envelope, EnvelopePointShape, Immutable, KnowsProject, Mutable, Position,
+    Project, ReaRsError, Reaper, ReaperResult, TimeSignature, UndoFlags,
+    WithReaperPtr,
+};
+use int_enum::IntEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoMarker {
+    pub position: Position,
+    pub bpm: f64,
+    /// None if marker does not change time signature.
+    pub time_signature: Option<TimeSignature>,
+    /// Tempo ramp to the next marker.
+    ///
+    /// [EnvelopePointShape::Square] keeps tempo till the next marker, any
+    /// other shape makes gradual (linear, if not set) tempo change.
+    pub shape: EnvelopePointShape,
+    /// from -1.0 to 1.0, used by [EnvelopePointShape::Beizer].
+    pub tension: f64,
+}
+impl TempoMarker {
+    /// Marker, that keeps tempo till the next marker.
+    pub fn new(position: Position, bpm: f64) -> Self {
+        Self {
+            position,
+            bpm,
+            time_signature: None,
+            shape: EnvelopePointShape::Square,
+            tension: 0.0,
+        }
+    }
+    /// Whether tempo changes gradually to the next marker.
+    pub fn is_linear(&self) -> bool {
+        self.shape != EnvelopePointShape::Square
+    }
+    /// Set [EnvelopePointShape::Linear] or [EnvelopePointShape::Square].
+    pub fn set_linear(&mut self, linear: bool) {
+        self.shape = match linear {
+            true => EnvelopePointShape::Linear,
+            false => EnvelopePointShape::Square,
+        }
+    }
+}
+
+pub struct TempoMarkersIterator<'a> {
+    index: usize,
+    len: usize,
+    project: &'a Project,
+}
+impl<'a> TempoMarkersIterator<'a> {
+    pub(crate) fn new(project: &'a Project) -> Self {
+        Self {
+            index: 0,
+            len: project.n_tempo_markers(),
+            project,
+        }
+    }
+}
+impl<'a> Iterator for TempoMarkersIterator<'a> {
+    type Item = TempoMarker;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.len {
+            let current = self.index;
+            self.index += 1;
+            if let Some(marker) = self.project.tempo_marker(current) {
+                return Some(marker);
+            }
+        }
+        None
+    }
+}
+
+impl KnowsProject for Project {
+    fn project(&self) -> &Project {
+        self
+    }
+}
+
+impl Project {
+    pub fn tempo_marker(&self, index: usize) -> Option<TempoMarker> {
+        let (mut position, mut measure, mut beat, mut bpm) =
+            (0.0, 0, 0.0, 0.0);
+        let (mut num, mut denom, mut linear) = (0, 0, false);
+        let result = unsafe {
+            Reaper::get().low().GetTempoTimeSigMarker(
+                self.context().to_raw(),
+                index as i32,
+                &mut position,
+                &mut measure,
+                &mut beat,
+                &mut bpm,
+                &mut num,
+                &mut denom,
+                &mut linear,
+            )
+        };
+        if !result {
+            return None;
+        }
+        let mut marker = TempoMarker::new(Position::from(position), bpm);
+        if num > 0 {
+            marker.time_signature =
+                Some(TimeSignature::new(num as u32, denom as u32));
+        }
+        marker.set_linear(linear);
+        if let Some(envelope) = self.tempo_envelope_ptr() {
+            let (mut shape, mut tension) = (0, 0.0);
+            let has_point = unsafe {
+                Reaper::get().low().GetEnvelopePointEx(
+                    envelope.as_ptr(),
+                    -1,
+                    index as i32,
+                    null_mut(),
+                    null_mut(),
+                    &mut shape,
+                    &mut tension,
+                    null_mut(),
+                )
+            };
+            if has_point {
+                if let Ok(shape) = EnvelopePointShape::from_int(shape) {
+                    marker.shape = shape;
+                }
+                marker.tension = tension;
+            }
+        }
+        Some(marker)
+    }
+
+    pub fn iter_tempo_markers(&self) -> TempoMarkersIterator<'_> {
+        TempoMarkersIterator::new(self)
+    }
+
+    /// Index of the last tempo marker at or before position.
+    pub fn tempo_marker_index_at(&self, position: Position) -> Option<usize> {
+        let index = unsafe {
+            Reaper::get().low().FindTempoTimeSigMarker(
+                self.context().to_raw(),
+                position.into(),
+            )
+        };
+        match index {
+            x if x < 0 => None,
+            x => Some(x as usize),
+        }
+    }
+
+    /// Master tempo envelope. Its points are tempo markers.
+    pub fn tempo_envelope(&self) -> Option<Envelope<'_, Project, Immutable>> {
+        Some(Envelope::new(self.tempo_envelope_ptr()?, self))
+    }
+    /// Master tempo envelope. Its points are tempo markers.
+    pub fn tempo_envelope_mut(
+        &mut self,
+    ) -> Option<Envelope<'_, Project, Mutable>> {
+        Some(Envelope::new(self.tempo_envelope_ptr()?, self))
+    }
+    fn tempo_envelope_ptr(&self) -> Option<TrackEnvelope> {
+        let master = self.get_master_track();
+        let mut name = String::from("Tempo map");
+        let ptr = unsafe {
+            Reaper::get().low().GetTrackEnvelopeByName(
+                master.get().as_ptr(),
+                as_c_str(name.with_null()).as_ptr(),
+            )
+        };
+        TrackEnvelope::new(ptr)
+    }
+
+    /// Add tempo marker and return its index.
+    pub fn insert_tempo_marker(
+        &mut self,
+        marker: TempoMarker,
+    ) -> ReaperResult<usize> {
+        let index = self.set_tempo_marker_ex(None, marker)?;
+        Reaper::get().update_timeline();
+        Ok(index)
+    }
+
+    /// Replace tempo marker and return its new index, which changes, if
+    /// marker is moved over another one.
+    pub fn set_tempo_marker(
+        &mut self,
+        index: usize,
+        marker: TempoMarker,
+    ) -> ReaperResult<usize> {
+        let index = self.set_tempo_marker_ex(Some(index), marker)?;
+        Reaper::get().update_timeline();
+        Ok(index)
+    }
+
+    pub fn delete_tempo_marker(&mut self, index: usize) -> ReaperResult<()> {
+        self.delete_tempo_marker_ex(index)?;
+        Reaper::get().update_timeline();
+        Ok(())
+    }
+
+    /// Make several tempo map changes with one timeline update and one
+    /// undo point.
+    ///
+    /// Timeline is updated and undo block is closed even if `f` fails.
+    pub fn edit_tempo_map<R>(
+        &mut self,
+        undo_name: impl Into<String>,
+        f: impl FnOnce(&mut TempoMapEditor) -> ReaperResult<R>,
+    ) -> ReaperResult<R> {
+        self.begin_undo_block();
+        let result = f(&mut TempoMapEditor { project: self });
+        Reaper::get().update_timeline();
+        self.end_undo_block(undo_name, UndoFlags::PROJECT_STATES);
+        result
+    }
+
+    fn set_tempo_marker_ex(
+        &mut self,
+        index: Option<usize>,
+        marker: TempoMarker,
+    ) -> ReaperResult<usize> {
+        let (num, denom) = match marker.time_signature {
+            None => (0, 0),
+            Some(sig) => (sig.numerator as i32, sig.denominator as i32),
+        };
+        let result = unsafe {
+            Reaper::get().low().SetTempoTimeSigMarker(
+                self.context().to_raw(),
+                index.map(|idx| idx as i32).unwrap_or(-1),
+                marker.position.into(),
+                -1,
+                -1.0,
+                marker.bpm,
+                num,
+                denom,
+                marker.is_linear(),
+            )
+        };
+        if !result {
+            return Err(ReaRsError::UnsuccessfulOperation(
+                "Can not set tempo marker",
+            ));
+        }
+        let index = self.tempo_marker_index_at(marker.position).ok_or(
+            ReaRsError::UnsuccessfulOperation("Can not find tempo marker"),
+        )?;
+        if let Some(envelope) = self.tempo_envelope_ptr() {
+            let mut shape = marker.shape.int_value();
+            let mut tension = marker.tension;
+            let mut no_sort = true;
+            unsafe {
+                Reaper::get().low().SetEnvelopePointEx(
+                    envelope.as_ptr(),
+                    -1,
+                    index as i32,
+                    null_mut(),
+                    null_mut(),
+                    &mut shape,
+                    &mut tension,
+                    null_mut(),
+                    &mut no_sort,
+                )
+            };
+        }
+        Ok(index)
+    }
+
+    fn delete_tempo_marker_ex(&mut self, index: usize) -> ReaperResult<()> {
+        let result = unsafe {
+            Reaper::get().low().DeleteTempoTimeSigMarker(
+                self.context().to_raw(),
+                index as i32,
+            )
+        };
+        match result {
+            true => Ok(()),
+            false => Err(ReaRsError::UnsuccessfulOperation(
+                "Can not delete tempo marker",
+            )),
+        }
+    }
+}
+
+/// Tempo map changes without timeline update.
+///
+/// Made by [Project::edit_tempo_map].
+pub struct TempoMapEditor<'a> {
+    project: &'a mut Project,
+}
+impl<'a> TempoMapEditor<'a> {
+    pub fn project(&self) -> &Project {
+        self.project
+    }
+    /// Add tempo marker and return its index.
+    pub fn insert(&mut self, marker: TempoMarker) -> ReaperResult<usize> {
+        self.project.set_tempo_marker_ex(None, marker)
+    }
+    /// Replace tempo marker and return its new index.
+    pub fn set(
+        &mut self,
+        index: usize,
+        marker: TempoMarker,
+    ) -> ReaperResult<usize> {
+        self.project.set_tempo_marker_ex(Some(index), marker)
+    }
+    pub fn delete(&mut self, index: usize) -> ReaperResult<()> {
+        self.project.delete_tempo_marker_ex(index)
+    }
+    /// Delete all tempo markers.
+    pub fn clear(&mut self) -> ReaperResult<()> {
+        for index in (0..self.project.n_tempo_markers()).rev() {
+            self.delete(index)?;
+        }
+        Ok(())
+    }
+    /// Replace the whole tempo map.
+    pub fn replace(
+        &mut self,
+        markers: impl IntoIterator<Item = TempoMarker>,
+    ) -> ReaperResult<()> {
+        self.clear()?;
+        for marker in markers {
+            self.insert(marker)?;
+        }
+        Ok(())
+    }
+}
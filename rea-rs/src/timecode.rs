@@ -0,0 +1,336 @@
+//! SMPTE timecode.
+//!
+//! Conversions are pure math, so they can be used without REAPER.
+//! [Project::timecode] and [Project::position_from_timecode] use project
+//! frame rate and start time (timecode offset).
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{FrameRate, Position, Timecode};
+//!
+//! let rate = FrameRate::Fps29_97Df;
+//! let tc = Timecode::parse("00:10:00;00", rate).unwrap();
+//! assert_eq!(tc.to_frames(), 17982);
+//!
+//! // Project starts at 01:00:00:00.
+//! let offset = 3600.0;
+//! let tc = Timecode::from_position(Position::from(1.5), rate, offset);
+//! assert_eq!(tc.to_string(), "01:00:01;15");
+//! // Position of the frame start.
+//! let start = tc.to_position(offset);
+//! assert!(start <= Position::from(1.5));
+//! assert_eq!(Timecode::from_position(start, rate, offset), tc);
+//! ```
+
+use std::{fmt::Display, mem::MaybeUninit};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{Position, Project, ReaRsError, Reaper, ReaperResult};
+
+/// Tolerance (in frames) for rounding on frame boundaries.
+const EPSILON: f64 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FrameRate {
+    Fps23_976,
+    Fps24,
+    Fps25,
+    /// 29.97 drop frame.
+    Fps29_97Df,
+    /// 29.97 non-drop frame.
+    Fps29_97Ndf,
+    Fps30,
+    Fps50,
+    Fps60,
+}
+impl FrameRate {
+    /// Real frames per second.
+    pub fn fps(&self) -> f64 {
+        match self {
+            Self::Fps23_976 => 24000.0 / 1001.0,
+            Self::Fps29_97Df | Self::Fps29_97Ndf => 30000.0 / 1001.0,
+            _ => self.nominal() as f64,
+        }
+    }
+    /// Frames per timecode second.
+    pub fn nominal(&self) -> u32 {
+        match self {
+            Self::Fps23_976 | Self::Fps24 => 24,
+            Self::Fps25 => 25,
+            Self::Fps29_97Df | Self::Fps29_97Ndf | Self::Fps30 => 30,
+            Self::Fps50 => 50,
+            Self::Fps60 => 60,
+        }
+    }
+    pub fn is_drop_frame(&self) -> bool {
+        *self == Self::Fps29_97Df
+    }
+    /// Frame rate, which fps is close to given.
+    pub fn from_fps(fps: f64, drop_frame: bool) -> Option<Self> {
+        [
+            Self::Fps23_976,
+            Self::Fps24,
+            Self::Fps25,
+            Self::Fps29_97Ndf,
+            Self::Fps30,
+            Self::Fps50,
+            Self::Fps60,
+        ]
+        .into_iter()
+        .find(|rate| (rate.fps() - fps).abs() < 0.001)
+        .map(|rate| match (rate, drop_frame) {
+            (Self::Fps29_97Ndf, true) => Self::Fps29_97Df,
+            (rate, _) => rate,
+        })
+    }
+    /// Number of frames in 24 hours of timecode.
+    fn frames_per_day(&self) -> u64 {
+        match self.is_drop_frame() {
+            true => 144 * 17982,
+            false => self.nominal() as u64 * 86400,
+        }
+    }
+}
+
+/// SMPTE timecode `HH:MM:SS:FF` (`HH:MM:SS;FF` for drop frame).
+///
+/// Timecode wraps around 24 hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    pub rate: FrameRate,
+}
+impl Timecode {
+    /// Fails if fields are out of range, or if drop frame timecode uses
+    /// dropped frame number.
+    pub fn new(
+        hours: u32,
+        minutes: u32,
+        seconds: u32,
+        frames: u32,
+        rate: FrameRate,
+    ) -> ReaperResult<Self> {
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(ReaRsError::InvalidObject("timecode out of range"));
+        }
+        if frames >= rate.nominal() {
+            return Err(ReaRsError::InvalidObject("frame out of range"));
+        }
+        if rate.is_drop_frame()
+            && seconds == 0
+            && frames < 2
+            && !minutes.is_multiple_of(10)
+        {
+            return Err(ReaRsError::InvalidObject("dropped frame number"));
+        }
+        Ok(Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        })
+    }
+    /// Parse `HH:MM:SS:FF`. Frames may be separated by `;` or `.` as well.
+    pub fn parse(text: &str, rate: FrameRate) -> ReaperResult<Self> {
+        let parts: Vec<u32> = text
+            .trim()
+            .split([':', ';', '.'])
+            .map(|part| part.parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| {
+                ReaRsError::InvalidObject("timecode is not a number")
+            })?;
+        match parts[..] {
+            [hours, minutes, seconds, frames] => {
+                Self::new(hours, minutes, seconds, frames, rate)
+            }
+            _ => Err(ReaRsError::InvalidObject("expected \"HH:MM:SS:FF\"")),
+        }
+    }
+
+    /// Timecode of frame count from `00:00:00:00`.
+    pub fn from_frames(frames: u64, rate: FrameRate) -> Self {
+        let mut frames = frames % rate.frames_per_day();
+        if rate.is_drop_frame() {
+            let (tens, rest) = (frames / 17982, frames % 17982);
+            frames += 18 * tens;
+            if rest >= 2 {
+                frames += 2 * ((rest - 2) / 1798);
+            }
+        }
+        let nominal = rate.nominal() as u64;
+        Self {
+            hours: (frames / (nominal * 3600)) as u32,
+            minutes: (frames / (nominal * 60) % 60) as u32,
+            seconds: (frames / nominal % 60) as u32,
+            frames: (frames % nominal) as u32,
+            rate,
+        }
+    }
+    /// Frame count from `00:00:00:00`.
+    pub fn to_frames(&self) -> u64 {
+        let nominal = self.rate.nominal() as u64;
+        let minutes = self.hours as u64 * 60 + self.minutes as u64;
+        let frames = (minutes * 60 + self.seconds as u64) * nominal
+            + self.frames as u64;
+        match self.rate.is_drop_frame() {
+            true => frames - 2 * (minutes - minutes / 10),
+            false => frames,
+        }
+    }
+
+    /// Timecode of the frame, containing the time.
+    ///
+    /// Negative time wraps around 24 hours.
+    pub fn from_seconds(seconds: f64, rate: FrameRate) -> Self {
+        let frames = (seconds * rate.fps() + EPSILON).floor() as i64;
+        let frames = frames.rem_euclid(rate.frames_per_day() as i64);
+        Self::from_frames(frames as u64, rate)
+    }
+    /// Real time of the frame start.
+    pub fn as_seconds(&self) -> f64 {
+        self.to_frames() as f64 / self.rate.fps()
+    }
+
+    /// Timecode of project position.
+    ///
+    /// offset is project start time in seconds.
+    pub fn from_position(
+        position: Position,
+        rate: FrameRate,
+        offset: f64,
+    ) -> Self {
+        let seconds: f64 = position.into();
+        Self::from_seconds(seconds + offset, rate)
+    }
+    /// Project position of the frame start.
+    ///
+    /// offset is project start time in seconds. Timecode before project
+    /// start gives zero position.
+    pub fn to_position(&self, offset: f64) -> Position {
+        Position::from((self.as_seconds() - offset).max(0.0))
+    }
+}
+impl Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = match self.rate.is_drop_frame() {
+            true => ';',
+            false => ':',
+        };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+impl Project {
+    /// Project frame rate, if it is one of [FrameRate].
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        let mut drop_frame = MaybeUninit::zeroed();
+        let fps = unsafe {
+            Reaper::get().low().TimeMap_curFrameRate(
+                self.context().to_raw(),
+                drop_frame.as_mut_ptr(),
+            )
+        };
+        FrameRate::from_fps(fps, unsafe { drop_frame.assume_init() })
+    }
+    /// Project start time (timecode offset) in seconds.
+    pub fn time_offset(&self) -> f64 {
+        unsafe {
+            Reaper::get()
+                .low()
+                .GetProjectTimeOffset(self.context().to_raw(), false)
+        }
+    }
+    /// Timecode of position with project frame rate and start time.
+    pub fn timecode(&self, position: Position) -> ReaperResult<Timecode> {
+        let rate = self.frame_rate().ok_or(ReaRsError::InvalidObject(
+            "project frame rate is not SMPTE",
+        ))?;
+        Ok(Timecode::from_position(position, rate, self.time_offset()))
+    }
+    /// Position of timecode with project start time.
+    pub fn position_from_timecode(&self, timecode: &Timecode) -> Position {
+        timecode.to_position(self.time_offset())
+    }
+    /// Parse timecode with project frame rate.
+    pub fn parse_timecode(&self, text: &str) -> ReaperResult<Timecode> {
+        let rate = self.frame_rate().ok_or(ReaRsError::InvalidObject(
+            "project frame rate is not SMPTE",
+        ))?;
+        Timecode::parse(text, rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_frame() {
+        let rate = FrameRate::Fps29_97Df;
+        let at = |frames| Timecode::from_frames(frames, rate).to_string();
+        assert_eq!(at(1799), "00:00:59;29");
+        assert_eq!(at(1800), "00:01:00;02");
+        assert_eq!(at(17981), "00:09:59;29");
+        assert_eq!(at(17982), "00:10:00;00");
+        assert_eq!(at(107892), "01:00:00;00");
+        for frames in [0, 1799, 1800, 17982, 17984, 2589407] {
+            assert_eq!(
+                Timecode::from_frames(frames, rate).to_frames(),
+                frames
+            );
+        }
+        assert_eq!(at(2589408), "00:00:00;00");
+        assert!(Timecode::new(0, 1, 0, 1, rate).is_err());
+        assert!(Timecode::new(0, 10, 0, 1, rate).is_ok());
+    }
+
+    #[test]
+    fn test_seconds() {
+        let tc = Timecode::from_seconds(3600.0, FrameRate::Fps29_97Df);
+        assert_eq!(tc.to_string(), "01:00:00;00");
+        let tc = Timecode::from_seconds(3600.0, FrameRate::Fps29_97Ndf);
+        assert_eq!(tc.to_string(), "00:59:56:12");
+        let tc = Timecode::from_seconds(10.5, FrameRate::Fps25);
+        assert_eq!(tc.to_string(), "00:00:10:12");
+        assert_eq!(tc.as_seconds(), 10.48);
+        let tc = Timecode::from_seconds(1001.0, FrameRate::Fps23_976);
+        assert_eq!(tc.to_frames(), 24000);
+        assert!((tc.as_seconds() - 1001.0).abs() < 1e-9);
+        let tc = Timecode::from_seconds(-1.0, FrameRate::Fps60);
+        assert_eq!(tc.to_string(), "23:59:59:00");
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let tc = Timecode::parse("01:02:03:04", FrameRate::Fps24).unwrap();
+        assert_eq!(tc.to_string(), "01:02:03:04");
+        assert_eq!(tc.to_frames(), ((62 * 60) + 3) * 24 + 4);
+        let tc = Timecode::parse("00:00:01.49", FrameRate::Fps50).unwrap();
+        assert_eq!(tc.to_string(), "00:00:01:49");
+        assert!(Timecode::parse("00:00:01:25", FrameRate::Fps25).is_err());
+        assert!(Timecode::parse("00:60:00:00", FrameRate::Fps30).is_err());
+        assert!(Timecode::parse("00:00:00", FrameRate::Fps30).is_err());
+        assert!(Timecode::parse("aa:00:00:00", FrameRate::Fps30).is_err());
+    }
+
+    #[test]
+    fn test_frame_rate() {
+        let rate = FrameRate::from_fps(29.97002997, true);
+        assert_eq!(rate, Some(FrameRate::Fps29_97Df));
+        let rate = FrameRate::from_fps(23.976, false);
+        assert_eq!(rate, Some(FrameRate::Fps23_976));
+        assert_eq!(FrameRate::from_fps(25.0, true), Some(FrameRate::Fps25));
+        assert_eq!(FrameRate::from_fps(48.0, false), None);
+    }
+}
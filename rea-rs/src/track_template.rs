@@ -0,0 +1,477 @@
+//! Track templates (`.RTrackTemplate` files).
+//!
+//! Template is a sequence of track state chunks. Receives (`AUXRECV`) refer
+//! to the source track by index, which inside template is relative to the
+//! first template track. Receives from tracks, that are not in template,
+//! are encoded out of template range: sources before the first template
+//! track keep their (negative) relative index, later sources are moved
+//! past the last template track by the number of template tracks. Such
+//! receives are reported as [UnconnectedReceive] on insertion.
+//!
+//! # Example
+//!
+//! ```
+//! use rea_rs::{ChunkBlock, ChunkLine, TrackTemplate, GUID};
+//!
+//! let mut bus = ChunkBlock::new("TRACK", [""; 0]);
+//! bus.push(ChunkLine::new("AUXRECV", ["4", "0", "1", "0", "0", "0", "0"]));
+//! let drums = ChunkBlock::new("TRACK", [""; 0]);
+//! let template = TrackTemplate::from_project_tracks([(3, bus), (4, drums)]);
+//! assert!(template.to_string().contains("AUXRECV 1 0 1"));
+//!
+//! let mut count = 0;
+//! let new_guid = || {
+//!     count += 1;
+//!     let text = format!("{{{:08X}-0000-0000-0000-000000000000}}", count);
+//!     GUID::parse_braced(&text).unwrap()
+//! };
+//! let (tracks, unconnected) = template.to_project_tracks(10, new_guid);
+//! assert_eq!(tracks[0].line("AUXRECV").unwrap().param(0), Some("11"));
+//! assert!(unconnected.is_empty());
+//! ```
+
+use std::{collections::HashMap, fmt::Display, ops::Range, path::Path};
+
+use crate::{
+    Chunk, ChunkBlock, ChunkLine, ChunkNode, Project, ReaRsError,
+    ReaperResult, RppTrack, TrackFolderState, GUID,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrackTemplate {
+    pub tracks: Vec<ChunkBlock>,
+}
+impl TrackTemplate {
+    /// Parse `.RTrackTemplate` text: all root `TRACK` blocks.
+    pub fn parse(text: &str) -> ReaperResult<Self> {
+        let chunk = Chunk::parse(text)?;
+        let tracks: Vec<ChunkBlock> = chunk
+            .nodes
+            .into_iter()
+            .filter_map(|n| match n {
+                ChunkNode::Block(b) if b.name() == "TRACK" => Some(b),
+                _ => None,
+            })
+            .collect();
+        match tracks.is_empty() {
+            true => Err(ReaRsError::InvalidObject("template has no tracks")),
+            false => Ok(Self { tracks }),
+        }
+    }
+    pub fn from_file(path: impl AsRef<Path>) -> ReaperResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ReaRsError::Io(e.to_string()))?;
+        Self::parse(&text)
+    }
+    pub fn write_file(&self, path: impl AsRef<Path>) -> ReaperResult<()> {
+        std::fs::write(path, self.to_string())
+            .map_err(|e| ReaRsError::Io(e.to_string()))
+    }
+
+    /// Make template from track chunks with their project indexes.
+    ///
+    /// Tracks are sorted by index, receive indexes are made relative to
+    /// the first track, and folder depth is balanced, so template neither
+    /// closes nor leaves open folders outside of it.
+    pub fn from_project_tracks(
+        tracks: impl IntoIterator<Item = (usize, ChunkBlock)>,
+    ) -> Self {
+        let mut tracks: Vec<(usize, ChunkBlock)> =
+            tracks.into_iter().collect();
+        tracks.sort_by_key(|(idx, _)| *idx);
+        let Some(first) = tracks.first().map(|(idx, _)| *idx as i64) else {
+            return Self::default();
+        };
+        let n_tracks = tracks.len() as i64;
+        let index_map: HashMap<i64, i64> = tracks
+            .iter()
+            .enumerate()
+            .map(|(pos, (idx, _))| (*idx as i64, pos as i64))
+            .collect();
+        let mut blocks: Vec<ChunkBlock> = tracks
+            .into_iter()
+            .map(|(_, mut block)| {
+                map_receives(&mut block, |source| {
+                    match index_map.get(&source) {
+                        Some(pos) => Some(*pos),
+                        None if source < first => Some(source - first),
+                        None => Some(source - first + n_tracks),
+                    }
+                });
+                block
+            })
+            .collect();
+        balance_folders(&mut blocks);
+        Self { tracks: blocks }
+    }
+
+    /// Track chunks to be inserted at `index` of project.
+    ///
+    /// All GUIDs are replaced by ones from `new_guid` (the same GUID is
+    /// replaced consistently across tracks). Receives are pointed to the
+    /// inserted tracks, and ones with source outside template are removed
+    /// and returned.
+    pub fn to_project_tracks(
+        &self,
+        index: usize,
+        mut new_guid: impl FnMut() -> GUID,
+    ) -> (Vec<ChunkBlock>, Vec<UnconnectedReceive>) {
+        let mut guids = HashMap::new();
+        let mut unconnected = Vec::new();
+        let n_tracks = self.tracks.len() as i64;
+        let tracks = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(pos, block)| {
+                let mut block = block.clone();
+                remap_guids(&mut block, &mut guids, &mut new_guid);
+                for line in block.lines_named("AUXRECV") {
+                    let Some(source) = receive_source(line) else {
+                        continue;
+                    };
+                    if !(0..n_tracks).contains(&source) {
+                        unconnected.push(UnconnectedReceive {
+                            track_index: index + pos,
+                            source_offset: match source < 0 {
+                                true => source,
+                                false => source - n_tracks,
+                            },
+                            line: line.clone(),
+                        });
+                    }
+                }
+                map_receives(&mut block, |source| {
+                    match (0..n_tracks).contains(&source) {
+                        true => Some(source + index as i64),
+                        false => None,
+                    }
+                });
+                block
+            })
+            .collect();
+        (tracks, unconnected)
+    }
+}
+impl Display for TrackTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for track in self.tracks.iter() {
+            track.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Receive of template track, which source is not in template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnconnectedReceive {
+    /// Project index of the inserted track.
+    pub track_index: usize,
+    /// Source index relative to the first template track.
+    pub source_offset: i64,
+    /// `AUXRECV` line as it was in template.
+    pub line: ChunkLine,
+}
+
+/// Result of [Project::insert_track_template].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackTemplateInsert {
+    /// Project indexes of inserted tracks.
+    pub tracks: Range<usize>,
+    pub unconnected_receives: Vec<UnconnectedReceive>,
+}
+
+impl Project {
+    /// Make template of tracks with the given indexes.
+    ///
+    /// If `with_children`, all tracks inside folders are included.
+    pub fn track_template(
+        &self,
+        indexes: impl IntoIterator<Item = usize>,
+        with_children: bool,
+    ) -> ReaperResult<TrackTemplate> {
+        let depths: Vec<i32> = (0..self.n_tracks())
+            .filter_map(|idx| self.get_track(idx))
+            .map(|t| t.folder_state().to_raw().0)
+            .collect();
+        let mut indexes: Vec<usize> = indexes.into_iter().collect();
+        if with_children {
+            let children: Vec<usize> = indexes
+                .iter()
+                .flat_map(|idx| folder_children(&depths, *idx))
+                .collect();
+            indexes.extend(children);
+        }
+        indexes.sort();
+        indexes.dedup();
+        let tracks = indexes
+            .into_iter()
+            .map(|idx| {
+                let track = self
+                    .get_track(idx)
+                    .ok_or(ReaRsError::InvalidObject("no track at index"))?;
+                let chunk = track.chunk_tree()?;
+                let root = chunk
+                    .root()
+                    .ok_or(ReaRsError::InvalidObject("empty track chunk"))?;
+                Ok((idx, root.clone()))
+            })
+            .collect::<ReaperResult<Vec<_>>>()?;
+        Ok(TrackTemplate::from_project_tracks(tracks))
+    }
+
+    /// Insert template tracks at index (or to the end), with new GUIDs.
+    ///
+    /// Receives from tracks, that are not in template, can not be
+    /// reconnected and are returned in [TrackTemplateInsert].
+    pub fn insert_track_template(
+        &mut self,
+        template: &TrackTemplate,
+        index: impl Into<Option<usize>>,
+    ) -> ReaperResult<TrackTemplateInsert> {
+        let n_tracks = self.n_tracks();
+        let index = index.into().unwrap_or(n_tracks).min(n_tracks);
+        let (tracks, unconnected_receives) =
+            template.to_project_tracks(index, GUID::new);
+        // All tracks have to exist before chunks are set, so receives
+        // find their sources.
+        for pos in 0..tracks.len() {
+            self.add_track(index + pos, "");
+        }
+        for (pos, block) in tracks.iter().enumerate() {
+            self.get_track_mut(index + pos)
+                .ok_or(ReaRsError::InvalidObject("no inserted track"))?
+                .set_chunk(block.to_string(), false)?;
+        }
+        Ok(TrackTemplateInsert {
+            tracks: index..index + tracks.len(),
+            unconnected_receives,
+        })
+    }
+}
+
+/// Indexes of tracks inside the folder, which starts at `index`.
+///
+/// `depths` are folder depth changes of all project tracks.
+pub fn folder_children(depths: &[i32], index: usize) -> Range<usize> {
+    let start = index + 1;
+    if depths.get(index).copied().unwrap_or(0) <= 0 {
+        return start..start;
+    }
+    let mut depth = 0;
+    for (idx, change) in depths.iter().enumerate().skip(index) {
+        depth += change;
+        if depth <= 0 {
+            return start..idx + 1;
+        }
+    }
+    start..depths.len()
+}
+
+fn receive_source(line: &ChunkLine) -> Option<i64> {
+    line.param(0)?.parse().ok()
+}
+
+/// Map source index of every `AUXRECV` line, remove it if `None`.
+fn map_receives(
+    block: &mut ChunkBlock,
+    mut map: impl FnMut(i64) -> Option<i64>,
+) {
+    block.children_mut().retain_mut(|node| {
+        let Some(line) = node.as_line_mut().filter(|l| l.name() == "AUXRECV")
+        else {
+            return true;
+        };
+        let Some(source) = receive_source(line) else {
+            return true;
+        };
+        match map(source) {
+            Some(new) if new == source => true,
+            Some(new) => {
+                line.set_param(0, new.to_string());
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+/// Clamp folder depth changes, so they sum to zero and never go below.
+fn balance_folders(blocks: &mut [ChunkBlock]) {
+    let mut depth = 0;
+    let n_blocks = blocks.len();
+    for (idx, block) in blocks.iter_mut().enumerate() {
+        let mut track = RppTrack::from_block(block);
+        let (change, compact) = track.folder_state.to_raw();
+        let new_change = match idx + 1 == n_blocks {
+            true => -depth,
+            false => change.max(-depth),
+        };
+        depth += new_change;
+        if new_change == change {
+            continue;
+        }
+        track.folder_state = match new_change {
+            0 => TrackFolderState::Normal,
+            1 => TrackFolderState::IsFolder(compact.unwrap_or(0)),
+            d => TrackFolderState::Last(d.unsigned_abs()),
+        };
+        *block = track.to_block();
+    }
+}
+
+/// Replace every GUID token (in block headers and lines), keeping the
+/// same replacement for the same GUID.
+fn remap_guids(
+    block: &mut ChunkBlock,
+    guids: &mut HashMap<String, GUID>,
+    new_guid: &mut impl FnMut() -> GUID,
+) {
+    remap_line_guids(block.header_mut(), guids, new_guid);
+    for node in block.children_mut() {
+        match node {
+            ChunkNode::Line(line) => remap_line_guids(line, guids, new_guid),
+            ChunkNode::Block(child) => remap_guids(child, guids, new_guid),
+            ChunkNode::Blob(_) => (),
+        }
+    }
+}
+
+fn remap_line_guids(
+    line: &mut ChunkLine,
+    guids: &mut HashMap<String, GUID>,
+    new_guid: &mut impl FnMut() -> GUID,
+) {
+    for idx in 0..line.params().len() {
+        let Some(old) =
+            line.param(idx).and_then(|p| GUID::parse_braced(p).ok())
+        else {
+            continue;
+        };
+        let new = *guids
+            .entry(old.to_braced_string())
+            .or_insert_with(&mut *new_guid);
+        line.set_param(idx, new.to_braced_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RppProject;
+
+    const PROJECT: &str = include_str!("../fixtures/project.rpp");
+
+    fn counter_guid() -> impl FnMut() -> GUID {
+        let mut count = 0;
+        move || {
+            count += 1;
+            let text = format!("{{00000000-0000-0000-0000-{:012X}}}", count);
+            GUID::parse_braced(&text).unwrap()
+        }
+    }
+
+    fn project_tracks() -> Vec<ChunkBlock> {
+        let project = RppProject::parse(PROJECT).unwrap();
+        let mut tracks: Vec<ChunkBlock> =
+            project.tracks.iter().map(|t| t.to_block()).collect();
+        let receives = [["6", "0", "1", "0"], ["2", "0", "0.5", "0"]];
+        for receive in receives {
+            tracks[0].push(ChunkLine::new("AUXRECV", receive));
+        }
+        tracks
+    }
+
+    #[test]
+    fn test_export() {
+        let tracks = project_tracks();
+        let template = TrackTemplate::from_project_tracks([
+            (6, tracks[1].clone()),
+            (5, tracks[0].clone()),
+        ]);
+        let text = template.to_string();
+        let first_line = text.lines().next().unwrap();
+        assert_eq!(
+            first_line.trim(),
+            "<TRACK {A1B2C3D4-0000-4000-8000-000000000001}"
+        );
+        assert!(text.contains("  AUXRECV 1 0 1 0\n"));
+        assert!(text.contains("  AUXRECV -3 0 0.5 0\n"));
+        assert_eq!(TrackTemplate::parse(&text).unwrap().to_string(), text);
+        assert!(TrackTemplate::parse("NAME x\n").is_err());
+
+        let kick =
+            TrackTemplate::from_project_tracks([(6, tracks[1].clone())]);
+        let kick = RppTrack::from_block(&kick.tracks[0]);
+        assert_eq!(kick.folder_state, TrackFolderState::Normal);
+        let drums =
+            TrackTemplate::from_project_tracks([(5, tracks[0].clone())]);
+        let drums = RppTrack::from_block(&drums.tracks[0]);
+        assert_eq!(drums.folder_state, TrackFolderState::Normal);
+    }
+
+    #[test]
+    fn test_import() {
+        let tracks = project_tracks();
+        let template = TrackTemplate::from_project_tracks([
+            (5, tracks[0].clone()),
+            (6, tracks[1].clone()),
+        ]);
+        let (inserted, unconnected) =
+            template.to_project_tracks(10, counter_guid());
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(unconnected.len(), 1);
+        assert_eq!(unconnected[0].track_index, 10);
+        assert_eq!(unconnected[0].source_offset, -3);
+        assert_eq!(unconnected[0].line.param(2), Some("0.5"));
+
+        let drums = RppTrack::from_block(&inserted[0]);
+        let receives: Vec<_> = inserted[0].lines_named("AUXRECV").collect();
+        assert_eq!(receives.len(), 1);
+        assert_eq!(receives[0].param(0), Some("11"));
+        assert_eq!(
+            inserted[0].param(0),
+            drums.guid.map(|g| g.to_braced_string()).as_deref()
+        );
+        let text: String = inserted.iter().map(|t| t.to_string()).collect();
+        assert!(!text.contains("A1B2C3D4"));
+        assert!(!text.contains("99999999-8888"));
+        assert!(text.contains("{00000000-0000-0000-0000-000000000001}"));
+
+        // the second insertion gets other GUIDs
+        let (again, _) = template.to_project_tracks(0, || {
+            GUID::parse_braced("{FFFFFFFF-0000-0000-0000-000000000000}")
+                .unwrap()
+        });
+        assert_ne!(again, inserted);
+    }
+
+    #[test]
+    fn test_non_contiguous() {
+        let tracks = project_tracks();
+        let mut bus = tracks[1].clone();
+        bus.push(ChunkLine::new("AUXRECV", ["6", "0", "1", "0"]));
+        let template = TrackTemplate::from_project_tracks([
+            (5, tracks[0].clone()),
+            (7, bus),
+        ]);
+        // track 6 is not in template, so it must not become a self-receive.
+        assert!(template.to_string().contains("  AUXRECV 3 0 1 0\n"));
+        let (inserted, unconnected) =
+            template.to_project_tracks(10, counter_guid());
+        assert_eq!(inserted[1].lines_named("AUXRECV").count(), 0);
+        assert_eq!(unconnected.len(), 3);
+        let from_gap = &unconnected[2];
+        assert_eq!(from_gap.track_index, 11);
+        assert_eq!(from_gap.source_offset, 1);
+    }
+
+    #[test]
+    fn test_children() {
+        let depths = [0, 1, 0, 1, 0, -2, 0];
+        assert_eq!(folder_children(&depths, 1), 2..6);
+        assert_eq!(folder_children(&depths, 3), 4..6);
+        assert_eq!(folder_children(&depths, 2), 3..3);
+        assert_eq!(folder_children(&[1, 0], 0), 1..2);
+    }
+}
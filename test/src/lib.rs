@@ -11,12 +11,14 @@ use rea_rs::{
     EnvelopePointShape, EnvelopeSelector, EnvelopeSendInfo, ExtState,
     GenericSend, GenericSendMut, HardwareSocket, Immutable, ItemFade,
     MarkerRegionInfo, MessageBoxValue, Mutable, Pan, PanLaw, Pitch, PlayRate,
-    PluginContext, Position, Project, RazorEdit, ReaRsError, Reaper, RecInput,
-    RecMode, RecMonitoring, RecOutMode, SampleAmount, SendDestChannels,
-    SendMIDIProps, SendMode, SendSourceChannels, SoloMode, SourceOffset,
-    TakeChannelMode, TakePitchMode, TimeMode, Track, TrackFolderState,
-    TrackGroupParam, TrackPan, TrackPerformanceFlags, TrackPlayOffset,
-    TrackSend, UndoFlags, VUMode, Volume, WithReaperPtr, FX, GUID,
+    PlayState, PluginContext, Position, Project, RazorEdit, ReaRsError,
+    Reaper, RecInput, RecMode, RecMonitoring, RecOutMode, RecordMode,
+    SampleAmount, SendDestChannels, SendMIDIProps, SendMode,
+    SendSourceChannels, SoloMode, SourceOffset, TakeChannelMode,
+    TakePitchMode, TempoMarker, TimeMode, TimeSignature, Track,
+    TrackFolderState, TrackGroupParam, TrackPan, TrackPerformanceFlags,
+    TrackPlayOffset, TrackSend, UndoFlags, VUMode, Volume, WithReaperPtr, FX,
+    GUID,
 };
 use rea_rs_macros::reaper_extension_plugin;
 use rea_rs_test::{TestStep, TestStepResult};
@@ -58,6 +60,7 @@ pub fn create_test_steps() -> impl Iterator<Item = TestStep> {
         misc_types(),
         ext_state(),
         markers(),
+        tempo_markers(),
         tracks(),
         sends(),
         envelopes(),
@@ -200,6 +203,27 @@ fn projects() -> TestStep {
         pr.stop();
         assert_eq!(pr.is_stopped(), true);
         assert_eq!(pr.is_playing(), false);
+        assert_eq!(pr.play_state(), PlayState::Stopped);
+        pr.play_from(Position::from(2.0));
+        assert_eq!(pr.play_state(), PlayState::Playing);
+        pr.stop();
+
+        pr.set_loop_points(Position::from(1.0), Position::from(3.0));
+        assert_eq!(
+            pr.loop_points(),
+            (Position::from(1.0), Position::from(3.0))
+        );
+        pr.set_record_mode(RecordMode::TimeSelectionPunch);
+        assert_eq!(pr.record_mode(), RecordMode::TimeSelectionPunch);
+        pr.set_record_mode(RecordMode::Normal);
+        assert_eq!(pr.record_mode(), RecordMode::Normal);
+        let metronome = pr.is_metronome_enabled();
+        pr.set_metronome_enabled(!metronome);
+        assert_eq!(pr.is_metronome_enabled(), !metronome);
+        pr.set_metronome_enabled(metronome);
+        pr.set_play_rate(PlayRate::from(1.5))?;
+        assert_eq!(pr.play_rate(), PlayRate::from(1.5));
+        pr.set_play_rate(PlayRate::from(1.0))?;
 
         debug!("Test group index");
         pr.set_track_group_name(0, "first group")?;
@@ -586,6 +610,35 @@ fn markers() -> TestStep {
         Ok(())
     })
 }
+fn tempo_markers() -> TestStep {
+    step("Tempo markers", |_| -> TestStepResult {
+        let rpr = Reaper::get();
+        let mut project = rpr.current_project();
+        let mut marker = TempoMarker::new(Position::from(2.0), 90.0);
+        marker.time_signature = Some(TimeSignature::new(3, 4));
+        let idx = project.insert_tempo_marker(marker)?;
+        assert_eq!(project.tempo_marker(idx), Some(marker));
+
+        project.edit_tempo_map("tempo ramp", |editor| {
+            let mut ramp = TempoMarker::new(Position::from(4.0), 120.0);
+            ramp.set_linear(true);
+            editor.insert(ramp)?;
+            marker.bpm = 100.0;
+            editor.set(idx, marker)?;
+            Ok(())
+        })?;
+        let all: Vec<TempoMarker> = project.iter_tempo_markers().collect();
+        assert_eq!(all.len(), project.n_tempo_markers());
+        assert_eq!(all[idx].bpm, 100.0);
+        assert!(all[idx + 1].is_linear());
+        let envelope = project.tempo_envelope().expect("no tempo envelope");
+        assert_eq!(envelope.n_points(), all.len());
+
+        project.edit_tempo_map("clear tempo map", |editor| editor.clear())?;
+        assert_eq!(project.n_tempo_markers(), 0);
+        Ok(())
+    })
+}
 fn tracks() -> TestStep {
     step("Tracks", |_| -> TestStepResult {
         let rpr = Reaper::get();